@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::Arc;
+use csv::WriterBuilder;
+use log::debug;
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use ephemeris::*;
+use time_series::{Store, TickerData, Time};
+use toolkit::{EclipseEvent, EclipseFinder};
+
+/// Daily ecliptic longitude and declination of a planet, as returned by `Query::query`.
+#[derive(Debug, Clone)]
+pub struct PlanetEphemeris {
+  pub planet: Planet,
+  /// Geocentric ecliptic longitude in degrees, the zodiac degree
+  pub longitudes: Vec<(Time, f32)>,
+  pub declinations: Vec<(Time, f32)>,
+}
+
+impl PlanetEphemeris {
+  /// Query Horizons, through `store` if given.
+  pub async fn query(planet: &Planet, start_date: Time, end_date: Time, store: Option<&Store>) -> std::io::Result<Self> {
+    let (longitudes, declinations) = match store {
+      Some(store) => (
+        Query::query_cached(store, Origin::Geocentric, planet, DataType::EclipticLongitude, start_date, end_date).await?,
+        Query::query_cached(store, Origin::Geocentric, planet, DataType::Declination, start_date, end_date).await?,
+      ),
+      None => (
+        Query::query(Origin::Geocentric, planet, DataType::EclipticLongitude, start_date, end_date).await?,
+        Query::query(Origin::Geocentric, planet, DataType::Declination, start_date, end_date).await?,
+      ),
+    };
+    Ok(Self {
+      planet: planet.clone(),
+      longitudes,
+      declinations
+    })
+  }
+
+  /// Degrees moved since the previous day, in (-180, 180], negative while retrograde.
+  /// The first day takes the speed of the second.
+  fn daily_speeds(&self) -> HashMap<i64, f64> {
+    let mut speeds = HashMap::<i64, f64>::new();
+    for pair in self.longitudes.windows(2) {
+      let speed = Self::angle_change(pair[0].1 as f64, pair[1].1 as f64);
+      if speeds.is_empty() {
+        speeds.insert(pair[0].0.to_unix(), speed);
+      }
+      speeds.insert(pair[1].0.to_unix(), speed);
+    }
+    speeds
+  }
+
+  fn angle_change(first: f64, second: f64) -> f64 {
+    let change = (second - first).rem_euclid(360.0);
+    if change > 180.0 { change - 360.0 } else { change }
+  }
+}
+
+/// Values of one feature, aligned with `FeatureTable::dates`. `None` where unavailable,
+/// such as forward returns past the last candle.
+#[derive(Debug, Clone)]
+pub struct FeatureColumn {
+  pub name: String,
+  pub values: Vec<Option<f64>>,
+}
+
+/// Daily modeling table: prices, planet positions, aspects, lunar phase, eclipse distance and forward returns.
+#[derive(Debug, Clone)]
+pub struct FeatureTable {
+  pub dates: Vec<Time>,
+  pub columns: Vec<FeatureColumn>,
+}
+
+impl FeatureTable {
+  /// Query every planet in `planets` over the candles' dates and build the table.
+  /// Eclipses are computed locally, a year either side so the first and last rows have both distances.
+  pub async fn build(
+    ticker_data: &TickerData,
+    planets: &[Planet],
+    forward_returns: &[usize],
+    store: Option<&Store>
+  ) -> std::io::Result<Self> {
+    if ticker_data.candles.is_empty() {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "No candles to build features from"));
+    }
+    let start_date = *ticker_data.earliest_date();
+    let end_date = *ticker_data.latest_date();
+    let mut ephemerides = Vec::<PlanetEphemeris>::new();
+    for planet in planets.iter() {
+      debug!("Querying {} for features", planet.to_str());
+      ephemerides.push(PlanetEphemeris::query(planet, start_date, end_date, store).await?);
+    }
+    let eclipses = EclipseFinder::new(start_date.delta_date(-365), end_date.delta_date(365))?.find();
+    Self::from_ephemerides(ticker_data, &ephemerides, &eclipses, forward_returns)
+  }
+
+  /// One row per candle date, the day's last candle if intraday. Columns, in order:
+  /// - `open`, `high`, `low`, `close`, `volume`
+  /// - per planet: `{planet}_longitude` (ecliptic), `{planet}_declination`, `{planet}_speed` (degrees per day)
+  ///   and `{planet}_retrograde` (1 while speed is negative)
+  /// - per pair of planets: `{a}_{b}_aspect`, their separation in ecliptic longitude in [0, 180] degrees
+  /// - `lunar_phase`: Moon minus Sun in [0, 360), 0 at New Moon, if both are in `ephemerides`
+  /// - `days_since_eclipse`, `days_until_eclipse`: to the nearest eclipse of either kind, 0 on the day
+  /// - per N in `forward_returns`: `forward_return_{N}`, the change in close N rows later
+  pub fn from_ephemerides(
+    ticker_data: &TickerData,
+    ephemerides: &[PlanetEphemeris],
+    eclipses: &[EclipseEvent],
+    forward_returns: &[usize]
+  ) -> std::io::Result<Self> {
+    let mut candles = Vec::<&time_series::Candle>::new();
+    for candle in ticker_data.candles.iter() {
+      match candles.last_mut() {
+        Some(last) if last.date == candle.date => *last = candle,
+        _ => candles.push(candle),
+      }
+    }
+    let dates: Vec<Time> = candles.iter().map(|candle| candle.date).collect();
+    let mut table = Self {
+      dates,
+      columns: Vec::new()
+    };
+
+    table.push("open", candles.iter().map(|candle| Some(candle.open)).collect());
+    table.push("high", candles.iter().map(|candle| Some(candle.high)).collect());
+    table.push("low", candles.iter().map(|candle| Some(candle.low)).collect());
+    table.push("close", candles.iter().map(|candle| Some(candle.close)).collect());
+    table.push("volume", candles.iter().map(|candle| candle.volume).collect());
+
+    let by_date = |series: &[(Time, f32)]| series.iter()
+      .map(|(date, value)| (date.to_unix(), *value as f64))
+      .collect::<HashMap<i64, f64>>();
+    let longitudes: Vec<HashMap<i64, f64>> = ephemerides.iter().map(|ephemeris| by_date(&ephemeris.longitudes)).collect();
+    for (ephemeris, longitude) in ephemerides.iter().zip(longitudes.iter()) {
+      let name = ephemeris.planet.to_str().to_lowercase();
+      let declination = by_date(&ephemeris.declinations);
+      let speed = ephemeris.daily_speeds();
+      table.push(&format!("{}_longitude", name), table.lookup(longitude));
+      table.push(&format!("{}_declination", name), table.lookup(&declination));
+      table.push(&format!("{}_speed", name), table.lookup(&speed));
+      let retrograde = table.lookup(&speed).into_iter()
+        .map(|speed| speed.map(|speed| if speed < 0.0 { 1.0 } else { 0.0 }))
+        .collect();
+      table.push(&format!("{}_retrograde", name), retrograde);
+    }
+
+    for a in 0..ephemerides.len() {
+      for b in (a + 1)..ephemerides.len() {
+        let aspects = table.dates.iter()
+          .map(|date| {
+            let key = date.to_unix();
+            match (longitudes[a].get(&key), longitudes[b].get(&key)) {
+              (Some(first), Some(second)) => Some(PlanetEphemeris::angle_change(*second, *first).abs()),
+              _ => None,
+            }
+          })
+          .collect();
+        let name = format!(
+          "{}_{}_aspect",
+          ephemerides[a].planet.to_str().to_lowercase(),
+          ephemerides[b].planet.to_str().to_lowercase()
+        );
+        table.push(&name, aspects);
+      }
+    }
+
+    let moon = ephemerides.iter().position(|ephemeris| ephemeris.planet == Planet::Moon);
+    let sun = ephemerides.iter().position(|ephemeris| ephemeris.planet == Planet::Sun);
+    if let (Some(moon), Some(sun)) = (moon, sun) {
+      let phases = table.dates.iter()
+        .map(|date| {
+          let key = date.to_unix();
+          match (longitudes[moon].get(&key), longitudes[sun].get(&key)) {
+            (Some(moon), Some(sun)) => Some((moon - sun).rem_euclid(360.0)),
+            _ => None,
+          }
+        })
+        .collect();
+      table.push("lunar_phase", phases);
+    }
+
+    let mut eclipse_dates: Vec<Time> = eclipses.iter().map(|eclipse| eclipse.date).collect();
+    eclipse_dates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let since = table.dates.iter()
+      .map(|date| eclipse_dates.iter().rev().find(|eclipse| *eclipse <= date).map(|eclipse| eclipse.diff_days(date) as f64))
+      .collect();
+    let until = table.dates.iter()
+      .map(|date| eclipse_dates.iter().find(|eclipse| *eclipse >= date).map(|eclipse| date.diff_days(eclipse) as f64))
+      .collect();
+    table.push("days_since_eclipse", since);
+    table.push("days_until_eclipse", until);
+
+    for horizon in forward_returns.iter() {
+      let returns = (0..candles.len())
+        .map(|index| candles.get(index + horizon).map(|future| future.close / candles[index].close - 1.0))
+        .collect();
+      table.push(&format!("forward_return_{}", horizon), returns);
+    }
+    debug!("Built {} features over {} days", table.columns.len(), table.dates.len());
+    Ok(table)
+  }
+
+  fn push(&mut self, name: &str, values: Vec<Option<f64>>) {
+    self.columns.push(FeatureColumn {
+      name: name.to_string(),
+      values
+    });
+  }
+
+  /// Values of a daily series on each row's date.
+  fn lookup(&self, series: &HashMap<i64, f64>) -> Vec<Option<f64>> {
+    self.dates.iter().map(|date| series.get(&date.to_unix()).copied()).collect()
+  }
+
+  pub fn column(&self, name: &str) -> Option<&FeatureColumn> {
+    self.columns.iter().find(|column| column.name == name)
+  }
+
+  pub fn column_names(&self) -> Vec<&str> {
+    self.columns.iter().map(|column| column.name.as_str()).collect()
+  }
+
+  /// `date` then every column, empty where a value is `None`.
+  pub fn write_csv(&self, path: &PathBuf) -> std::io::Result<()> {
+    let mut wtr = WriterBuilder::new().from_path(path)?;
+    let mut header = vec!["date"];
+    header.extend(self.column_names());
+    wtr.write_record(&header)?;
+    for (row, date) in self.dates.iter().enumerate() {
+      let mut record = vec![date.as_string()];
+      record.extend(self.columns.iter().map(|column| column.values[row].map_or(String::new(), |value| value.to_string())));
+      wtr.write_record(&record)?;
+    }
+    wtr.flush()
+  }
+
+  /// `date` as a UTF8 string then every column as an optional double, in one row group.
+  pub fn write_parquet(&self, path: &PathBuf) -> std::io::Result<()> {
+    let parquet_error = |error: parquet::errors::ParquetError| std::io::Error::other(format!("Parquet: {}", error));
+    let fields: Vec<String> = self.columns.iter().map(|column| format!("OPTIONAL DOUBLE {};", column.name)).collect();
+    let message = format!("message features {{ REQUIRED BYTE_ARRAY date (UTF8); {} }}", fields.join(" "));
+    let schema = Arc::new(parse_message_type(&message).map_err(parquet_error)?);
+    let properties = Arc::new(WriterProperties::builder().build());
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, properties).map_err(parquet_error)?;
+
+    let mut row_group = writer.next_row_group().map_err(parquet_error)?;
+    let dates: Vec<ByteArray> = self.dates.iter().map(|date| ByteArray::from(date.as_string().as_str())).collect();
+    if let Some(mut column_writer) = row_group.next_column().map_err(parquet_error)? {
+      column_writer.typed::<ByteArrayType>().write_batch(&dates, None, None).map_err(parquet_error)?;
+      column_writer.close().map_err(parquet_error)?;
+    }
+    for column in self.columns.iter() {
+      let values: Vec<f64> = column.values.iter().flatten().copied().collect();
+      let definition_levels: Vec<i16> = column.values.iter().map(|value| value.is_some() as i16).collect();
+      if let Some(mut column_writer) = row_group.next_column().map_err(parquet_error)? {
+        column_writer.typed::<DoubleType>().write_batch(&values, Some(&definition_levels), None).map_err(parquet_error)?;
+        column_writer.close().map_err(parquet_error)?;
+      }
+    }
+    row_group.close().map_err(parquet_error)?;
+    writer.close().map_err(parquet_error)?;
+    Ok(())
+  }
+}
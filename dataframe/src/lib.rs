@@ -1,2 +1,7 @@
 pub mod dataframe;
-pub use dataframe::*;
\ No newline at end of file
+pub mod features;
+pub mod modeling;
+
+pub use dataframe::*;
+pub use features::*;
+pub use modeling::*;
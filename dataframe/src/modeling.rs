@@ -0,0 +1,662 @@
+use log::debug;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ephemeris::Planet;
+use time_series::{Store, TickerData, Time};
+use toolkit::PERMUTATION_SEED;
+use crate::FeatureTable;
+
+/// Rows of astro features labeled by whether the close is higher `horizon` rows later.
+#[derive(Debug, Clone)]
+pub struct Dataset {
+  pub dates: Vec<Time>,
+  pub feature_names: Vec<String>,
+  /// One row per date, one value per feature name
+  pub features: Vec<Vec<f64>>,
+  pub labels: Vec<bool>,
+  /// Rows each label looks ahead; cross-validation leaves this many rows between train and test
+  pub horizon: usize,
+}
+
+impl Dataset {
+  /// Build the feature table of `ticker_data` and `planets`, then its cyclic dataset.
+  pub async fn build(
+    ticker_data: &TickerData,
+    planets: &[Planet],
+    horizon: usize,
+    store: Option<&Store>
+  ) -> std::io::Result<Self> {
+    let table = FeatureTable::build(ticker_data, planets, &[horizon], store).await?;
+    Self::cyclic(&table, horizon)
+  }
+
+  /// Angles as their sine and cosine, so 359° sits next to 0°:
+  /// `{planet}_longitude`, `{a}_{b}_aspect` and `lunar_phase` become `_sin` and `_cos` features.
+  /// Declination, speed, retrograde and eclipse distances are used as they are; prices are left out.
+  /// The label is `forward_return_{horizon}` > 0, which `table` must have. Rows with a missing value are dropped.
+  pub fn cyclic(table: &FeatureTable, horizon: usize) -> std::io::Result<Self> {
+    let label_name = format!("forward_return_{}", horizon);
+    let returns = table.column(&label_name).ok_or_else(|| std::io::Error::new(
+      std::io::ErrorKind::InvalidInput,
+      format!("Feature table has no {} column", label_name)
+    ))?;
+
+    let mut feature_names = Vec::<String>::new();
+    let mut columns = Vec::<Vec<Option<f64>>>::new();
+    for column in table.columns.iter() {
+      let name = column.name.as_str();
+      if name.ends_with("_longitude") || name.ends_with("_aspect") || name == "lunar_phase" {
+        feature_names.push(format!("{}_sin", name));
+        columns.push(column.values.iter().map(|value| value.map(|degrees| degrees.to_radians().sin())).collect());
+        feature_names.push(format!("{}_cos", name));
+        columns.push(column.values.iter().map(|value| value.map(|degrees| degrees.to_radians().cos())).collect());
+      } else if name.ends_with("_declination")
+        || name.ends_with("_speed")
+        || name.ends_with("_retrograde")
+        || name.starts_with("days_")
+      {
+        feature_names.push(name.to_string());
+        columns.push(column.values.clone());
+      }
+    }
+    if feature_names.is_empty() {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Feature table has no astro features"));
+    }
+
+    let mut dataset = Self {
+      dates: Vec::new(),
+      feature_names,
+      features: Vec::new(),
+      labels: Vec::new(),
+      horizon
+    };
+    for (row, date) in table.dates.iter().enumerate() {
+      let features: Option<Vec<f64>> = columns.iter().map(|column| column[row]).collect();
+      if let (Some(features), Some(forward_return)) = (features, returns.values[row]) {
+        dataset.dates.push(*date);
+        dataset.features.push(features);
+        dataset.labels.push(forward_return > 0.0);
+      }
+    }
+    debug!("{} rows of {} features to predict {}", dataset.len(), dataset.feature_names.len(), label_name);
+    Ok(dataset)
+  }
+
+  pub fn len(&self) -> usize {
+    self.labels.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.labels.is_empty()
+  }
+
+  /// Fraction of rows labeled up.
+  pub fn base_rate(&self) -> f64 {
+    if self.is_empty() {
+      return 0.0;
+    }
+    self.labels.iter().filter(|label| **label).count() as f64 / self.len() as f64
+  }
+}
+
+/// Classifier and its training settings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelKind {
+  /// Gradient descent on standardized features with an L2 penalty
+  LogisticRegression {
+    epochs: usize,
+    learning_rate: f64,
+    l2: f64,
+  },
+  /// Newton-boosted regression trees on log loss, split on quantile bins of each feature
+  GradientBoostedTrees {
+    trees: usize,
+    max_depth: usize,
+    learning_rate: f64,
+    min_samples_leaf: usize,
+  },
+}
+
+impl ModelKind {
+  pub fn logistic_regression() -> Self {
+    Self::LogisticRegression {
+      epochs: 200,
+      learning_rate: 0.1,
+      l2: 0.01,
+    }
+  }
+
+  pub fn gradient_boosted_trees() -> Self {
+    Self::GradientBoostedTrees {
+      trees: 50,
+      max_depth: 3,
+      learning_rate: 0.1,
+      min_samples_leaf: 50,
+    }
+  }
+
+  pub fn as_string(&self) -> String {
+    match self {
+      Self::LogisticRegression { .. } => "Logistic Regression".to_string(),
+      Self::GradientBoostedTrees { .. } => "Gradient Boosted Trees".to_string(),
+    }
+  }
+
+  /// Train on `rows` of `dataset`.
+  pub fn fit(&self, dataset: &Dataset, rows: &[usize]) -> Model {
+    self.fit_labels(dataset, &dataset.labels, rows)
+  }
+
+  fn fit_labels(&self, dataset: &Dataset, labels: &[bool], rows: &[usize]) -> Model {
+    match self {
+      Self::LogisticRegression { epochs, learning_rate, l2 } => Model::LogisticRegression(
+        LogisticRegression::fit(dataset, labels, rows, *epochs, *learning_rate, *l2)
+      ),
+      Self::GradientBoostedTrees { trees, max_depth, learning_rate, min_samples_leaf } => Model::GradientBoostedTrees(
+        GradientBoostedTrees::fit(dataset, labels, rows, *trees, *max_depth, *learning_rate, *min_samples_leaf)
+      ),
+    }
+  }
+}
+
+/// A trained `ModelKind`.
+#[derive(Debug, Clone)]
+pub enum Model {
+  LogisticRegression(LogisticRegression),
+  GradientBoostedTrees(GradientBoostedTrees),
+}
+
+impl Model {
+  /// Probability the label of a row with `features` is up.
+  pub fn predict(&self, features: &[f64]) -> f64 {
+    match self {
+      Self::LogisticRegression(model) => model.predict(features),
+      Self::GradientBoostedTrees(model) => model.predict(features),
+    }
+  }
+
+  /// Share of the model each feature accounts for, summing to 1:
+  /// absolute standardized weight for logistic regression, total split gain for trees.
+  pub fn feature_importance(&self) -> Vec<f64> {
+    let importance = match self {
+      Self::LogisticRegression(model) => model.weights.iter().map(|weight| weight.abs()).collect(),
+      Self::GradientBoostedTrees(model) => model.gains.clone(),
+    };
+    let total: f64 = importance.iter().sum();
+    if total > 0.0 {
+      importance.iter().map(|value| value / total).collect()
+    } else {
+      importance
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogisticRegression {
+  /// Training mean and standard deviation of each feature
+  pub means: Vec<f64>,
+  pub scales: Vec<f64>,
+  /// Weight of each standardized feature
+  pub weights: Vec<f64>,
+  pub bias: f64,
+}
+
+impl LogisticRegression {
+  fn fit(dataset: &Dataset, labels: &[bool], rows: &[usize], epochs: usize, learning_rate: f64, l2: f64) -> Self {
+    let count = dataset.feature_names.len();
+    let n = rows.len().max(1) as f64;
+    let mut means = vec![0.0; count];
+    let mut scales = vec![0.0; count];
+    for row in rows.iter() {
+      for (mean, value) in means.iter_mut().zip(dataset.features[*row].iter()) {
+        *mean += value / n;
+      }
+    }
+    for row in rows.iter() {
+      for ((scale, mean), value) in scales.iter_mut().zip(means.iter()).zip(dataset.features[*row].iter()) {
+        *scale += (value - mean).powi(2) / n;
+      }
+    }
+    // constant features stay at 0 once standardized
+    let scales = scales.into_iter().map(|variance| if variance > 0.0 { variance.sqrt() } else { 1.0 }).collect();
+
+    let mut model = Self {
+      means,
+      scales,
+      weights: vec![0.0; count],
+      bias: 0.0
+    };
+    let standardized: Vec<Vec<f64>> = rows.iter().map(|row| model.standardize(&dataset.features[*row])).collect();
+    for _ in 0..epochs {
+      let mut weight_gradients = vec![0.0; count];
+      let mut bias_gradient = 0.0;
+      for (features, row) in standardized.iter().zip(rows.iter()) {
+        let error = model.probability(features) - labels[*row] as u8 as f64;
+        for (gradient, value) in weight_gradients.iter_mut().zip(features.iter()) {
+          *gradient += error * value / n;
+        }
+        bias_gradient += error / n;
+      }
+      for (weight, gradient) in model.weights.iter_mut().zip(weight_gradients.iter()) {
+        *weight -= learning_rate * (gradient + l2 * *weight);
+      }
+      model.bias -= learning_rate * bias_gradient;
+    }
+    model
+  }
+
+  fn standardize(&self, features: &[f64]) -> Vec<f64> {
+    features.iter().zip(self.means.iter()).zip(self.scales.iter())
+      .map(|((value, mean), scale)| (value - mean) / scale)
+      .collect()
+  }
+
+  fn probability(&self, standardized: &[f64]) -> f64 {
+    let logit: f64 = self.bias + self.weights.iter().zip(standardized.iter()).map(|(weight, value)| weight * value).sum::<f64>();
+    sigmoid(logit)
+  }
+
+  pub fn predict(&self, features: &[f64]) -> f64 {
+    self.probability(&self.standardize(features))
+  }
+}
+
+/// Quantile bins each feature is split on while training.
+const TREE_BINS: usize = 32;
+/// L2 penalty on leaf values, so small leaves don't jump to extreme log odds.
+const TREE_LAMBDA: f64 = 1.0;
+
+#[derive(Debug, Clone)]
+enum TreeNode {
+  /// Rows with `feature` <= `threshold` go to `left`
+  Split {
+    feature: usize,
+    threshold: f64,
+    left: usize,
+    right: usize,
+  },
+  /// Log odds added by the tree, already scaled by the learning rate
+  Leaf(f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct GradientBoostedTrees {
+  /// Log odds of the training base rate
+  pub base_score: f64,
+  /// Total loss reduction of the splits on each feature
+  pub gains: Vec<f64>,
+  /// Nodes of each tree, the root first
+  trees: Vec<Vec<TreeNode>>,
+}
+
+/// Training state shared while growing one tree.
+struct TreeBuilder<'a> {
+  bins: &'a [Vec<u8>],
+  thresholds: &'a [Vec<f64>],
+  gradients: &'a [f64],
+  hessians: &'a [f64],
+  max_depth: usize,
+  learning_rate: f64,
+  min_samples_leaf: usize,
+}
+
+impl GradientBoostedTrees {
+  fn fit(
+    dataset: &Dataset,
+    labels: &[bool],
+    rows: &[usize],
+    trees: usize,
+    max_depth: usize,
+    learning_rate: f64,
+    min_samples_leaf: usize
+  ) -> Self {
+    let count = dataset.feature_names.len();
+    let targets: Vec<f64> = rows.iter().map(|row| labels[*row] as u8 as f64).collect();
+    let base_rate = (targets.iter().sum::<f64>() / targets.len().max(1) as f64).clamp(1e-6, 1.0 - 1e-6);
+
+    // upper edge of each bin, from quantiles of the training rows
+    let thresholds: Vec<Vec<f64>> = (0..count)
+      .map(|feature| {
+        let mut values: Vec<f64> = rows.iter().map(|row| dataset.features[*row][feature]).collect();
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut edges: Vec<f64> = (1..TREE_BINS)
+          .filter_map(|bin| values.get(bin * values.len() / TREE_BINS).copied())
+          .collect();
+        edges.dedup();
+        edges
+      })
+      .collect();
+    let bins: Vec<Vec<u8>> = rows.iter()
+      .map(|row| dataset.features[*row].iter().zip(thresholds.iter())
+        .map(|(value, edges)| edges.partition_point(|edge| edge < value) as u8)
+        .collect())
+      .collect();
+
+    let mut model = Self {
+      base_score: (base_rate / (1.0 - base_rate)).ln(),
+      gains: vec![0.0; count],
+      trees: Vec::new()
+    };
+    let mut scores = vec![model.base_score; rows.len()];
+    let all: Vec<usize> = (0..rows.len()).collect();
+    for _ in 0..trees {
+      let probabilities: Vec<f64> = scores.iter().map(|score| sigmoid(*score)).collect();
+      let gradients: Vec<f64> = probabilities.iter().zip(targets.iter()).map(|(p, y)| p - y).collect();
+      let hessians: Vec<f64> = probabilities.iter().map(|p| (p * (1.0 - p)).max(1e-12)).collect();
+      let builder = TreeBuilder {
+        bins: &bins,
+        thresholds: &thresholds,
+        gradients: &gradients,
+        hessians: &hessians,
+        max_depth,
+        learning_rate,
+        min_samples_leaf: min_samples_leaf.max(1)
+      };
+      let mut nodes = Vec::<TreeNode>::new();
+      builder.grow(&all, 0, &mut nodes, &mut model.gains);
+      for (index, row) in rows.iter().enumerate() {
+        scores[index] += Self::tree_output(&nodes, &dataset.features[*row]);
+      }
+      model.trees.push(nodes);
+    }
+    model
+  }
+
+  fn tree_output(nodes: &[TreeNode], features: &[f64]) -> f64 {
+    let mut index = 0;
+    loop {
+      match &nodes[index] {
+        TreeNode::Split { feature, threshold, left, right } => {
+          index = if features[*feature] <= *threshold { *left } else { *right };
+        },
+        TreeNode::Leaf(value) => return *value,
+      }
+    }
+  }
+
+  pub fn predict(&self, features: &[f64]) -> f64 {
+    sigmoid(self.base_score + self.trees.iter().map(|nodes| Self::tree_output(nodes, features)).sum::<f64>())
+  }
+}
+
+impl TreeBuilder<'_> {
+  /// Append the subtree over `rows` (indices into the training rows) to `nodes` and return its index.
+  fn grow(&self, rows: &[usize], depth: usize, nodes: &mut Vec<TreeNode>, gains: &mut [f64]) -> usize {
+    let gradient: f64 = rows.iter().map(|row| self.gradients[*row]).sum();
+    let hessian: f64 = rows.iter().map(|row| self.hessians[*row]).sum();
+    let index = nodes.len();
+    nodes.push(TreeNode::Leaf(-self.learning_rate * gradient / (hessian + TREE_LAMBDA)));
+    if depth >= self.max_depth || rows.len() < 2 * self.min_samples_leaf {
+      return index;
+    }
+
+    let parent_score = gradient * gradient / (hessian + TREE_LAMBDA);
+    // (gain, feature, bin): rows in bins <= bin go left
+    let mut best: Option<(f64, usize, usize)> = None;
+    for (feature, edges) in self.thresholds.iter().enumerate() {
+      if edges.is_empty() {
+        continue;
+      }
+      let mut histogram = vec![(0.0, 0.0, 0usize); edges.len() + 1];
+      for row in rows.iter() {
+        let bin = &mut histogram[self.bins[*row][feature] as usize];
+        bin.0 += self.gradients[*row];
+        bin.1 += self.hessians[*row];
+        bin.2 += 1;
+      }
+      let (mut left_gradient, mut left_hessian, mut left_count) = (0.0, 0.0, 0);
+      for (bin, (bin_gradient, bin_hessian, bin_count)) in histogram.iter().enumerate().take(edges.len()) {
+        left_gradient += bin_gradient;
+        left_hessian += bin_hessian;
+        left_count += bin_count;
+        let right_count = rows.len() - left_count;
+        if left_count < self.min_samples_leaf || right_count < self.min_samples_leaf {
+          continue;
+        }
+        let right_gradient = gradient - left_gradient;
+        let right_hessian = hessian - left_hessian;
+        let gain = left_gradient * left_gradient / (left_hessian + TREE_LAMBDA)
+          + right_gradient * right_gradient / (right_hessian + TREE_LAMBDA)
+          - parent_score;
+        if gain > best.map_or(0.0, |(best_gain, _, _)| best_gain) {
+          best = Some((gain, feature, bin));
+        }
+      }
+    }
+
+    if let Some((gain, feature, bin)) = best {
+      gains[feature] += gain;
+      let (left_rows, right_rows): (Vec<usize>, Vec<usize>) = rows.iter()
+        .partition(|row| self.bins[**row][feature] as usize <= bin);
+      let left = self.grow(&left_rows, depth + 1, nodes, gains);
+      let right = self.grow(&right_rows, depth + 1, nodes, gains);
+      nodes[index] = TreeNode::Split {
+        feature,
+        threshold: self.thresholds[feature][bin],
+        left,
+        right
+      };
+    }
+    index
+  }
+}
+
+fn sigmoid(logit: f64) -> f64 {
+  1.0 / (1.0 + (-logit).exp())
+}
+
+/// Walk-forward cross-validation: the rows are cut into `folds` + 1 consecutive blocks and each fold
+/// tests one block after training on every row before it, less the dataset's `horizon` so no training
+/// label looks into the test block.
+#[derive(Debug, Clone)]
+pub struct CrossValidation {
+  pub folds: usize,
+  /// Times the labels are circularly shifted and the cross-validation rerun, for the chance baseline.
+  /// Shifting keeps the autocorrelation of overlapping forward returns, which an i.i.d. shuffle would break.
+  pub permutations: usize,
+  pub seed: u64,
+}
+
+impl Default for CrossValidation {
+  fn default() -> Self {
+    Self {
+      folds: 5,
+      permutations: 20,
+      seed: PERMUTATION_SEED,
+    }
+  }
+}
+
+/// Out-of-sample scores of one fold.
+#[derive(Debug, Clone)]
+pub struct FoldScore {
+  pub train_rows: usize,
+  pub test_start: Time,
+  pub test_end: Time,
+  pub test_rows: usize,
+  /// Fraction of up labels in the test block, the accuracy of always guessing up
+  pub base_rate: f64,
+  /// Predicting up above a probability of 0.5
+  pub accuracy: f64,
+  /// Area under the ROC curve, 0.5 for no skill
+  pub auc: f64,
+  pub log_loss: f64,
+}
+
+/// Cross-validated skill of a model compared to the same model trained on circularly shifted labels.
+#[derive(Debug, Clone)]
+pub struct ModelEvaluation {
+  pub model: String,
+  pub folds: Vec<FoldScore>,
+  pub mean_accuracy: f64,
+  pub mean_auc: f64,
+  pub mean_log_loss: f64,
+  /// Mean AUC of each shifted-label rerun
+  pub shifted_aucs: Vec<f64>,
+  /// Fraction of shifted-label reruns (counting the real one) with a mean AUC at least `mean_auc`
+  pub p_value: f64,
+  /// Feature names and importance of the model trained on every row, most important first
+  pub feature_importance: Vec<(String, f64)>,
+}
+
+impl CrossValidation {
+  /// Train and test rows of each fold.
+  pub fn splits(&self, dataset: &Dataset) -> Vec<(Vec<usize>, Vec<usize>)> {
+    let blocks = self.folds + 1;
+    let block_size = dataset.len() / blocks;
+    let mut splits = Vec::new();
+    if block_size == 0 {
+      return splits;
+    }
+    for fold in 1..blocks {
+      let test_start = fold * block_size;
+      let test_end = if fold == self.folds { dataset.len() } else { test_start + block_size };
+      let train_end = test_start.saturating_sub(dataset.horizon);
+      if train_end == 0 {
+        continue;
+      }
+      splits.push(((0..train_end).collect(), (test_start..test_end).collect()));
+    }
+    splits
+  }
+
+  pub fn evaluate(&self, dataset: &Dataset, kind: &ModelKind) -> std::io::Result<ModelEvaluation> {
+    let splits = self.splits(dataset);
+    if splits.is_empty() {
+      return Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("{} rows are too few for {} folds", dataset.len(), self.folds)
+      ));
+    }
+    let folds = Self::score_folds(dataset, &dataset.labels, kind, &splits);
+    let mean = |score: fn(&FoldScore) -> f64| folds.iter().map(score).sum::<f64>() / folds.len() as f64;
+    let mean_auc = mean(|fold| fold.auc);
+
+    // shift by at least the horizon, so no row keeps a label overlapping its own
+    let min_shift = dataset.horizon.max(1);
+    let mut rng = StdRng::seed_from_u64(self.seed);
+    let mut shifted_aucs = Vec::<f64>::new();
+    for permutation in 0..self.permutations {
+      let shift = if dataset.len() > 2 * min_shift {
+        rng.gen_range(min_shift..=dataset.len() - min_shift)
+      } else {
+        rng.gen_range(0..dataset.len())
+      };
+      let mut labels = dataset.labels.clone();
+      labels.rotate_right(shift);
+      let shifted = Self::score_folds(dataset, &labels, kind, &splits);
+      shifted_aucs.push(shifted.iter().map(|fold| fold.auc).sum::<f64>() / shifted.len() as f64);
+      debug!("{} labels shifted {} rows: AUC {:.4}", kind.as_string(), shift, shifted_aucs[permutation]);
+    }
+    let as_good = shifted_aucs.iter().filter(|auc| **auc >= mean_auc).count();
+
+    let all: Vec<usize> = (0..dataset.len()).collect();
+    let mut feature_importance: Vec<(String, f64)> = dataset.feature_names.iter().cloned()
+      .zip(kind.fit(dataset, &all).feature_importance())
+      .collect();
+    feature_importance.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+    Ok(ModelEvaluation {
+      model: kind.as_string(),
+      mean_accuracy: mean(|fold| fold.accuracy),
+      mean_auc,
+      mean_log_loss: mean(|fold| fold.log_loss),
+      folds,
+      p_value: (as_good + 1) as f64 / (self.permutations + 1) as f64,
+      shifted_aucs,
+      feature_importance
+    })
+  }
+
+  fn score_folds(dataset: &Dataset, labels: &[bool], kind: &ModelKind, splits: &[(Vec<usize>, Vec<usize>)]) -> Vec<FoldScore> {
+    splits.iter()
+      .map(|(train, test)| {
+        let model = kind.fit_labels(dataset, labels, train);
+        let probabilities: Vec<f64> = test.iter().map(|row| model.predict(&dataset.features[*row])).collect();
+        let outcomes: Vec<bool> = test.iter().map(|row| labels[*row]).collect();
+        let n = test.len() as f64;
+        FoldScore {
+          train_rows: train.len(),
+          test_start: dataset.dates[test[0]],
+          test_end: dataset.dates[test[test.len() - 1]],
+          test_rows: test.len(),
+          base_rate: outcomes.iter().filter(|up| **up).count() as f64 / n,
+          accuracy: probabilities.iter().zip(outcomes.iter()).filter(|(p, up)| (**p > 0.5) == **up).count() as f64 / n,
+          auc: auc(&probabilities, &outcomes),
+          log_loss: probabilities.iter().zip(outcomes.iter())
+            .map(|(p, up)| {
+              let p = p.clamp(1e-12, 1.0 - 1e-12);
+              if *up { -p.ln() } else { -(1.0 - p).ln() }
+            })
+            .sum::<f64>() / n,
+        }
+      })
+      .collect()
+  }
+}
+
+/// Probability a random up row scores above a random down row, ties counting half.
+fn auc(scores: &[f64], labels: &[bool]) -> f64 {
+  let mut order: Vec<usize> = (0..scores.len()).collect();
+  order.sort_by(|a, b| scores[*a].partial_cmp(&scores[*b]).unwrap());
+  let mut ranks = vec![0.0; scores.len()];
+  let mut start = 0;
+  while start < order.len() {
+    let mut end = start;
+    while end + 1 < order.len() && scores[order[end + 1]] == scores[order[start]] {
+      end += 1;
+    }
+    // ranks start at 1, tied scores share the average
+    let rank = (start + end) as f64 / 2.0 + 1.0;
+    for index in order[start..=end].iter() {
+      ranks[*index] = rank;
+    }
+    start = end + 1;
+  }
+  let positives = labels.iter().filter(|label| **label).count() as f64;
+  let negatives = labels.len() as f64 - positives;
+  if positives == 0.0 || negatives == 0.0 {
+    return 0.5;
+  }
+  let positive_ranks: f64 = ranks.iter().zip(labels.iter()).filter(|(_, label)| **label).map(|(rank, _)| rank).sum();
+  (positive_ranks - positives * (positives + 1.0) / 2.0) / (positives * negatives)
+}
+
+impl ModelEvaluation {
+  /// Folds, then the summary against shifted labels, then the `top_features` most important features.
+  pub fn print(&self, top_features: usize) {
+    println!("{}", self.model);
+    println!("TEST START\tTEST END\tTRAIN\tTEST\tBASE RATE\tACCURACY\tAUC\tLOG LOSS");
+    for fold in self.folds.iter() {
+      println!(
+        "{}\t{}\t{}\t{}\t{:.1}%\t\t{:.1}%\t\t{:.4}\t{:.4}",
+        fold.test_start.as_string(),
+        fold.test_end.as_string(),
+        fold.train_rows,
+        fold.test_rows,
+        fold.base_rate * 100.0,
+        fold.accuracy * 100.0,
+        fold.auc,
+        fold.log_loss
+      );
+    }
+    let shifted_mean = if self.shifted_aucs.is_empty() {
+      "-".to_string()
+    } else {
+      format!("{:.4}", self.shifted_aucs.iter().sum::<f64>() / self.shifted_aucs.len() as f64)
+    };
+    println!("ACCURACY\tAUC\tLOG LOSS\tSHIFTED AUC\tP-VALUE");
+    println!(
+      "{:.1}%\t\t{:.4}\t{:.4}\t\t{}\t\t{:.4}",
+      self.mean_accuracy * 100.0,
+      self.mean_auc,
+      self.mean_log_loss,
+      shifted_mean,
+      self.p_value
+    );
+    println!("FEATURE\t\t\tIMPORTANCE");
+    for (name, importance) in self.feature_importance.iter().take(top_features) {
+      println!("{}\t\t{:.2}%", name, importance * 100.0);
+    }
+  }
+}
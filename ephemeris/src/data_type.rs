@@ -2,6 +2,18 @@
 pub enum DataType {
   RightAscension,
   Declination,
+  /// Ecliptic longitude converted from right ascension and declination, the zodiac degree
+  EclipticLongitude,
+}
+
+impl DataType {
+  pub fn as_string(&self) -> &str {
+    match self {
+      DataType::RightAscension => "RightAscension",
+      DataType::Declination => "Declination",
+      DataType::EclipticLongitude => "EclipticLongitude",
+    }
+  }
 }
 
 pub struct RightAscension {
@@ -42,4 +54,22 @@ impl Declination {
       degrees * -1.0
     }
   }
-}
\ No newline at end of file
+}
+
+/// Obliquity of the ecliptic at J2000, the frame Horizons reports astrometric positions in
+pub const OBLIQUITY_J2000: f64 = 23.439_291_1;
+
+pub struct EclipticLongitude;
+
+impl EclipticLongitude {
+  /// Ecliptic longitude in [0, 360) from right ascension and declination in degrees.
+  pub fn from_equatorial(right_ascension: f32, declination: f32) -> f32 {
+    let (ra, dec, obliquity) = (
+      (right_ascension as f64).to_radians(),
+      (declination as f64).to_radians(),
+      OBLIQUITY_J2000.to_radians()
+    );
+    let longitude = (ra.sin() * obliquity.cos() + dec.tan() * obliquity.sin()).atan2(ra.cos());
+    longitude.to_degrees().rem_euclid(360.0) as f32
+  }
+}
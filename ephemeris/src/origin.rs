@@ -6,6 +6,13 @@ pub enum Origin {
 }
 
 impl Origin {
+  pub fn as_string(&self) -> &str {
+    match self {
+      Origin::Geocentric => "Geocentric",
+      Origin::Heliocentric => "Heliocentric",
+    }
+  }
+
   pub fn as_query(&self) -> &str {
     match self {
       Origin::Geocentric => "&CENTER='500@399'",
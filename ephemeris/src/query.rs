@@ -1,9 +1,11 @@
 use std::io::Error;
-use crate::{Alignment, DataType, Declination, Origin, Planet, RightAscension};
+use log::debug;
+use crate::{Alignment, DataType, Declination, EclipticLongitude, Origin, Planet, RightAscension};
 use crate::target::Target;
 use crate::quantities::Quantities;
 use crate::step_size::StepSize;
 use time_series::time::Time;
+use time_series::Store;
 
 pub const BASE_QUERY: &str = "https://ssd.jpl.nasa.gov/api/horizons.api?format=text";
 
@@ -44,9 +46,30 @@ impl Query {
     match data_type {
       DataType::RightAscension => Ok(Self::format_for_right_ascension(data)),
       DataType::Declination => Ok(Self::format_for_declination(data)),
+      DataType::EclipticLongitude => Ok(Self::format_for_ecliptic_longitude(data)),
     }
   }
 
+  /// `query`, reading from `store` if it has every day of the period and saving to it otherwise.
+  pub async fn query_cached(
+    store: &Store,
+    origin: Origin,
+    planet: &Planet,
+    data_type: DataType,
+    start_time: Time,
+    stop_time: Time,
+  ) -> Result<Vec<(Time, f32)>, Error> {
+    let stored = store.load_positions(planet.to_str(), origin.as_string(), data_type.as_string(), &start_time, &stop_time)?;
+    if stored.len() as i64 == start_time.diff_days(&stop_time) + 1 {
+      debug!("{} {} {} read from store", origin.as_string(), planet.to_str(), data_type.as_string());
+      return Ok(stored);
+    }
+    let coordinate = data_type.as_string().to_string();
+    let positions = Self::query(origin, planet, data_type, start_time, stop_time).await?;
+    store.upsert_positions(planet.to_str(), origin.as_string(), &coordinate, &positions)?;
+    Ok(positions)
+  }
+
   /// Construct a query to interact with the 'Horizon API'
   fn build_query(
     command: Target,
@@ -147,6 +170,14 @@ impl Query {
     vec
   }
 
+  /// Format API response of into vector of (Time, ecliptic longitude as degrees)
+  pub fn format_for_ecliptic_longitude(data: String) -> Vec<(Time, f32)> {
+    Self::format_for_right_ascension(data.clone()).into_iter()
+      .zip(Self::format_for_declination(data))
+      .map(|((time, ra_degrees), (_, declination))| (time, EclipticLongitude::from_equatorial(ra_degrees, declination)))
+      .collect()
+  }
+
   /// Finds duplicate Alignments on consecutive dates where f32 is within margin of error
   /// filters for the date with f32 closest to the actual Alignment angle
   pub fn remove_duplicate_values(vec: &mut Vec<(Time, f32, Alignment)>) -> Vec<(Time, f32, Alignment)> {
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 
 #[derive(Clone, Debug)]
 pub struct Target {
@@ -13,7 +15,7 @@ impl Target {
 }
 
 #[allow(dead_code)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum Planet {
   Moon,
   Sun,
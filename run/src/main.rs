@@ -22,7 +22,7 @@ async fn main() {
 
   // stream real-time data from RapidAPI to TickerData
   let rapid_api = RapidApi::new("SPX".to_string());
-  let candles = rapid_api.query(Interval::Daily).await;
+  let candles = rapid_api.query(Interval::Daily).await.expect("Failed to query RapidApi");
   ticker_data.add_series(candles).expect("Failed to add API series to TickerData");
   // write full ticker_data history to CSV
   dataframe::ticker_dataframe(&ticker_data, &PathBuf::from("./SPX/SPX_history.csv"));
@@ -125,7 +125,7 @@ pub fn init_logger() {
 //   &Planet::to_vec(),
 // ).await.unwrap();
 // retrograde.print();
-// // retrograde.backtest(10, 1.0, 2).await.unwrap();
+// retrograde.backtest(&ticker_data, &SwingDetector::Window(10), 1.0, Tolerance::Days(2)).unwrap();
 // retrograde.confluent_retrograde(2);
 
 // println!("----------------------------------------------------------------------------------------");
@@ -147,3 +147,310 @@ pub fn init_logger() {
 //   vec![Planet::Sun, Planet::Jupiter, Planet::Saturn, Planet::Uranus, Planet::Neptune, Planet::Pluto],
 //   vec![Alignment::Conjunct, Alignment::Opposite, Alignment::Square90, Alignment::Square270, Alignment::Trine120, Alignment::Trine240],
 // ).await.unwrap();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### ECLIPSES FROM EPHEMERIS ###\t\t");
+// let computed_eclipses = Eclipses::from_ephemeris(
+//   Time::new(1900, &Month::from_num(1), &Day::from_num(1)),
+//   Time::new(2100, &Month::from_num(1), &Day::from_num(1)),
+// ).unwrap();
+// let catalog_eclipses = Eclipses::new(
+//   &PathBuf::from(SOLAR_ECLIPSE_CSV),
+//   &PathBuf::from(LUNAR_ECLIPSE_CSV)
+// );
+// computed_eclipses.cross_check(&catalog_eclipses, 1).print();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### SAROS ECHO ###\t\t");
+// let eclipses = Eclipses::new(
+//   &PathBuf::from(SOLAR_ECLIPSE_CSV),
+//   &PathBuf::from(LUNAR_ECLIPSE_CSV)
+// );
+// eclipses.test_saros_echo(&ticker_data, &SwingDetector::Window(20), Tolerance::Sessions(3, TradingCalendar::Nyse));
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### ECLIPSE DEGREE TRANSITS ###\t\t");
+// let eclipse_degrees = EclipseDegrees::new(
+//   &eclipses,
+//   Time::new(2014, &Month::from_num(8), &Day::from_num(1)),
+//   Time::new(2023, &Month::from_num(3), &Day::from_num(1)),
+//   1.0,
+//   &[Planet::Mars, Planet::Jupiter, Planet::Saturn],
+//   &[Alignment::Conjunct, Alignment::Opposite, Alignment::Square90, Alignment::Square270],
+// ).await.unwrap();
+// eclipse_degrees.print();
+// eclipse_degrees.backtest(
+//   &ticker_data.find_reversals(20),
+//   Tolerance::Days(2),
+//   &[Planet::Mars, Planet::Jupiter, Planet::Saturn]
+// );
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### ECLIPSE SIGNIFICANCE ###\t\t");
+// let signals = eclipses.test_confluence(
+//   Time::new(2013, &Month::from_num(1), &Day::from_num(1)),
+//   Time::new(2023, &Month::from_num(3), &Day::from_num(1)),
+//   3,
+//   1.0,
+//   vec![Planet::Sun, Planet::Jupiter, Planet::Saturn],
+//   vec![Alignment::Conjunct, Alignment::Opposite, Alignment::Square90, Alignment::Square270],
+// ).await.unwrap();
+// Eclipses::test_confluence_significance(
+//   &signals,
+//   &ticker_data.find_reversals(20),
+//   Time::new(2013, &Month::from_num(1), &Day::from_num(1)),
+//   Time::new(2023, &Month::from_num(3), &Day::from_num(1)),
+//   Tolerance::Sessions(3, TradingCalendar::Nyse)
+// );
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### EVENT STUDY ###\t\t");
+// let retrograde_stations: Vec<Time> = retrograde.retrogrades.iter().map(|event| event.start_date).collect();
+// let event_study = EventStudy::new(&ticker_data, &retrograde_stations, 20, 40);
+// event_study.print();
+// event_study.plot("./SPX/SPX_retrograde_event_study.png", "SPX - Retrograde Stations", &BLUE);
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### TRADING SIMULATION ###\t\t");
+// let signals = TradeSignal::from_dates(&retrograde_stations, Side::Short);
+// let strategy = Strategy {
+//   exit_rule: ExitRule::HoldCandles(20),
+//   stop: Some(PriceOffset::Atr { period: 14, multiple: 2.0 }),
+//   target: Some(PriceOffset::Percent(10.0)),
+//   commission_percent: 0.05,
+//   slippage_percent: 0.02,
+//   ..Default::default()
+// };
+// let simulation = Simulation::run(&ticker_data, &signals, &strategy);
+// simulation.print();
+// simulation.print_trades();
+// simulation.write_trades_csv(&PathBuf::from("./SPX/SPX_retrograde_trades.csv")).unwrap();
+// simulation.plot_equity("./SPX/SPX_retrograde_equity.png", "SPX - Retrograde Stations", &BLUE);
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### WALK FORWARD ###\t\t");
+// // reversal definition (candle_range) chosen on 4 years of history, traded on the next year.
+// // `find_reversals` looks `candle_range` candles ahead, so each signal waits until the reversal is confirmed
+// let walk_forward = WalkForward::run(
+//   &ticker_data,
+//   &[5, 10, 20, 40],
+//   |data, candle_range| TradeSignal::from_confirmed_reversals(data, &data.find_reversals(*candle_range), *candle_range),
+//   &Strategy::default(),
+//   &WalkForwardConfig {
+//     train_candles: 252 * 4,
+//     test_candles: 252,
+//     mode: WindowMode::Rolling,
+//     objective: Objective::Sharpe,
+//   }
+// ).unwrap();
+// walk_forward.print();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### PARAMETER GRID SEARCH ###\t\t");
+// let optimizer = SignalOptimizer::new(
+//   *ticker_data.earliest_date(),
+//   *ticker_data.latest_date(),
+//   &[Planet::Sun, Planet::Mars, Planet::Jupiter, Planet::Saturn]
+// ).await.unwrap();
+// let planet_matrix_grid = optimizer.optimize_planet_matrix(
+//   &ticker_data,
+//   &[0.5, 1.0, 2.0, 3.0],
+//   &[1, 2, 3, 5],
+//   &[SwingDetector::Window(10), SwingDetector::Window(20), SwingDetector::ZigZag(10.0), SwingDetector::Atr { period: 14, multiple: 3.0 }],
+//   &[Alignment::Conjunct, Alignment::Opposite, Alignment::Square90, Alignment::Square270]
+// );
+// planet_matrix_grid.print(20);
+// planet_matrix_grid.plot_heatmap("orb", "error_margin_days", "./SPX/SPX_planet_matrix_grid.png", "SPX - Planet Matrix Lift").unwrap();
+// // README "Price-Planet" tool: scale SquareOfNine for the highest lift
+// let square_of_nine_grid = optimizer.optimize_square_of_nine(
+//   &ticker_data,
+//   &[0.1, 0.5, 1.0, 5.0, 10.0, 25.0],
+//   &[0.5, 1.0, 2.0, 4.0],
+//   Tolerance::Days(2),
+//   &SwingDetector::Window(20)
+// );
+// square_of_nine_grid.print(20);
+// square_of_nine_grid.plot_heatmap("step", "orb", "./SPX/SPX_square_of_nine_grid.png", "SPX - Square of Nine Scale Lift").unwrap();
+// // retrograde stations and declination equator crosses over the same period
+// let retrograde = Retrograde::new(*ticker_data.earliest_date(), *ticker_data.latest_date(), &vec![Planet::Mars, Planet::Jupiter, Planet::Saturn]).await.unwrap();
+// let retrograde_grid = optimizer.optimize_retrograde(
+//   &ticker_data,
+//   &retrograde,
+//   &[0.1, 0.25, 0.5, 1.0],
+//   &[1, 2, 3, 5],
+//   &[SwingDetector::Window(10), SwingDetector::Window(20)]
+// );
+// retrograde_grid.print(20);
+// let equator_crosses = PlanetEquatorCrosses::new(*ticker_data.earliest_date(), *ticker_data.latest_date()).await;
+// optimizer.optimize_equator_crosses(&ticker_data, &equator_crosses, &[1, 2, 3, 5], &[SwingDetector::Window(10), SwingDetector::Window(20)]).print(20);
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### FORECAST PRECISION/RECALL ###\t\t");
+// let forecasts = Forecast::from_predictions(&ticker_data.square_price_reversals(&SwingDetector::Window(20)));
+// ForecastEvaluation::new(&forecasts, &ticker_data.find_reversals(20), 3).print();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### SWINGS ###\t\t");
+// // any backtest taking a `&SwingDetector` can use these instead of `SwingDetector::Window`
+// Swing::print(&ticker_data.find_swings(&SwingDetector::ZigZag(10.0)));
+// Swing::print(&ticker_data.find_swings(&SwingDetector::Atr { period: 14, multiple: 3.0 }));
+// Swing::print(&ticker_data.find_swings(&SwingDetector::Fractal(5)));
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### MARKET STRUCTURE EVENTS ###\t\t");
+// // minor, intermediate and major degree
+// let structure = MarketStructureEvents::new(
+//   &ticker_data,
+//   &[SwingDetector::Fractal(5), SwingDetector::ZigZag(10.0), SwingDetector::ZigZag(25.0)]
+// );
+// structure.print();
+// structure.write_csv(&PathBuf::from("./SPX/SPX_market_structure.csv")).unwrap();
+// structure.write_json(&PathBuf::from("./SPX/SPX_market_structure.json")).unwrap();
+// // do retrograde stations land near a change of character?
+// let choch_dates = structure.degrees[1].event_dates(Some(StructureEventKind::ChangeOfCharacter), None);
+// let choch_reversals: Vec<Reversal> = choch_dates.iter()
+//   .filter_map(|date| ticker_data.get_candles().iter().find(|candle| candle.date == *date))
+//   .map(|candle| Reversal { candle: candle.clone(), reversal_type: ReversalType::Top })
+//   .collect();
+// ForecastEvaluation::new(&Forecast::from_dates(&retrograde_stations), &choch_reversals, 3).print();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### TRADING CALENDAR ###\t\t");
+// let nyse = TradingCalendar::Nyse;
+// for holiday in nyse.holidays(2023).iter() {
+//   println!("{}", holiday.as_string());
+// }
+// let date = Time::new(2023, &Month::from_num(1), &Day::from_num(14));
+// println!("Nearest session: {}", nyse.nearest_session(&date).as_string());
+// println!("5 sessions later: {}", nyse.offset_sessions(&date, 5).as_string());
+// println!("Sessions to year end: {}", nyse.diff_sessions(&date, &Time::new(2023, &Month::from_num(12), &Day::from_num(31))));
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### INTRADAY ###\t\t");
+// // hourly bars keep their timestamps, so reversals and swings are found within the day
+// let rapid_api = RapidApi::new("SPX".to_string());
+// let mut hourly = TickerData::new();
+// hourly.add_series(rapid_api.query(Interval::OneHour).await.unwrap()).unwrap();
+// println!("Interval: {:?}", hourly.interval());
+// for reversal in hourly.find_reversals(10).iter() {
+//   println!("{}\t{}", reversal.candle.datetime(), reversal.reversal_type.as_string());
+// }
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### MULTI-TIMEFRAME ###\t\t");
+// // weekly and monthly pivots for outer planet studies
+// let multi_timeframe = MultiTimeframe::new(
+//   ticker_data.clone(),
+//   &[Timeframe::Weekly(chrono::Weekday::Mon), Timeframe::Monthly, Timeframe::Quarterly, Timeframe::Yearly]
+// ).unwrap();
+// multi_timeframe.print();
+// for (timeframe, swings) in multi_timeframe.find_swings(&SwingDetector::ZigZag(15.0)).iter() {
+//   println!("{}", timeframe.map_or("Base".to_string(), |timeframe| timeframe.as_string()));
+//   Swing::print(swings);
+// }
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### CSV SCHEMA ###\t\t");
+// // re-read the dataframe crate's export
+// let mut dataframe_ticker = TickerData::new();
+// dataframe_ticker.add_csv_with_schema(&PathBuf::from("./ticker_dataframe.csv"), &CsvSchema::dataframe()).unwrap();
+// // Yahoo-style export: named columns, US dates, no volume needed
+// let mut schema = CsvSchema::by_name("Date", DateFormat::Custom("%m/%d/%Y".to_string()), "Open", "High", "Low", "Close", Some("Volume"));
+// schema.delimiter = b';';
+// let mut yahoo_ticker = TickerData::new();
+// if let Err(error) = yahoo_ticker.add_csv_with_schema(&PathBuf::from("./SPX/SPX_yahoo.csv"), &schema) {
+//   println!("{}", error);
+// }
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### DATA QUALITY ###\t\t");
+// let report = ticker_data.validate(&TradingCalendar::Nyse);
+// report.print();
+// // flat candles in place of bad ticks and missing sessions, so PFS and reversals see a clean series
+// let repaired = ticker_data.repair(&report, &Repair::ForwardFill);
+// // back-adjust for a 4:1 split and a dividend
+// let actions = [
+//   CorporateAction::Split { ex_date: Time::new(2020, &Month::August, &Day::ThirtyOne), ratio: 4.0 },
+//   CorporateAction::Dividend { ex_date: Time::new(2023, &Month::February, &Day::Ten), amount: 0.23 },
+// ];
+// AdjustmentFactor::print(&repaired.adjustment_factors(&actions).unwrap());
+// let adjusted = repaired.adjust(&actions).unwrap();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### MARKET DATA PROVIDERS ###\t\t");
+// // RAPID_API_KEY comes from the environment or ./market_data.json ({"RAPID_API_KEY": "..."})
+// let client = MarketDataClient::new(RapidApi::new("SPX".to_string()));
+// let start = Time::new(2000, &Month::January, &Day::One);
+// let candles = client.fetch("SPX", Interval::Daily, &start, &Time::today()).await.unwrap();
+// // record once, then replay offline through the same paging and retry path
+// let fixtures = FixtureProvider::new(PathBuf::from("./fixtures"));
+// fixtures.record("SPX", Interval::Daily, &candles).unwrap();
+// let offline = MarketDataClient::with_policy(fixtures, FetchPolicy::immediate());
+// let mut offline_ticker = TickerData::new();
+// offline_ticker.add_series(offline.fetch("SPX", Interval::Daily, &start, &Time::today()).await.unwrap()).unwrap();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### EXCHANGE FORMATS ###\t\t");
+// // the TradingView export is trusted first, exchange klines fill dates it lacks
+// let mut tradingview = TickerData::new();
+// tradingview.add_csv_series(&PathBuf::from("./BTCUSD.csv")).unwrap();
+// let binance = ExchangeFormat::BinanceCsv.read(&PathBuf::from("./BTCUSDT-1d-2023-03.csv")).unwrap();
+// let coinbase = ExchangeFormat::CoinbaseJson.read(&PathBuf::from("./BTC-USD_candles.json")).unwrap();
+// let mut btc = TickerData::new();
+// btc.add_series(merge_sources(&[tradingview.candles.clone(), binance, coinbase])).unwrap();
+// // split and dividend adjusted equity history
+// let mut spy = TickerData::new();
+// spy.add_series(ExchangeFormat::YahooCsv { adjusted: true }.read(&PathBuf::from("./SPY.csv")).unwrap()).unwrap();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### LOCAL STORE ###\t\t");
+// let store = Store::open(&PathBuf::from("./market.db")).unwrap();
+// store.save_ticker_data("SPX", &ticker_data).unwrap();
+// // later runs start from the store and only fetch newer candles
+// let mut stored_spx = store.load_ticker_data("SPX", Interval::Daily).unwrap();
+// if let Some(latest) = store.latest_candle_timestamp("SPX", Interval::Daily).unwrap() {
+//   let client = MarketDataClient::new(RapidApi::new("SPX".to_string()));
+//   let newer = client.fetch("SPX", Interval::Daily, &Time::from_unix(latest), &Time::today()).await.unwrap();
+//   store.upsert_candles("SPX", &newer).unwrap();
+//   stored_spx.add_series(newer).unwrap();
+// }
+// // ephemerides and retrograde searches are read from the store once computed
+// let mars = ephemeris::Query::query_cached(&store, ephemeris::Origin::Geocentric, &ephemeris::Planet::Mars, ephemeris::DataType::RightAscension, start_date, end_date).await.unwrap();
+// let retrograde = toolkit::Retrograde::new_cached(&store, start_date, end_date, &ephemeris::Planet::to_vec()).await.unwrap();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### FEATURE TABLE ###\t\t");
+// // one row per day: prices, planet positions, aspects, lunar phase, eclipse distance and 1/5/20 day forward returns
+// let store = Store::open(&PathBuf::from("./market.db")).unwrap();
+// let features = dataframe::FeatureTable::build(&ticker_data, &ephemeris::Planet::to_vec(), &[1, 5, 20], Some(&store)).await.unwrap();
+// features.write_csv(&PathBuf::from("./SPX/SPX_features.csv")).unwrap();
+// features.write_parquet(&PathBuf::from("./SPX/SPX_features.parquet")).unwrap();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### ASTRO FEATURE MODELS ###\t\t");
+// // does the planetary state predict whether SPX is higher in 5 trading days, beyond shifted labels?
+// let dataset = dataframe::Dataset::cyclic(&features, 5).unwrap();
+// let cross_validation = dataframe::CrossValidation::default();
+// for kind in [dataframe::ModelKind::logistic_regression(), dataframe::ModelKind::gradient_boosted_trees()] {
+//   cross_validation.evaluate(&dataset, &kind).unwrap().print(10);
+// }
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### SPECTRAL CYCLES ###\t\t");
+// // cycles from 20 trading days to 30 years in detrended log closes, next to known planetary periods
+// let cycle_analysis = CycleAnalysis::new(&ticker_data).unwrap();
+// let mut cycles = cycle_analysis.fft(20.0, 30.0 * DAYS_PER_YEAR, 8).unwrap();
+// cycles.extend(cycle_analysis.lomb_scargle(20.0, 30.0 * DAYS_PER_YEAR, 8).unwrap());
+// cycles.extend(cycle_analysis.synodic_scan().unwrap());
+// Cycle::print(&cycles);
+// // PFS and HDA years compared to Jupiter-Saturn harmonics, e.g. 10 years to half of 19.86
+// SynodicReport::new(&cycles, &SYNODIC_PERIODS, 3, 0.05).print();
+
+// println!("----------------------------------------------------------------------------------------");
+// println!("\t\t### SYNODIC PHASE ###\t\t");
+// // Jupiter-Saturn phase since each conjunction, and SPX returns and reversals per 45 degrees of it:
+// // do markets bottom in the waning square (270-315)?
+// let jupiter_saturn = toolkit::SynodicPhase::new(ephemeris::Origin::Geocentric, &ephemeris::Planet::Jupiter, &ephemeris::Planet::Saturn, start_date, end_date).await.unwrap();
+// jupiter_saturn.print_conjunctions();
+// let phase_stats = jupiter_saturn.bucket_stats(&ticker_data, 45.0, &ticker_data.detect_reversals(&SwingDetector::ZigZag(10.0))).unwrap();
+// jupiter_saturn.print_bucket_stats(&phase_stats);
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use log::debug;
+use crate::*;
+
+/// Event that changes the price of a share without a change in value.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum CorporateAction {
+  /// New shares per old share from `ex_date`, e.g. 4.0 for a 4:1 split or 0.1 for a 1:10 reverse split
+  Split { ex_date: Time, ratio: f64 },
+  /// Cash per share paid to holders before `ex_date`
+  Dividend { ex_date: Time, amount: f64 },
+}
+
+impl CorporateAction {
+  pub fn ex_date(&self) -> &Time {
+    match self {
+      CorporateAction::Split { ex_date, .. } => ex_date,
+      CorporateAction::Dividend { ex_date, .. } => ex_date,
+    }
+  }
+
+  pub fn as_string(&self) -> String {
+    match self {
+      CorporateAction::Split { ex_date, ratio } => format!("{} split {}", ex_date.as_string(), ratio),
+      CorporateAction::Dividend { ex_date, amount } => format!("{} dividend {}", ex_date.as_string(), amount),
+    }
+  }
+}
+
+/// Multipliers that back-adjust a candle for every corporate action after it.
+/// The latest candles have factors of 1.0, so adjusted prices match the current quote.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct AdjustmentFactor {
+  pub date: Time,
+  /// Multiplies open, high, low and close
+  pub price: f64,
+  /// Multiplies volume; splits only, dividends don't change the share count
+  pub volume: f64,
+}
+
+impl AdjustmentFactor {
+  pub fn print(factors: &[AdjustmentFactor]) {
+    println!("DATE\t\tPRICE\t\tVOLUME");
+    let mut previous: Option<&AdjustmentFactor> = None;
+    for factor in factors.iter() {
+      // only print where the factors step
+      if previous.is_some_and(|previous| previous.price == factor.price && previous.volume == factor.volume) {
+        continue;
+      }
+      println!("{}\t{:.6}\t{:.6}", factor.date.as_string(), factor.price, factor.volume);
+      previous = Some(factor);
+    }
+  }
+}
+
+impl TickerData {
+  /// Factor series for `actions`, one per candle.
+  /// A split divides earlier prices by its ratio. A dividend multiplies earlier prices by
+  /// `1 - amount / close`, using the close of the last candle before the ex-date.
+  /// Errors on a non-positive ratio or amount, a dividend at least the prior close,
+  /// or an action with no candle before its ex-date.
+  pub fn adjustment_factors(&self, actions: &[CorporateAction]) -> std::io::Result<Vec<AdjustmentFactor>> {
+    let invalid = |action: &CorporateAction, reason: &str| std::io::Error::new(
+      std::io::ErrorKind::InvalidInput,
+      format!("{}: {}", action.as_string(), reason)
+    );
+    // (ex-date, price factor, volume factor) of each action
+    let mut steps = Vec::<(Time, f64, f64)>::new();
+    for action in actions.iter() {
+      let prior = self.candles.iter().rev().find(|candle| candle.date < *action.ex_date())
+        .ok_or_else(|| invalid(action, "no candle before the ex-date"))?;
+      match action {
+        CorporateAction::Split { ratio, .. } => {
+          if *ratio <= 0.0 || ratio.is_nan() {
+            return Err(invalid(action, "ratio must be positive"));
+          }
+          steps.push((*action.ex_date(), 1.0 / ratio, *ratio));
+        },
+        CorporateAction::Dividend { amount, .. } => {
+          if *amount <= 0.0 || amount.is_nan() {
+            return Err(invalid(action, "amount must be positive"));
+          }
+          // the prior close is in its own units, so later splits don't enter the ratio
+          if *amount >= prior.close {
+            return Err(invalid(action, &format!("amount is not below the prior close {}", prior.close)));
+          }
+          steps.push((*action.ex_date(), 1.0 - amount / prior.close, 1.0));
+        }
+      }
+    }
+
+    let factors = self.candles.iter()
+      .map(|candle| {
+        let (price, volume) = steps.iter()
+          .filter(|(ex_date, _, _)| candle.date < *ex_date)
+          .fold((1.0, 1.0), |(price, volume), (_, price_step, volume_step)| (price * price_step, volume * volume_step));
+        AdjustmentFactor {
+          date: candle.date,
+          price,
+          volume
+        }
+      })
+      .collect();
+    debug!("Adjustment factors for {} corporate actions over {} candles", actions.len(), self.candles.len());
+    Ok(factors)
+  }
+
+  /// Copy of the candles back-adjusted for `actions`.
+  pub fn adjust(&self, actions: &[CorporateAction]) -> std::io::Result<TickerData> {
+    let factors = self.adjustment_factors(actions)?;
+    self.apply_adjustment_factors(&factors)
+  }
+
+  /// Copy of the candles multiplied by `factors`, e.g. a vendor's factor series.
+  /// Factors are a step function by date: each candle takes the latest factor on or before its date.
+  /// Errors if a candle predates the first factor.
+  pub fn apply_adjustment_factors(&self, factors: &[AdjustmentFactor]) -> std::io::Result<TickerData> {
+    let mut factors = factors.to_vec();
+    factors.sort_by(|a, b| a.date.partial_cmp(&b.date).unwrap());
+
+    let mut ticker_data = TickerData::new();
+    for candle in self.candles.iter() {
+      let index = factors.partition_point(|factor| factor.date <= candle.date);
+      let factor = index.checked_sub(1).map(|index| &factors[index]).ok_or_else(|| std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("No adjustment factor on or before {}", candle.date.as_string())
+      ))?;
+      let mut adjusted = candle.clone();
+      adjusted.open *= factor.price;
+      adjusted.high *= factor.price;
+      adjusted.low *= factor.price;
+      adjusted.close *= factor.price;
+      adjusted.volume = candle.volume.map(|volume| volume * factor.volume);
+      ticker_data.append_candle(&adjusted);
+    }
+    ticker_data.flagged = self.flagged.clone();
+    Ok(ticker_data)
+  }
+}
@@ -0,0 +1,301 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+/// NYSE closures not covered by the holiday rules, since 1960.
+const NYSE_SPECIAL_CLOSURES: &[(i32, u32, u32)] = &[
+  (1961, 5, 29), // Day before Decoration Day
+  (1963, 11, 25), // President Kennedy's funeral
+  (1964, 5, 29), // Memorial Day observed despite ending the month
+  (1968, 2, 12), // Lincoln's Birthday
+  (1968, 4, 9), // Day of mourning for Martin Luther King Jr.
+  (1968, 7, 5), // Day after Independence Day
+  (1968, 11, 11), // Veterans Day, in place of that week's paperwork crisis Wednesday
+  (1969, 2, 10), // Snowstorm
+  (1969, 3, 31), // President Eisenhower's funeral
+  (1969, 7, 21), // Apollo 11 moon landing
+  (1972, 12, 28), // President Truman's funeral
+  (1973, 1, 25), // President Johnson's funeral
+  (1977, 7, 14), // New York City blackout
+  (1985, 9, 27), // Hurricane Gloria
+  (1994, 4, 27), // President Nixon's funeral
+  (2001, 9, 11), // September 11 attacks
+  (2001, 9, 12),
+  (2001, 9, 13),
+  (2001, 9, 14),
+  (2004, 6, 11), // President Reagan's funeral
+  (2007, 1, 2), // President Ford's funeral
+  (2012, 10, 29), // Hurricane Sandy
+  (2012, 10, 30),
+  (2018, 12, 5), // President George H.W. Bush's funeral
+  (2025, 1, 9), // President Carter's funeral
+];
+
+thread_local! {
+  /// `TradingCalendar::holidays` by calendar and year, since sessions are checked one date at a time.
+  static HOLIDAYS: RefCell<HashMap<(TradingCalendar, i32), Vec<Time>>> = RefCell::new(HashMap::new());
+}
+
+/// Which days a market has a daily candle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum TradingCalendar {
+  /// New York Stock Exchange: weekdays less holidays (historical rules from 1954) and special closures
+  Nyse,
+  /// CME equity and rate futures trade dates: weekdays less the NYSE holiday rules, without NYSE-only closures
+  Cme,
+  /// Every day
+  Crypto,
+}
+
+impl TradingCalendar {
+  pub fn as_string(&self) -> &str {
+    match self {
+      TradingCalendar::Nyse => "NYSE",
+      TradingCalendar::Cme => "CME",
+      TradingCalendar::Crypto => "Crypto",
+    }
+  }
+
+  /// Days in `year` the market is closed, other than weekends.
+  pub fn holidays(&self, year: i32) -> Vec<Time> {
+    let mut holidays = match self {
+      TradingCalendar::Nyse => {
+        let mut holidays = Self::rule_holidays(year);
+        holidays.extend(NYSE_SPECIAL_CLOSURES.iter()
+          .filter(|(closure_year, _, _)| *closure_year == year)
+          .map(|(year, month, day)| Self::date(*year, *month, *day)));
+        holidays.extend(Self::paperwork_crisis_closures(year));
+        holidays
+      },
+      TradingCalendar::Cme => {
+        let mut holidays = Self::rule_holidays(year);
+        // the exchange was shut with NYSE after September 11
+        if year == 2001 {
+          holidays.extend((11..=14).map(|day| Self::date(2001, 9, day)));
+        }
+        holidays
+      },
+      TradingCalendar::Crypto => Vec::new(),
+    };
+    holidays.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    holidays.dedup();
+    holidays
+  }
+
+  pub fn is_session(&self, date: &Time) -> bool {
+    match self {
+      TradingCalendar::Crypto => true,
+      _ => !date.is_weekend() && !HOLIDAYS.with(|holidays| {
+        holidays.borrow_mut()
+          .entry((*self, date.year))
+          .or_insert_with(|| self.holidays(date.year))
+          .contains(date)
+      }),
+    }
+  }
+
+  /// First session on or after `date`.
+  pub fn next_session(&self, date: &Time) -> Time {
+    let mut session = *date;
+    while !self.is_session(&session) {
+      session = session.delta_date(1);
+    }
+    session
+  }
+
+  /// Last session on or before `date`.
+  pub fn previous_session(&self, date: &Time) -> Time {
+    let mut session = *date;
+    while !self.is_session(&session) {
+      session = session.delta_date(-1);
+    }
+    session
+  }
+
+  /// Closest session to `date`, the earlier one if tied.
+  pub fn nearest_session(&self, date: &Time) -> Time {
+    let previous = self.previous_session(date);
+    let next = self.next_session(date);
+    if previous.diff_days(date) <= date.diff_days(&next) { previous } else { next }
+  }
+
+  /// Move `sessions` sessions from `date`, backward if negative. `date` itself doesn't count, and needn't be a session.
+  pub fn offset_sessions(&self, date: &Time, sessions: i64) -> Time {
+    let step = if sessions < 0 { -1 } else { 1 };
+    let mut remaining = sessions.abs();
+    let mut current = *date;
+    while remaining > 0 {
+      current = current.delta_date(step);
+      if self.is_session(&current) {
+        remaining -= 1;
+      }
+    }
+    current
+  }
+
+  /// Sessions after `start` up to and including `end`, negative if `end` is before `start`.
+  /// The session counterpart of `Time::diff_days`.
+  pub fn diff_sessions(&self, start: &Time, end: &Time) -> i64 {
+    if end < start {
+      return -self.diff_sessions(end, start);
+    }
+    start.delta_date(1).time_period(end).iter().filter(|date| self.is_session(date)).count() as i64
+  }
+
+  /// Every session from `start` to `end` inclusive.
+  pub fn sessions_between(&self, start: &Time, end: &Time) -> Vec<Time> {
+    start.time_period(end).into_iter().filter(|date| self.is_session(date)).collect()
+  }
+
+  fn date(year: i32, month: u32, day: u32) -> Time {
+    Time::new(year, &Month::from_num(month), &Day::from_num(day))
+  }
+
+  fn naive(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).expect("invalid holiday date")
+  }
+
+  /// Saturday holidays are observed Friday, unless that Friday is the last session of the month
+  /// (so a Saturday New Year's Day is not observed). Sunday holidays are observed Monday.
+  fn observed(year: i32, month: u32, day: u32) -> Option<Time> {
+    let date = Self::naive(year, month, day);
+    let date = match date.weekday() {
+      Weekday::Sat => {
+        let friday = date.pred_opt().unwrap();
+        if (friday + chrono::Duration::days(3)).month() != friday.month() {
+          return None;
+        }
+        friday
+      },
+      Weekday::Sun => date.succ_opt().unwrap(),
+      _ => date,
+    };
+    Some(Self::date(date.year(), date.month(), date.day()))
+  }
+
+  /// `n`th `weekday` of the month, counting from 1.
+  fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u32) -> Time {
+    let date = NaiveDate::from_weekday_of_month_opt(year, month, weekday, n as u8).expect("invalid nth weekday");
+    Self::date(date.year(), date.month(), date.day())
+  }
+
+  fn last_weekday(year: i32, month: u32, weekday: Weekday) -> Time {
+    let mut date = Self::naive(year, month, 1).checked_add_months(chrono::Months::new(1)).unwrap().pred_opt().unwrap();
+    while date.weekday() != weekday {
+      date = date.pred_opt().unwrap();
+    }
+    Self::date(date.year(), date.month(), date.day())
+  }
+
+  /// Gregorian Easter Sunday (anonymous Gregorian algorithm).
+  fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    Self::naive(year, month as u32, day as u32)
+  }
+
+  /// Recurring NYSE holidays as the rules stood in `year`.
+  fn rule_holidays(year: i32) -> Vec<Time> {
+    let mut holidays = Vec::<Time>::new();
+    holidays.extend(Self::observed(year, 1, 1));
+    if year >= 1998 {
+      holidays.push(Self::nth_weekday(year, 1, Weekday::Mon, 3));
+    }
+    if year <= 1953 {
+      holidays.extend(Self::observed(year, 2, 12));
+    }
+    if year < 1971 {
+      holidays.extend(Self::observed(year, 2, 22));
+      holidays.extend(Self::observed(year, 5, 30));
+    } else {
+      holidays.push(Self::nth_weekday(year, 2, Weekday::Mon, 3));
+      holidays.push(Self::last_weekday(year, 5, Weekday::Mon));
+    }
+    let good_friday = Self::easter(year) - chrono::Duration::days(2);
+    holidays.push(Self::date(good_friday.year(), good_friday.month(), good_friday.day()));
+    if year >= 2022 {
+      holidays.extend(Self::observed(year, 6, 19));
+    }
+    holidays.extend(Self::observed(year, 7, 4));
+    holidays.push(Self::nth_weekday(year, 9, Weekday::Mon, 1));
+    if year <= 1953 {
+      holidays.extend(Self::observed(year, 10, 12));
+      holidays.extend(Self::observed(year, 11, 11));
+    }
+    // every Election Day through 1968, then presidential elections until 1980
+    if year <= 1968 || [1972, 1976, 1980].contains(&year) {
+      let first_monday = Self::nth_weekday(year, 11, Weekday::Mon, 1);
+      holidays.push(first_monday.delta_date(1));
+    }
+    holidays.push(Self::nth_weekday(year, 11, Weekday::Thu, 4));
+    holidays.extend(Self::observed(year, 12, 25));
+    holidays
+  }
+
+  /// Wednesdays from June 12 to December 31 1968 the NYSE closed to clear the paperwork backlog,
+  /// except in weeks that already had a holiday.
+  fn paperwork_crisis_closures(year: i32) -> Vec<Time> {
+    if year != 1968 {
+      return Vec::new();
+    }
+    let mut holidays = Self::rule_holidays(year);
+    holidays.extend(NYSE_SPECIAL_CLOSURES.iter()
+      .filter(|(closure_year, _, _)| *closure_year == year)
+      .map(|(year, month, day)| Self::date(*year, *month, *day)));
+    Self::date(1968, 6, 12).time_period(&Self::date(1968, 12, 31)).into_iter()
+      .filter(|date| date.to_naive_date().weekday() == Weekday::Wed)
+      .filter(|wednesday| {
+        let monday = wednesday.delta_date(-2);
+        let friday = wednesday.delta_date(2);
+        !holidays.iter().any(|holiday| holiday.within_range(monday, friday) && holiday != wednesday)
+      })
+      .collect()
+  }
+}
+
+/// How close a signal must be to an event: calendar days, or sessions of a `TradingCalendar`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum Tolerance {
+  Days(i64),
+  Sessions(i64, TradingCalendar),
+}
+
+impl Tolerance {
+  pub fn as_string(&self) -> String {
+    match self {
+      Tolerance::Days(days) => format!("{} days", days),
+      Tolerance::Sessions(sessions, calendar) => format!("{} {} sessions", sessions, calendar.as_string()),
+    }
+  }
+
+  /// First and last date within tolerance of `date`.
+  pub fn window(&self, date: &Time) -> (Time, Time) {
+    match self {
+      Tolerance::Days(days) => (date.delta_date(-days), date.delta_date(*days)),
+      Tolerance::Sessions(sessions, calendar) => (
+        calendar.offset_sessions(date, -sessions),
+        calendar.offset_sessions(date, *sessions)
+      ),
+    }
+  }
+
+  pub fn contains(&self, date: &Time, other: &Time) -> bool {
+    let (start, end) = self.window(date);
+    other.within_range(start, end)
+  }
+}
+
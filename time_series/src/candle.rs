@@ -1,12 +1,136 @@
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 use crate::Time;
 
+/// Timeframe of a candle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Interval {
+  OneMinute,
+  FiveMinutes,
+  FifteenMinutes,
+  ThirtyMinutes,
+  FourtyFiveMinutes,
+  OneHour,
+  TwoHour,
+  FourHour,
+  /// Any other number of hours, from resampling
+  Hours(u32),
+  Daily,
+  Weekly,
+  Monthly,
+  Quarterly,
+  Yearly,
+}
+
+impl Interval {
+  pub fn to_str(&self) -> String {
+    match self {
+      Interval::OneMinute => "1min".to_string(),
+      Interval::FiveMinutes => "5min".to_string(),
+      Interval::FifteenMinutes => "15min".to_string(),
+      Interval::ThirtyMinutes => "30min".to_string(),
+      Interval::FourtyFiveMinutes => "45min".to_string(),
+      Interval::OneHour => "1h".to_string(),
+      Interval::TwoHour => "2h".to_string(),
+      Interval::FourHour => "4h".to_string(),
+      Interval::Hours(hours) => format!("{}h", hours),
+      Interval::Daily => "1day".to_string(),
+      Interval::Weekly => "1week".to_string(),
+      Interval::Monthly => "1month".to_string(),
+      Interval::Quarterly => "3month".to_string(),
+      Interval::Yearly => "1year".to_string(),
+    }
+  }
+
+  /// Inverse of `to_str`.
+  pub fn from_string(interval: &str) -> Option<Self> {
+    match interval {
+      "1min" => Some(Interval::OneMinute),
+      "5min" => Some(Interval::FiveMinutes),
+      "15min" => Some(Interval::FifteenMinutes),
+      "30min" => Some(Interval::ThirtyMinutes),
+      "45min" => Some(Interval::FourtyFiveMinutes),
+      "1day" => Some(Interval::Daily),
+      "1week" => Some(Interval::Weekly),
+      "1month" => Some(Interval::Monthly),
+      "3month" => Some(Interval::Quarterly),
+      "1year" => Some(Interval::Yearly),
+      hours => hours.strip_suffix('h').and_then(|hours| hours.parse::<u32>().ok()).filter(|hours| *hours > 0).map(Self::hours),
+    }
+  }
+
+  /// Length of one candle in seconds, `None` for months, quarters and years which vary in length.
+  pub fn seconds(&self) -> Option<i64> {
+    match self {
+      Interval::OneMinute => Some(60),
+      Interval::FiveMinutes => Some(5 * 60),
+      Interval::FifteenMinutes => Some(15 * 60),
+      Interval::ThirtyMinutes => Some(30 * 60),
+      Interval::FourtyFiveMinutes => Some(45 * 60),
+      Interval::OneHour => Some(3600),
+      Interval::TwoHour => Some(2 * 3600),
+      Interval::FourHour => Some(4 * 3600),
+      Interval::Hours(hours) => Some(*hours as i64 * 3600),
+      Interval::Daily => Some(86400),
+      Interval::Weekly => Some(7 * 86400),
+      Interval::Monthly | Interval::Quarterly | Interval::Yearly => None,
+    }
+  }
+
+  /// Typical length in seconds, for ordering intervals from finest to coarsest.
+  pub fn approximate_seconds(&self) -> i64 {
+    match self {
+      Interval::Monthly => 30 * 86400,
+      Interval::Quarterly => 91 * 86400,
+      Interval::Yearly => 365 * 86400,
+      _ => self.seconds().unwrap(),
+    }
+  }
+
+  /// Shorter than a day, so several candles share a `Time`.
+  pub fn is_intraday(&self) -> bool {
+    self.seconds().is_some_and(|seconds| seconds < 86400)
+  }
+
+  /// Interval whose candles are `seconds` apart, e.g. the smallest gap between timestamps of a series.
+  /// 28 to 31 days is monthly.
+  pub fn from_seconds(seconds: i64) -> Option<Self> {
+    const INTERVALS: [Interval; 10] = [
+      Interval::OneMinute, Interval::FiveMinutes, Interval::FifteenMinutes, Interval::ThirtyMinutes,
+      Interval::FourtyFiveMinutes, Interval::OneHour, Interval::TwoHour, Interval::FourHour,
+      Interval::Daily, Interval::Weekly
+    ];
+    if (28 * 86400..=31 * 86400).contains(&seconds) {
+      return Some(Interval::Monthly);
+    }
+    INTERVALS.into_iter()
+      .find(|interval| interval.seconds() == Some(seconds))
+      .or_else(|| (seconds > 0 && seconds < 86400 && seconds % 3600 == 0).then(|| Self::hours(seconds as u32 / 3600)))
+  }
+
+  /// `hours` long candles, using the named variant where there is one.
+  pub fn hours(hours: u32) -> Self {
+    match hours {
+      1 => Interval::OneHour,
+      2 => Interval::TwoHour,
+      4 => Interval::FourHour,
+      24 => Interval::Daily,
+      _ => Interval::Hours(hours),
+    }
+  }
+}
+
 /// Event for a single candlestick for a given ticker.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Candle {
-  /// UNIX timestamp in seconds
+  /// UTC date the candle opened
   pub date: Time,
+  /// UNIX timestamp in seconds the candle opened
+  pub timestamp: i64,
+  /// Timeframe of the candle
+  pub interval: Interval,
   /// Open price
   pub open: f64,
   /// High price
@@ -23,22 +147,34 @@ impl Candle {
   pub fn percent_change(&self, prev_close: f64) -> f64 {
     (100.0 / prev_close) * self.close
   }
+
+  pub fn datetime(&self) -> DateTime<Utc> {
+    Utc.timestamp_opt(self.timestamp, 0).unwrap()
+  }
 }
 
 impl PartialEq for Candle {
   fn eq(&self, other: &Self) -> bool {
-    self.date.as_string() == other.date.as_string() && self.close == other.close
+    self.timestamp == other.timestamp && self.interval == other.interval && self.close == other.close
   }
 }
 
 pub trait CandleTrait {
   fn unix_date(&self) -> u64;
+  fn unix_timestamp(&self) -> i64;
+  fn interval(&self) -> Interval;
 }
 
 impl CandleTrait for Candle {
   fn unix_date(&self) -> u64 {
     self.date.to_unix() as u64
   }
+  fn unix_timestamp(&self) -> i64 {
+    self.timestamp
+  }
+  fn interval(&self) -> Interval {
+    self.interval
+  }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -58,10 +194,16 @@ impl CandleHashTrait for CandleHasher {
   fn finish(&mut self) -> u64 {
     self.0.finish()
   }
-  /// Hash account using key and slot
+  /// Hash intraday candles by timestamp and interval, so candles of the same day are distinct.
+  /// Daily and coarser candles hash by date and interval, since vendors stamp sessions at different times of day.
   fn hash_candle<T: CandleTrait>(&mut self, candle: &T) -> u64 {
     self.0 = DefaultHasher::new();
-    candle.unix_date().hash(&mut self.0);
+    if candle.interval().is_intraday() {
+      candle.unix_timestamp().hash(&mut self.0);
+    } else {
+      candle.unix_date().hash(&mut self.0);
+    }
+    candle.interval().hash(&mut self.0);
     self.finish()
   }
 }
\ No newline at end of file
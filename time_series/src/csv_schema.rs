@@ -0,0 +1,197 @@
+use std::fs::File;
+use std::path::PathBuf;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use crate::*;
+
+/// How the date column is written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateFormat {
+  /// UNIX timestamp in seconds
+  UnixSeconds,
+  /// UNIX timestamp in milliseconds
+  UnixMilliseconds,
+  /// 2023-03-10
+  IsoDate,
+  /// 2023-03-10T15:30:00, 2023-03-10 15:30:00 or RFC 3339 with an offset
+  IsoDateTime,
+  /// chrono strftime pattern, with or without a time of day, e.g. "%m/%d/%Y"
+  Custom(String),
+  /// UNIX seconds if an integer, otherwise ISO datetime or ISO date
+  Auto,
+}
+
+impl DateFormat {
+  /// UNIX timestamp in seconds, times without an offset are UTC.
+  pub fn parse(&self, value: &str) -> Result<i64, String> {
+    let invalid = |format: &str| format!("invalid {} date \"{}\"", format, value);
+    match self {
+      DateFormat::UnixSeconds => value.parse::<i64>().map_err(|_| invalid("UNIX seconds")),
+      DateFormat::UnixMilliseconds => value.parse::<i64>().map(|msec| msec.div_euclid(1000)).map_err(|_| invalid("UNIX milliseconds")),
+      DateFormat::IsoDate => Self::parse_date(value, "%Y-%m-%d").ok_or_else(|| invalid("ISO")),
+      DateFormat::IsoDateTime => DateTime::parse_from_rfc3339(value).map(|datetime| datetime.timestamp()).ok()
+        .or_else(|| Self::parse_datetime(value, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|| Self::parse_datetime(value, "%Y-%m-%d %H:%M:%S"))
+        .ok_or_else(|| invalid("ISO")),
+      DateFormat::Custom(format) => Self::parse_datetime(value, format)
+        .or_else(|| Self::parse_date(value, format))
+        .ok_or_else(|| invalid(format)),
+      DateFormat::Auto => DateFormat::UnixSeconds.parse(value)
+        .or_else(|_| DateFormat::IsoDateTime.parse(value))
+        .or_else(|_| DateFormat::IsoDate.parse(value))
+        .map_err(|_| format!("unrecognized date \"{}\"", value)),
+    }
+  }
+
+  fn parse_datetime(value: &str, format: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(value, format).ok().map(|datetime| Utc.from_utc_datetime(&datetime).timestamp())
+  }
+
+  fn parse_date(value: &str, format: &str) -> Option<i64> {
+    NaiveDate::parse_from_str(value, format).ok()
+      .and_then(|date| date.and_hms_opt(0, 0, 0))
+      .map(|datetime| Utc.from_utc_datetime(&datetime).timestamp())
+  }
+}
+
+/// A column by header name or by zero-based index.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+  Name(String),
+  Index(usize),
+}
+
+impl Column {
+  pub fn name(name: &str) -> Self {
+    Column::Name(name.to_string())
+  }
+
+  fn resolve(&self, headers: &Option<Vec<String>>) -> std::io::Result<usize> {
+    match (self, headers) {
+      (Column::Index(index), _) => Ok(*index),
+      (Column::Name(name), Some(headers)) => headers.iter()
+        .position(|header| header.eq_ignore_ascii_case(name))
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("line 1: column \"{}\" not found in header", name))),
+      (Column::Name(name), None) => Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("column \"{}\" is named but the schema has no header row", name)
+      )),
+    }
+  }
+}
+
+/// Layout of a price history CSV. Columns not in the schema are ignored.
+#[derive(Debug, Clone)]
+pub struct CsvSchema {
+  pub delimiter: u8,
+  pub has_headers: bool,
+  pub date: Column,
+  pub date_format: DateFormat,
+  pub open: Column,
+  pub high: Column,
+  pub low: Column,
+  pub close: Column,
+  /// Empty, "NaN" or missing values, or a missing column, are `None`
+  pub volume: Option<Column>,
+  /// Inferred from the timestamps if `None`
+  pub interval: Option<Interval>,
+}
+
+/// `date,open,high,low,close,volume` with UNIX second dates, as read by `TickerData::add_csv_series`.
+impl Default for CsvSchema {
+  fn default() -> Self {
+    Self {
+      delimiter: b',',
+      has_headers: true,
+      date: Column::Index(0),
+      date_format: DateFormat::UnixSeconds,
+      open: Column::Index(1),
+      high: Column::Index(2),
+      low: Column::Index(3),
+      close: Column::Index(4),
+      volume: Some(Column::Index(5)),
+      interval: None,
+    }
+  }
+}
+
+impl CsvSchema {
+  /// Columns by header name.
+  pub fn by_name(date: &str, date_format: DateFormat, open: &str, high: &str, low: &str, close: &str, volume: Option<&str>) -> Self {
+    Self {
+      date: Column::name(date),
+      date_format,
+      open: Column::name(open),
+      high: Column::name(high),
+      low: Column::name(low),
+      close: Column::name(close),
+      volume: volume.map(Column::name),
+      ..Default::default()
+    }
+  }
+
+  /// `date,close,open,high,low` as written by `dataframe::ticker_dataframe`, with UNIX or ISO dates.
+  pub fn dataframe() -> Self {
+    Self::by_name("date", DateFormat::Auto, "open", "high", "low", "close", None)
+  }
+}
+
+impl TickerData {
+  /// Read candles from a CSV laid out as `schema`.
+  /// Handles duplicate candles and sorts candles by timestamp.
+  /// Errors name the line of the bad record; no candles are added if any record fails.
+  pub fn add_csv_with_schema(&mut self, csv_path: &PathBuf, schema: &CsvSchema) -> std::io::Result<()> {
+    let file_buffer = File::open(csv_path)?;
+    let mut csv = csv::ReaderBuilder::new()
+      .delimiter(schema.delimiter)
+      .has_headers(schema.has_headers)
+      .flexible(true)
+      .trim(csv::Trim::All)
+      .from_reader(file_buffer);
+
+    let headers = match schema.has_headers {
+      true => Some(csv.headers()?.iter().map(String::from).collect::<Vec<String>>()),
+      false => None,
+    };
+    let date_index = schema.date.resolve(&headers)?;
+    let open_index = schema.open.resolve(&headers)?;
+    let high_index = schema.high.resolve(&headers)?;
+    let low_index = schema.low.resolve(&headers)?;
+    let close_index = schema.close.resolve(&headers)?;
+    // a volume column missing from the header reads as no volume
+    let volume_index = schema.volume.as_ref().and_then(|column| column.resolve(&headers).ok());
+
+    let mut candles = Vec::<Candle>::new();
+    for record in csv.records() {
+      let record = record.map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error.to_string()))?;
+      let line = record.position().map_or(0, |position| position.line());
+      let parse_error = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("line {}: {}", line, message));
+      let field = |index: usize, name: &str| record.get(index)
+        .ok_or_else(|| parse_error(format!("missing {} column {}", name, index)));
+      let price = |index: usize, name: &str| field(index, name).and_then(|value| value.parse::<f64>()
+        .map_err(|_| parse_error(format!("invalid {} \"{}\"", name, value))));
+
+      let timestamp = schema.date_format.parse(field(date_index, "date")?).map_err(parse_error)?;
+      let volume = match volume_index.and_then(|index| record.get(index)) {
+        None | Some("") | Some("NaN") => None,
+        Some(value) => Some(value.parse::<f64>().map_err(|_| parse_error(format!("invalid volume \"{}\"", value)))?),
+      };
+      candles.push(Candle {
+        date: Time::from_unix(timestamp),
+        timestamp,
+        interval: Interval::Daily,
+        open: price(open_index, "open")?,
+        high: price(high_index, "high")?,
+        low: price(low_index, "low")?,
+        close: price(close_index, "close")?,
+        volume
+      });
+    }
+
+    let interval = schema.interval.unwrap_or_else(|| Self::infer_interval(&candles));
+    for mut candle in candles.into_iter() {
+      candle.interval = interval;
+      self.append_candle(&candle);
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,460 @@
+use std::f64::consts::PI;
+use log::debug;
+use crate::*;
+
+/// Average Gregorian year, to report periods in years.
+pub const DAYS_PER_YEAR: f64 = 365.2425;
+
+/// Period of a planetary cycle, in days, that price cycles are compared to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SynodicPeriod {
+  pub name: &'static str,
+  pub days: f64,
+}
+
+/// Synodic periods (returns to the same angle from the Sun, or Jupiter to Saturn) and the lunar node cycle.
+pub const SYNODIC_PERIODS: [SynodicPeriod; 8] = [
+  SynodicPeriod { name: "Lunar", days: 29.530589 },
+  SynodicPeriod { name: "Mercury", days: 115.88 },
+  SynodicPeriod { name: "Saturn", days: 378.09 },
+  SynodicPeriod { name: "Jupiter", days: 398.88 },
+  SynodicPeriod { name: "Venus", days: 583.92 },
+  SynodicPeriod { name: "Mars", days: 779.94 },
+  SynodicPeriod { name: "Lunar Nodes", days: 6798.38 },
+  SynodicPeriod { name: "Jupiter-Saturn", days: 7253.45 },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpectralMethod {
+  /// Power spectrum of the calendar-day series
+  Fft,
+  /// Periodogram of the trading days as sampled, without filling weekends and holidays
+  LombScargle,
+  /// Power at chosen periods of the calendar-day series
+  Goertzel,
+}
+
+impl SpectralMethod {
+  pub fn as_string(&self) -> String {
+    match self {
+      Self::Fft => "FFT".to_string(),
+      Self::LombScargle => "Lomb-Scargle".to_string(),
+      Self::Goertzel => "Goertzel".to_string(),
+    }
+  }
+}
+
+/// Sine wave in detrended log closes, fit by least squares at the period a method found or scanned.
+#[derive(Debug, Clone)]
+pub struct Cycle {
+  pub method: SpectralMethod,
+  /// Calendar days per cycle
+  pub period: f64,
+  /// Degrees through the cycle on `reference_date`: 0 at a peak, 180 at a trough
+  pub phase: f64,
+  /// Half the peak to trough swing, in log price
+  pub amplitude: f64,
+  /// Fraction of the detrended variance the cycle explains
+  pub strength: f64,
+  /// The method's own spectral power; only comparable between cycles of the same method
+  pub power: f64,
+  /// Last date of the analysed series
+  pub reference_date: Time,
+}
+
+impl Cycle {
+  pub fn period_years(&self) -> f64 {
+    self.period / DAYS_PER_YEAR
+  }
+
+  /// First peak on or after `reference_date` if the cycle continues.
+  pub fn next_peak(&self) -> Time {
+    self.days_to_phase(0.0)
+  }
+
+  /// First trough on or after `reference_date` if the cycle continues.
+  pub fn next_trough(&self) -> Time {
+    self.days_to_phase(180.0)
+  }
+
+  fn days_to_phase(&self, phase: f64) -> Time {
+    let degrees = (phase - self.phase).rem_euclid(360.0);
+    self.reference_date.delta_date((degrees / 360.0 * self.period).round() as i64)
+  }
+
+  pub fn print(cycles: &[Cycle]) {
+    println!("METHOD\t\tPERIOD (DAYS)\tYEARS\tPHASE\tAMPLITUDE\tSTRENGTH\tPOWER\tNEXT PEAK\tNEXT TROUGH");
+    for cycle in cycles.iter() {
+      println!(
+        "{}\t{:.2}\t\t{:.2}\t{:.0}\t{:.4}\t\t{:.2}%\t\t{:.4}\t{}\t{}",
+        cycle.method.as_string(),
+        cycle.period,
+        cycle.period_years(),
+        cycle.phase,
+        cycle.amplitude,
+        cycle.strength * 100.0,
+        cycle.power,
+        cycle.next_peak().as_string(),
+        cycle.next_trough().as_string()
+      );
+    }
+  }
+}
+
+/// Log closes of one close per date with the linear trend removed, ready for cycle detection.
+#[derive(Debug, Clone)]
+pub struct CycleAnalysis {
+  /// Calendar days since the first date, one per trading day
+  pub days: Vec<f64>,
+  /// Detrended log close on each of `days`
+  pub values: Vec<f64>,
+  pub start_date: Time,
+  pub reference_date: Time,
+}
+
+impl CycleAnalysis {
+  /// Takes the last close of each date, so intraday data is analysed daily.
+  pub fn new(ticker_data: &TickerData) -> std::io::Result<Self> {
+    let mut closes = Vec::<(Time, f64)>::new();
+    for candle in ticker_data.candles.iter() {
+      match closes.last_mut() {
+        Some(last) if last.0 == candle.date => last.1 = candle.close,
+        _ => closes.push((candle.date, candle.close)),
+      }
+    }
+    if closes.len() < 3 {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Too few dates to detect cycles"));
+    }
+    if closes.iter().any(|(_, close)| *close <= 0.0) {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Cycles need positive closes"));
+    }
+    let start_date = closes[0].0;
+    let days: Vec<f64> = closes.iter().map(|(date, _)| start_date.diff_days(date) as f64).collect();
+    let logs: Vec<f64> = closes.iter().map(|(_, close)| close.ln()).collect();
+
+    // least squares line through the log closes
+    let n = days.len() as f64;
+    let mean_day = days.iter().sum::<f64>() / n;
+    let mean_log = logs.iter().sum::<f64>() / n;
+    let covariance: f64 = days.iter().zip(logs.iter()).map(|(day, log)| (day - mean_day) * (log - mean_log)).sum();
+    let variance: f64 = days.iter().map(|day| (day - mean_day).powi(2)).sum();
+    let slope = if variance > 0.0 { covariance / variance } else { 0.0 };
+    let values = days.iter().zip(logs.iter())
+      .map(|(day, log)| log - mean_log - slope * (day - mean_day))
+      .collect();
+    debug!("Detrended {} closes, trend {:.2}% per year", days.len(), (slope * DAYS_PER_YEAR).exp_m1() * 100.0);
+
+    Ok(Self {
+      days,
+      values,
+      start_date,
+      reference_date: closes[closes.len() - 1].0
+    })
+  }
+
+  /// Detrended values on every calendar day, weekends and holidays holding the previous close.
+  pub fn calendar_series(&self) -> Vec<f64> {
+    let length = *self.days.last().unwrap() as usize + 1;
+    let mut series = Vec::with_capacity(length);
+    let mut index = 0;
+    for day in 0..length {
+      while index + 1 < self.days.len() && self.days[index + 1] as usize <= day {
+        index += 1;
+      }
+      series.push(self.values[index]);
+    }
+    series
+  }
+
+  /// The `count` strongest FFT peaks with periods from `min_period` to `max_period` days.
+  /// The calendar series is zero-padded to a power of two, so periods are `padded length / bin`.
+  pub fn fft(&self, min_period: f64, max_period: f64, count: usize) -> std::io::Result<Vec<Cycle>> {
+    validate_period_range(min_period, max_period)?;
+    let series = self.calendar_series();
+    let size = series.len().next_power_of_two();
+    let mut spectrum: Vec<(f64, f64)> = series.iter().map(|value| (*value, 0.0)).collect();
+    spectrum.resize(size, (0.0, 0.0));
+    fft(&mut spectrum);
+
+    let powers: Vec<f64> = spectrum[..size / 2].iter().map(|(re, im)| re * re + im * im).collect();
+    let total: f64 = powers.iter().skip(1).sum();
+    let periodogram: Vec<(f64, f64)> = (1..size / 2)
+      .map(|bin| (size as f64 / bin as f64, if total > 0.0 { powers[bin] / total } else { 0.0 }))
+      .collect();
+    Ok(self.peaks(SpectralMethod::Fft, &periodogram, min_period, max_period, count))
+  }
+
+  /// Normalized Lomb-Scargle power from `min_period` to `max_period` days, by period.
+  /// Frequencies are spaced a quarter of `1 / series length` apart.
+  pub fn lomb_scargle_periodogram(&self, min_period: f64, max_period: f64) -> std::io::Result<Vec<(f64, f64)>> {
+    validate_period_range(min_period, max_period)?;
+    let span = self.days.last().unwrap() - self.days[0];
+    let step = 1.0 / (4.0 * span.max(1.0));
+    let n = self.values.len() as f64;
+    let mean = self.values.iter().sum::<f64>() / n;
+    let variance = self.values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (n - 1.0);
+
+    let mut periodogram = Vec::<(f64, f64)>::new();
+    let mut frequency = 1.0 / max_period;
+    while frequency <= 1.0 / min_period {
+      let omega = 2.0 * PI * frequency;
+      let (sin_sum, cos_sum) = self.days.iter()
+        .fold((0.0, 0.0), |(s, c), day| (s + (2.0 * omega * day).sin(), c + (2.0 * omega * day).cos()));
+      let tau = sin_sum.atan2(cos_sum) / (2.0 * omega);
+      let (mut yc, mut ys, mut cc, mut ss) = (0.0, 0.0, 0.0, 0.0);
+      for (day, value) in self.days.iter().zip(self.values.iter()) {
+        let angle = omega * (day - tau);
+        let (sin, cos) = angle.sin_cos();
+        yc += (value - mean) * cos;
+        ys += (value - mean) * sin;
+        cc += cos * cos;
+        ss += sin * sin;
+      }
+      let power = if variance > 0.0 { (yc * yc / cc + ys * ys / ss) / (2.0 * variance) } else { 0.0 };
+      periodogram.push((1.0 / frequency, power));
+      frequency += step;
+    }
+    Ok(periodogram)
+  }
+
+  /// The `count` strongest Lomb-Scargle peaks with periods from `min_period` to `max_period` days.
+  pub fn lomb_scargle(&self, min_period: f64, max_period: f64, count: usize) -> std::io::Result<Vec<Cycle>> {
+    let periodogram = self.lomb_scargle_periodogram(min_period, max_period)?;
+    Ok(self.peaks(SpectralMethod::LombScargle, &periodogram, min_period, max_period, count))
+  }
+
+  /// One cycle per period in `periods` (days), with the Goertzel power of the calendar series:
+  /// the squared DFT amplitude at that exact period, as a fraction of the series' variance.
+  pub fn goertzel(&self, periods: &[f64]) -> std::io::Result<Vec<Cycle>> {
+    if let Some(period) = periods.iter().find(|period| !(period.is_finite() && **period > 0.0)) {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Period must be positive, got {}", period)));
+    }
+    let series = self.calendar_series();
+    let n = series.len() as f64;
+    let mean = series.iter().sum::<f64>() / n;
+    let variance = series.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / n;
+    Ok(periods.iter()
+      .map(|period| {
+        let omega = 2.0 * PI / period;
+        let coefficient = 2.0 * omega.cos();
+        let (mut previous, mut before_previous) = (0.0, 0.0);
+        for value in series.iter() {
+          let current = value - mean + coefficient * previous - before_previous;
+          before_previous = previous;
+          previous = current;
+        }
+        let power = previous * previous + before_previous * before_previous - coefficient * previous * before_previous;
+        let amplitude_squared = 4.0 * power / (n * n);
+        self.cycle(SpectralMethod::Goertzel, *period, if variance > 0.0 { amplitude_squared / 2.0 / variance } else { 0.0 })
+      })
+      .collect())
+  }
+
+  /// Goertzel scan of every entry of `SYNODIC_PERIODS`.
+  pub fn synodic_scan(&self) -> std::io::Result<Vec<Cycle>> {
+    self.goertzel(&SYNODIC_PERIODS.iter().map(|synodic| synodic.days).collect::<Vec<f64>>())
+  }
+
+  /// Local maxima of `periodogram` (period, power) within the range, strongest first.
+  fn peaks(&self, method: SpectralMethod, periodogram: &[(f64, f64)], min_period: f64, max_period: f64, count: usize) -> Vec<Cycle> {
+    let mut peaks: Vec<(f64, f64)> = periodogram.windows(3)
+      .filter(|window| window[1].1 > window[0].1 && window[1].1 >= window[2].1)
+      .map(|window| window[1])
+      .filter(|(period, _)| *period >= min_period && *period <= max_period)
+      .collect();
+    peaks.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+    peaks.into_iter()
+      .take(count)
+      .map(|(period, power)| self.cycle(method, period, power))
+      .collect()
+  }
+
+  /// Fit `a·cos + b·sin + c` at `period` to the trading days, with time measured from `reference_date`.
+  fn cycle(&self, method: SpectralMethod, period: f64, power: f64) -> Cycle {
+    let omega = 2.0 * PI / period;
+    let end = *self.days.last().unwrap();
+    let basis: Vec<[f64; 3]> = self.days.iter()
+      .map(|day| {
+        let (sin, cos) = (omega * (day - end)).sin_cos();
+        [cos, sin, 1.0]
+      })
+      .collect();
+    let mut normal = [[0.0; 3]; 3];
+    let mut target = [0.0; 3];
+    for (row, value) in basis.iter().zip(self.values.iter()) {
+      for i in 0..3 {
+        target[i] += row[i] * value;
+        for j in 0..3 {
+          normal[i][j] += row[i] * row[j];
+        }
+      }
+    }
+    let [a, b, c] = solve(normal, target);
+
+    let n = self.values.len() as f64;
+    let mean = self.values.iter().sum::<f64>() / n;
+    let total: f64 = self.values.iter().map(|value| (value - mean).powi(2)).sum();
+    let residual: f64 = basis.iter().zip(self.values.iter())
+      .map(|(row, value)| (value - a * row[0] - b * row[1] - c).powi(2))
+      .sum();
+    Cycle {
+      method,
+      period,
+      // a·cos(x) + b·sin(x) peaks at x = atan2(b, a), so on the reference date the cycle is -atan2(b, a) past its peak
+      phase: (-b.atan2(a)).to_degrees().rem_euclid(360.0),
+      amplitude: a.hypot(b),
+      strength: if total > 0.0 { (1.0 - residual / total).max(0.0) } else { 0.0 },
+      power,
+      reference_date: self.reference_date
+    }
+  }
+}
+
+/// Periods must satisfy `0 < min_period < max_period`, or the frequency scan never ends.
+fn validate_period_range(min_period: f64, max_period: f64) -> std::io::Result<()> {
+  if min_period > 0.0 && min_period < max_period && max_period.is_finite() {
+    Ok(())
+  } else {
+    Err(std::io::Error::new(
+      std::io::ErrorKind::InvalidInput,
+      format!("Periods must satisfy 0 < min_period < max_period, got {} and {}", min_period, max_period)
+    ))
+  }
+}
+
+/// In-place radix-2 FFT; the length must be a power of two.
+fn fft(data: &mut [(f64, f64)]) {
+  let n = data.len();
+  let mut j = 0;
+  for i in 1..n {
+    let mut bit = n >> 1;
+    while j & bit != 0 {
+      j ^= bit;
+      bit >>= 1;
+    }
+    j |= bit;
+    if i < j {
+      data.swap(i, j);
+    }
+  }
+  let mut length = 2;
+  while length <= n {
+    let angle = -2.0 * PI / length as f64;
+    let (sin, cos) = angle.sin_cos();
+    for start in (0..n).step_by(length) {
+      let (mut w_re, mut w_im) = (1.0, 0.0);
+      for k in 0..length / 2 {
+        let (u_re, u_im) = data[start + k];
+        let (x_re, x_im) = data[start + k + length / 2];
+        let (v_re, v_im) = (x_re * w_re - x_im * w_im, x_re * w_im + x_im * w_re);
+        data[start + k] = (u_re + v_re, u_im + v_im);
+        data[start + k + length / 2] = (u_re - v_re, u_im - v_im);
+        (w_re, w_im) = (w_re * cos - w_im * sin, w_re * sin + w_im * cos);
+      }
+    }
+    length <<= 1;
+  }
+}
+
+/// Gaussian elimination with partial pivoting; singular systems give zeros.
+fn solve(mut matrix: [[f64; 3]; 3], mut target: [f64; 3]) -> [f64; 3] {
+  for column in 0..3 {
+    let pivot = (column..3).max_by(|a, b| matrix[*a][column].abs().partial_cmp(&matrix[*b][column].abs()).unwrap()).unwrap();
+    if matrix[pivot][column].abs() < 1e-12 {
+      return [0.0; 3];
+    }
+    matrix.swap(column, pivot);
+    target.swap(column, pivot);
+    for row in (column + 1)..3 {
+      let factor = matrix[row][column] / matrix[column][column];
+      let pivot_row = matrix[column];
+      for (value, pivot_value) in matrix[row].iter_mut().zip(pivot_row.iter()).skip(column) {
+        *value -= factor * pivot_value;
+      }
+      target[row] -= factor * target[column];
+    }
+  }
+  let mut solution = [0.0; 3];
+  for row in (0..3).rev() {
+    let known: f64 = ((row + 1)..3).map(|k| matrix[row][k] * solution[k]).sum();
+    solution[row] = (target[row] - known) / matrix[row][row];
+  }
+  solution
+}
+
+/// A detected cycle and the synodic period, or whole fraction of one, nearest to it.
+#[derive(Debug, Clone)]
+pub struct SynodicAlignment {
+  pub cycle: Cycle,
+  pub synodic: SynodicPeriod,
+  /// The cycle is compared to `synodic.days / harmonic`, e.g. 2 for half a Jupiter-Saturn cycle
+  pub harmonic: u32,
+  /// (cycle period - harmonic period) / harmonic period
+  pub relative_error: f64,
+}
+
+impl SynodicAlignment {
+  pub fn is_aligned(&self, tolerance: f64) -> bool {
+    self.relative_error.abs() <= tolerance
+  }
+}
+
+/// Detected cycles next to the nearest known planetary period.
+#[derive(Debug, Clone)]
+pub struct SynodicReport {
+  pub alignments: Vec<SynodicAlignment>,
+  /// Largest relative error counted as aligned
+  pub tolerance: f64,
+}
+
+impl SynodicReport {
+  /// Compare each of `cycles` to `synodic_periods` and their first `max_harmonic` harmonics.
+  pub fn new(cycles: &[Cycle], synodic_periods: &[SynodicPeriod], max_harmonic: u32, tolerance: f64) -> Self {
+    let alignments = cycles.iter()
+      .filter_map(|cycle| {
+        synodic_periods.iter()
+          .flat_map(|synodic| (1..=max_harmonic.max(1)).map(move |harmonic| (*synodic, harmonic)))
+          .map(|(synodic, harmonic)| {
+            let expected = synodic.days / harmonic as f64;
+            SynodicAlignment {
+              cycle: cycle.clone(),
+              synodic,
+              harmonic,
+              relative_error: (cycle.period - expected) / expected,
+            }
+          })
+          .min_by(|a, b| a.relative_error.abs().partial_cmp(&b.relative_error.abs()).unwrap())
+      })
+      .collect();
+    Self {
+      alignments,
+      tolerance
+    }
+  }
+
+  pub fn aligned(&self) -> Vec<&SynodicAlignment> {
+    self.alignments.iter().filter(|alignment| alignment.is_aligned(self.tolerance)).collect()
+  }
+
+  pub fn print(&self) {
+    println!("METHOD\t\tPERIOD (DAYS)\tYEARS\tSTRENGTH\tNEAREST\t\tSYNODIC (DAYS)\tERROR\tALIGNED");
+    for alignment in self.alignments.iter() {
+      let nearest = if alignment.harmonic == 1 {
+        alignment.synodic.name.to_string()
+      } else {
+        format!("{}/{}", alignment.synodic.name, alignment.harmonic)
+      };
+      println!(
+        "{}\t{:.2}\t\t{:.2}\t{:.2}%\t\t{}\t\t{:.2}\t\t{:+.1}%\t{}",
+        alignment.cycle.method.as_string(),
+        alignment.cycle.period,
+        alignment.cycle.period_years(),
+        alignment.cycle.strength * 100.0,
+        nearest,
+        alignment.synodic.days / alignment.harmonic as f64,
+        alignment.relative_error * 100.0,
+        if alignment.is_aligned(self.tolerance) { "yes" } else { "no" }
+      );
+    }
+  }
+}
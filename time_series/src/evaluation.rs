@@ -0,0 +1,181 @@
+use crate::*;
+
+/// Forecast of a reversal on a date, with the expected type if the signal predicts one.
+#[derive(Debug, Clone)]
+pub struct Forecast {
+  pub date: Time,
+  pub reversal_type: Option<ReversalType>,
+}
+
+impl Forecast {
+  pub fn new(date: Time, reversal_type: Option<ReversalType>) -> Self {
+    Self { date, reversal_type }
+  }
+
+  /// Forecasts without a direction, e.g. eclipse or alignment dates.
+  pub fn from_dates(dates: &[Time]) -> Vec<Self> {
+    dates.iter().map(|date| Self::new(*date, None)).collect()
+  }
+
+  /// A long signal forecasts a bottom, a short signal a top.
+  pub fn from_trade_signals(signals: &[TradeSignal]) -> Vec<Self> {
+    signals.iter()
+      .map(|signal| match signal.side {
+        Side::Long => Self::new(signal.date, Some(ReversalType::Bottom)),
+        Side::Short => Self::new(signal.date, Some(ReversalType::Top)),
+      })
+      .collect()
+  }
+
+  /// Forecasts from `TickerData::square_price_reversals`.
+  /// Predictions without a candle are past the end of the price history and can't be verified, so they are skipped.
+  pub fn from_predictions(predictions: &[ReversalPrediction]) -> Vec<Self> {
+    predictions.iter()
+      .filter(|prediction| prediction.candle.is_some())
+      .map(|prediction| Self::new(prediction.date, None))
+      .collect()
+  }
+}
+
+/// A forecast paired with the reversal it caught.
+#[derive(Debug, Clone)]
+pub struct ForecastMatch {
+  pub forecast: Forecast,
+  pub reversal: Reversal,
+  /// Days from forecast to reversal. Positive if the reversal came after the forecast.
+  pub error_days: i64,
+}
+
+impl ForecastMatch {
+  /// `None` if the forecast had no direction.
+  pub fn direction_correct(&self) -> Option<bool> {
+    self.forecast.reversal_type.as_ref().map(|reversal_type| *reversal_type == self.reversal.reversal_type)
+  }
+}
+
+/// Precision and recall of forecasts against ground-truth reversals.
+/// Each reversal can be caught by at most one forecast and each forecast catches at most one reversal.
+#[derive(Debug, Clone)]
+pub struct ForecastEvaluation {
+  pub tolerance_days: i64,
+  pub matches: Vec<ForecastMatch>,
+  /// False positives
+  pub unmatched_forecasts: Vec<Forecast>,
+  /// False negatives
+  pub missed_reversals: Vec<Reversal>,
+}
+
+impl ForecastEvaluation {
+  /// Match forecasts to reversals within `tolerance_days`, closest pairs first.
+  pub fn new(forecasts: &[Forecast], reversals: &[Reversal], tolerance_days: i64) -> Self {
+    // every candidate pair within tolerance, sorted by distance
+    let mut pairs = Vec::<(usize, usize, i64)>::new();
+    for (forecast_index, forecast) in forecasts.iter().enumerate() {
+      for (reversal_index, reversal) in reversals.iter().enumerate() {
+        let error_days = forecast.date.diff_days(&reversal.candle.date);
+        if error_days.abs() <= tolerance_days {
+          pairs.push((forecast_index, reversal_index, error_days));
+        }
+      }
+    }
+    pairs.sort_by_key(|(_, _, error_days)| error_days.abs());
+
+    let mut forecast_matched = vec![false; forecasts.len()];
+    let mut reversal_matched = vec![false; reversals.len()];
+    let mut matches = Vec::<ForecastMatch>::new();
+    for (forecast_index, reversal_index, error_days) in pairs.into_iter() {
+      if forecast_matched[forecast_index] || reversal_matched[reversal_index] {
+        continue;
+      }
+      forecast_matched[forecast_index] = true;
+      reversal_matched[reversal_index] = true;
+      matches.push(ForecastMatch {
+        forecast: forecasts[forecast_index].clone(),
+        reversal: reversals[reversal_index].clone(),
+        error_days,
+      });
+    }
+    matches.sort_by(|a, b| a.forecast.date.partial_cmp(&b.forecast.date).unwrap());
+
+    Self {
+      tolerance_days,
+      matches,
+      unmatched_forecasts: forecasts.iter().zip(forecast_matched.iter())
+        .filter(|(_, matched)| !**matched)
+        .map(|(forecast, _)| forecast.clone())
+        .collect(),
+      missed_reversals: reversals.iter().zip(reversal_matched.iter())
+        .filter(|(_, matched)| !**matched)
+        .map(|(reversal, _)| reversal.clone())
+        .collect(),
+    }
+  }
+
+  /// Fraction of forecasts that caught a reversal.
+  pub fn precision(&self) -> f64 {
+    let total = self.matches.len() + self.unmatched_forecasts.len();
+    if total == 0 { 0.0 } else { self.matches.len() as f64 / total as f64 }
+  }
+
+  /// Fraction of reversals caught by a forecast.
+  pub fn recall(&self) -> f64 {
+    let total = self.matches.len() + self.missed_reversals.len();
+    if total == 0 { 0.0 } else { self.matches.len() as f64 / total as f64 }
+  }
+
+  pub fn f1(&self) -> f64 {
+    let (precision, recall) = (self.precision(), self.recall());
+    if precision + recall == 0.0 { 0.0 } else { 2.0 * precision * recall / (precision + recall) }
+  }
+
+  pub fn mean_error_days(&self) -> f64 {
+    if self.matches.is_empty() {
+      return 0.0;
+    }
+    self.matches.iter().map(|m| m.error_days as f64).sum::<f64>() / self.matches.len() as f64
+  }
+
+  pub fn mean_absolute_error_days(&self) -> f64 {
+    if self.matches.is_empty() {
+      return 0.0;
+    }
+    self.matches.iter().map(|m| m.error_days.abs() as f64).sum::<f64>() / self.matches.len() as f64
+  }
+
+  /// Number of matches for each timing error from `-tolerance_days` to `tolerance_days`.
+  pub fn timing_error_distribution(&self) -> Vec<(i64, usize)> {
+    (-self.tolerance_days..=self.tolerance_days)
+      .map(|error_days| (error_days, self.matches.iter().filter(|m| m.error_days == error_days).count()))
+      .collect()
+  }
+
+  /// Fraction of matched forecasts with a direction whose type (top/bottom) was correct.
+  /// `None` if no matched forecast had a direction.
+  pub fn direction_accuracy(&self) -> Option<f64> {
+    let directions: Vec<bool> = self.matches.iter().filter_map(|m| m.direction_correct()).collect();
+    if directions.is_empty() {
+      return None;
+    }
+    Some(directions.iter().filter(|correct| **correct).count() as f64 / directions.len() as f64)
+  }
+
+  pub fn print(&self) {
+    println!("PRECISION\tRECALL\tF1\tMATCHED\tFALSE POSITIVES\tMISSED\tMEAN ERROR\tMEAN ABS ERROR\tDIRECTION");
+    println!(
+      "{:.1}%\t\t{:.1}%\t{:.3}\t{}\t{}\t\t{}\t{:.2}\t\t{:.2}\t\t{}",
+      self.precision() * 100.0,
+      self.recall() * 100.0,
+      self.f1(),
+      self.matches.len(),
+      self.unmatched_forecasts.len(),
+      self.missed_reversals.len(),
+      self.mean_error_days(),
+      self.mean_absolute_error_days(),
+      self.direction_accuracy().map_or("-".to_string(), |accuracy| format!("{:.1}%", accuracy * 100.0))
+    );
+    println!("ERROR DAYS\tMATCHES");
+    for (error_days, count) in self.timing_error_distribution() {
+      println!("{}\t\t{}", error_days, count);
+    }
+  }
+}
@@ -0,0 +1,258 @@
+use log::debug;
+use plotters::prelude::*;
+use crate::*;
+
+/// z-score of a two-sided 95% confidence interval
+const CONFIDENCE_Z: f64 = 1.96;
+
+/// Price path around one event, anchored at the close of the first candle on or after the event date.
+#[derive(Debug, Clone)]
+pub struct EventPath {
+  pub event_date: Time,
+  /// Candle at offset 0
+  pub anchor: Candle,
+  /// Cumulative percent return from the anchor close for each offset in `EventStudy::offsets`
+  pub returns: Vec<f64>,
+}
+
+/// Average price behaviour in a window of candles around a list of event dates.
+/// Offsets are in candles (trading days for a daily series), not calendar days.
+#[derive(Debug, Clone)]
+pub struct EventStudy {
+  /// Candles before the event
+  pub pre_candles: usize,
+  /// Candles after the event
+  pub post_candles: usize,
+  /// `-pre_candles..=post_candles`
+  pub offsets: Vec<i64>,
+  pub paths: Vec<EventPath>,
+  /// Events without enough candles before or after to fill the window
+  pub skipped_count: usize,
+  pub mean_path: Vec<f64>,
+  pub median_path: Vec<f64>,
+  /// Lower 95% confidence band of `mean_path`
+  pub lower_band: Vec<f64>,
+  /// Upper 95% confidence band of `mean_path`
+  pub upper_band: Vec<f64>,
+  /// Fraction of events with a positive cumulative return at each offset
+  pub hit_rate: Vec<f64>,
+  /// Mean standard deviation of daily percent returns before the event
+  pub volatility_before: f64,
+  /// Mean standard deviation of daily percent returns after the event
+  pub volatility_after: f64,
+}
+
+impl EventStudy {
+  /// Build an event study from any list of event dates, e.g. retrograde stations, eclipses,
+  /// `PlanetPairAlignment` or equator cross dates.
+  /// Duplicate events that land on the same candle are counted once.
+  pub fn new(ticker_data: &TickerData, event_dates: &[Time], pre_candles: usize, post_candles: usize) -> Self {
+    let candles = ticker_data.get_candles();
+    let offsets: Vec<i64> = (-(pre_candles as i64)..=post_candles as i64).collect();
+
+    let mut paths = Vec::<EventPath>::new();
+    let mut anchor_indices = Vec::<usize>::new();
+    let mut skipped_count = 0;
+    for event_date in event_dates.iter() {
+      let index = match candles.iter().position(|candle| candle.date >= *event_date) {
+        Some(index) => index,
+        None => {
+          skipped_count += 1;
+          continue;
+        }
+      };
+      if index < pre_candles || index + post_candles >= candles.len() {
+        debug!("Skip event {}: window exceeds candle history", event_date.as_string());
+        skipped_count += 1;
+        continue;
+      }
+      if anchor_indices.contains(&index) {
+        continue;
+      }
+      anchor_indices.push(index);
+      let anchor = &candles[index];
+      let returns = offsets.iter()
+        .map(|offset| {
+          let candle = &candles[(index as i64 + offset) as usize];
+          (candle.close / anchor.close - 1.0) * 100.0
+        })
+        .collect();
+      paths.push(EventPath {
+        event_date: *event_date,
+        anchor: anchor.clone(),
+        returns,
+      });
+    }
+
+    let mut mean_path = Vec::<f64>::new();
+    let mut median_path = Vec::<f64>::new();
+    let mut lower_band = Vec::<f64>::new();
+    let mut upper_band = Vec::<f64>::new();
+    let mut hit_rate = Vec::<f64>::new();
+    for offset_index in 0..offsets.len() {
+      let returns: Vec<f64> = paths.iter().map(|path| path.returns[offset_index]).collect();
+      let mean = Self::mean(&returns);
+      let standard_error = if returns.len() > 1 {
+        Self::std_dev(&returns) / (returns.len() as f64).sqrt()
+      } else {
+        0.0
+      };
+      mean_path.push(mean);
+      median_path.push(Self::median(&returns));
+      lower_band.push(mean - CONFIDENCE_Z * standard_error);
+      upper_band.push(mean + CONFIDENCE_Z * standard_error);
+      let positive_count = returns.iter().filter(|r| **r > 0.0).count();
+      hit_rate.push(if returns.is_empty() { 0.0 } else { positive_count as f64 / returns.len() as f64 });
+    }
+
+    let (volatility_before, volatility_after) = Self::volatility(candles, &anchor_indices, pre_candles, post_candles);
+
+    Self {
+      pre_candles,
+      post_candles,
+      offsets,
+      paths,
+      skipped_count,
+      mean_path,
+      median_path,
+      lower_band,
+      upper_band,
+      hit_rate,
+      volatility_before,
+      volatility_after,
+    }
+  }
+
+  /// Mean standard deviation of daily percent returns across events, before and after the anchor candle.
+  fn volatility(candles: &[Candle], anchor_indices: &[usize], pre_candles: usize, post_candles: usize) -> (f64, f64) {
+    let daily_returns = |start: usize, end: usize| -> Vec<f64> {
+      (start + 1..=end)
+        .map(|index| (candles[index].close / candles[index - 1].close - 1.0) * 100.0)
+        .collect()
+    };
+    let mut before = Vec::<f64>::new();
+    let mut after = Vec::<f64>::new();
+    for index in anchor_indices.iter() {
+      if pre_candles > 1 {
+        before.push(Self::std_dev(&daily_returns(index - pre_candles, *index)));
+      }
+      if post_candles > 1 {
+        after.push(Self::std_dev(&daily_returns(*index, index + post_candles)));
+      }
+    }
+    (Self::mean(&before), Self::mean(&after))
+  }
+
+  fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+      return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+  }
+
+  /// Sample standard deviation
+  fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+      return 0.0;
+    }
+    let mean = Self::mean(values);
+    let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (values.len() - 1) as f64;
+    variance.sqrt()
+  }
+
+  fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+      return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+      (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+      sorted[middle]
+    }
+  }
+
+  /// Index into the per-offset vectors for `offset` candles from the event.
+  pub fn offset_index(&self, offset: i64) -> Option<usize> {
+    self.offsets.iter().position(|o| *o == offset)
+  }
+
+  /// Percent change in volatility after the event compared to before.
+  pub fn volatility_change(&self) -> f64 {
+    if self.volatility_before == 0.0 {
+      return 0.0;
+    }
+    (self.volatility_after / self.volatility_before - 1.0) * 100.0
+  }
+
+  pub fn print(&self) {
+    println!(
+      "Events: {}\t\tSkipped: {}\t\tVolatility Before: {:.2}%\t\tVolatility After: {:.2}%\t\tChange: {:.1}%",
+      self.paths.len(),
+      self.skipped_count,
+      self.volatility_before,
+      self.volatility_after,
+      self.volatility_change()
+    );
+    println!("OFFSET\tMEAN\tMEDIAN\tLOWER\tUPPER\tHIT RATE");
+    for (index, offset) in self.offsets.iter().enumerate() {
+      println!(
+        "{}\t{:.2}%\t{:.2}%\t{:.2}%\t{:.2}%\t{:.0}%",
+        offset,
+        self.mean_path[index],
+        self.median_path[index],
+        self.lower_band[index],
+        self.upper_band[index],
+        self.hit_rate[index] * 100.0
+      );
+    }
+  }
+
+  /// Plot the mean cumulative return path with its confidence bands, and the median path.
+  pub fn plot(&self, out_file: &str, plot_title: &str, plot_color: &RGBColor) {
+    let root = BitMapBackend::new(out_file, (2048, 1024)).into_drawing_area();
+    root.fill(&WHITE).unwrap();
+    let from_offset = -(self.pre_candles as i64);
+    let to_offset = self.post_candles as i64;
+    let min_return = self.lower_band.iter().chain(self.median_path.iter()).fold(0.0, |min: f64, r| min.min(*r));
+    let max_return = self.upper_band.iter().chain(self.median_path.iter()).fold(0.0, |max: f64, r| max.max(*r));
+    // pad y axis so the paths don't touch the frame
+    let padding = ((max_return - min_return) * 0.1).max(0.1);
+    let mut chart = ChartBuilder::on(&root)
+      .x_label_area_size(40)
+      .y_label_area_size(40)
+      .caption(plot_title, ("sans-serif", 50.0).into_font())
+      .build_cartesian_2d(from_offset..to_offset, (min_return - padding)..(max_return + padding)).unwrap();
+    chart.configure_mesh()
+      .light_line_style(WHITE)
+      .x_desc("Candles from event")
+      .y_desc("Cumulative return %")
+      .draw().unwrap();
+    // event candle and zero return
+    chart.draw_series(LineSeries::new(vec![(0, min_return - padding), (0, max_return + padding)], &BLACK)).unwrap();
+    chart.draw_series(LineSeries::new(vec![(from_offset, 0.0), (to_offset, 0.0)], &BLACK)).unwrap();
+    // confidence band between the upper band and the reversed lower band
+    let band: Vec<(i64, f64)> = self.offsets.iter().zip(self.upper_band.iter())
+      .chain(self.offsets.iter().zip(self.lower_band.iter()).rev())
+      .map(|(o, r)| (*o, *r))
+      .collect();
+    chart.draw_series(std::iter::once(Polygon::new(band, plot_color.mix(0.2).filled()))).unwrap();
+    // mean and median paths
+    chart.draw_series(
+      LineSeries::new(self.offsets.iter().zip(self.mean_path.iter()).map(|(o, r)| (*o, *r)), plot_color.stroke_width(3))
+    ).unwrap()
+      .label("Mean")
+      .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], *plot_color));
+    chart.draw_series(
+      LineSeries::new(self.offsets.iter().zip(self.median_path.iter()).map(|(o, r)| (*o, *r)), &BLACK)
+    ).unwrap()
+      .label("Median")
+      .legend(|(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], BLACK));
+    chart.configure_series_labels().background_style(WHITE).border_style(BLACK).draw().unwrap();
+    // To avoid the IO failure being ignored silently, we manually call the present function
+    root.present().expect("Unable to write result to file, please make sure the output dir exists");
+    println!("Result has been saved to {}", out_file);
+  }
+}
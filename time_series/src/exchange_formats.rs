@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use log::debug;
+use crate::*;
+
+/// Price history export of an exchange or data vendor.
+/// Each parses into candles sorted by timestamp, ready for `TickerData::add_series`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExchangeFormat {
+  /// data.binance.vision kline CSV without a header: open time, OHLCV, close time, ...
+  /// Open times may be milliseconds or, since 2025, microseconds.
+  BinanceCsv,
+  /// `/api/v3/klines` response: an array of `[open time, "open", "high", "low", "close", "volume", close time, ...]`
+  BinanceJson,
+  /// Coinbase Exchange `/products/{id}/candles` (`[[time, low, high, open, close, volume], ...]`)
+  /// or Advanced Trade `{"candles": [{"start", "low", "high", "open", "close", "volume"}, ...]}`
+  CoinbaseJson,
+  /// Yahoo Finance historical download: `Date,Open,High,Low,Close,Adj Close,Volume`. Rows of "null" are skipped.
+  /// If `adjusted`, OHLC are scaled by `Adj Close / Close` for splits and dividends.
+  YahooCsv { adjusted: bool },
+  /// Stooq download: `Date,Open,High,Low,Close,Volume`, or with a `Time` column for intraday bars
+  /// stamped in local time `utc_offset_seconds` east of UTC (Stooq uses Warsaw time).
+  StooqCsv { utc_offset_seconds: i32 },
+}
+
+impl ExchangeFormat {
+  pub fn as_string(&self) -> &str {
+    match self {
+      ExchangeFormat::BinanceCsv => "Binance CSV",
+      ExchangeFormat::BinanceJson => "Binance JSON",
+      ExchangeFormat::CoinbaseJson => "Coinbase JSON",
+      ExchangeFormat::YahooCsv { .. } => "Yahoo CSV",
+      ExchangeFormat::StooqCsv { .. } => "Stooq CSV",
+    }
+  }
+
+  pub fn read(&self, path: &PathBuf) -> std::io::Result<Vec<Candle>> {
+    let text = std::fs::read_to_string(path)?;
+    self.parse(&text).map_err(|error| std::io::Error::new(error.kind(), format!("{}: {}", path.display(), error)))
+  }
+
+  /// Candles in UTC sorted by timestamp. Daily candles from date-only exports open at midnight UTC of
+  /// the trading date; the interval is inferred from the timestamps.
+  pub fn parse(&self, text: &str) -> std::io::Result<Vec<Candle>> {
+    let mut candles = match self {
+      ExchangeFormat::BinanceCsv => Self::parse_binance_csv(text)?,
+      ExchangeFormat::BinanceJson => Self::parse_binance_json(text)?,
+      ExchangeFormat::CoinbaseJson => Self::parse_coinbase_json(text)?,
+      ExchangeFormat::YahooCsv { adjusted } => Self::parse_yahoo_csv(text, *adjusted)?,
+      ExchangeFormat::StooqCsv { utc_offset_seconds } => Self::parse_stooq_csv(text, *utc_offset_seconds)?,
+    };
+    candles.sort_by_key(|candle| candle.timestamp);
+    let interval = TickerData::infer_interval(&candles);
+    for candle in candles.iter_mut() {
+      candle.interval = interval;
+    }
+    debug!("Parsed {} candles from {}", candles.len(), self.as_string());
+    Ok(candles)
+  }
+
+  fn invalid(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+  }
+
+  fn candle(timestamp: i64, open: f64, high: f64, low: f64, close: f64, volume: Option<f64>) -> Candle {
+    Candle {
+      date: Time::from_unix(timestamp),
+      timestamp,
+      interval: Interval::Daily,
+      open,
+      high,
+      low,
+      close,
+      volume
+    }
+  }
+
+  /// Binance open times in milliseconds, or microseconds from 2025, to seconds.
+  fn binance_seconds(open_time: i64) -> i64 {
+    if open_time >= 100_000_000_000_000 {
+      open_time.div_euclid(1_000_000)
+    } else {
+      open_time.div_euclid(1000)
+    }
+  }
+
+  fn parse_binance_csv(text: &str) -> std::io::Result<Vec<Candle>> {
+    let mut csv = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(text.as_bytes());
+    let mut candles = Vec::<Candle>::new();
+    for record in csv.records() {
+      let record = record.map_err(|error| Self::invalid(error.to_string()))?;
+      let line = record.position().map_or(0, |position| position.line());
+      let field = |index: usize, name: &str| record.get(index)
+        .ok_or_else(|| Self::invalid(format!("line {}: missing {}", line, name)));
+      let number = |index: usize, name: &str| field(index, name).and_then(|value| value.trim().parse::<f64>()
+        .map_err(|_| Self::invalid(format!("line {}: invalid {} \"{}\"", line, name, value))));
+      // exports from the UI have a header row
+      if line == 1 && field(0, "open time")?.parse::<i64>().is_err() {
+        continue;
+      }
+      let open_time = field(0, "open time")?.trim().parse::<i64>()
+        .map_err(|_| Self::invalid(format!("line {}: invalid open time", line)))?;
+      candles.push(Self::candle(
+        Self::binance_seconds(open_time),
+        number(1, "open")?,
+        number(2, "high")?,
+        number(3, "low")?,
+        number(4, "close")?,
+        Some(number(5, "volume")?)
+      ));
+    }
+    Ok(candles)
+  }
+
+  /// A JSON number or numeric string.
+  fn json_number(value: &serde_json::Value) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_str().and_then(|value| value.parse::<f64>().ok()))
+  }
+
+  fn parse_binance_json(text: &str) -> std::io::Result<Vec<Candle>> {
+    let json: serde_json::Value = serde_json::from_str(text).map_err(|error| Self::invalid(error.to_string()))?;
+    let klines = json.as_array().ok_or_else(|| Self::invalid(format!("expected an array of klines: {}", json)))?;
+    let mut candles = Vec::<Candle>::new();
+    for (index, kline) in klines.iter().enumerate() {
+      let number = |field: usize, name: &str| Self::json_number(&kline[field])
+        .ok_or_else(|| Self::invalid(format!("kline {}: invalid {} {}", index, name, kline[field])));
+      let open_time = kline[0].as_i64().ok_or_else(|| Self::invalid(format!("kline {}: invalid open time", index)))?;
+      candles.push(Self::candle(
+        Self::binance_seconds(open_time),
+        number(1, "open")?,
+        number(2, "high")?,
+        number(3, "low")?,
+        number(4, "close")?,
+        Some(number(5, "volume")?)
+      ));
+    }
+    Ok(candles)
+  }
+
+  fn parse_coinbase_json(text: &str) -> std::io::Result<Vec<Candle>> {
+    let json: serde_json::Value = serde_json::from_str(text).map_err(|error| Self::invalid(error.to_string()))?;
+    let mut candles = Vec::<Candle>::new();
+    // Advanced Trade wraps named fields, the Exchange API returns bare arrays
+    if let Some(named) = json["candles"].as_array() {
+      for (index, value) in named.iter().enumerate() {
+        let number = |name: &str| Self::json_number(&value[name])
+          .ok_or_else(|| Self::invalid(format!("candle {}: invalid {} {}", index, name, value[name])));
+        candles.push(Self::candle(
+          number("start")? as i64,
+          number("open")?,
+          number("high")?,
+          number("low")?,
+          number("close")?,
+          Some(number("volume")?)
+        ));
+      }
+      return Ok(candles);
+    }
+    let rows = json.as_array().ok_or_else(|| Self::invalid(format!("expected an array of candles: {}", json)))?;
+    for (index, row) in rows.iter().enumerate() {
+      let number = |field: usize, name: &str| Self::json_number(&row[field])
+        .ok_or_else(|| Self::invalid(format!("candle {}: invalid {} {}", index, name, row[field])));
+      candles.push(Self::candle(
+        number(0, "time")? as i64,
+        number(3, "open")?,
+        number(2, "high")?,
+        number(1, "low")?,
+        number(4, "close")?,
+        Some(number(5, "volume")?)
+      ));
+    }
+    Ok(candles)
+  }
+
+  /// CSV with a header, indexing columns by case-insensitive name.
+  fn csv_records(text: &str) -> std::io::Result<(HashMap<String, usize>, Vec<csv::StringRecord>)> {
+    let mut csv = csv::ReaderBuilder::new().flexible(true).trim(csv::Trim::All).from_reader(text.as_bytes());
+    let headers = csv.headers().map_err(|error| Self::invalid(error.to_string()))?.iter()
+      .enumerate()
+      .map(|(index, header)| (header.to_lowercase(), index))
+      .collect();
+    let records = csv.records().collect::<Result<Vec<_>, _>>().map_err(|error| Self::invalid(error.to_string()))?;
+    Ok((headers, records))
+  }
+
+  fn column(headers: &HashMap<String, usize>, name: &str) -> std::io::Result<usize> {
+    headers.get(name).copied().ok_or_else(|| Self::invalid(format!("line 1: no {} column", name)))
+  }
+
+  fn parse_yahoo_csv(text: &str, adjusted: bool) -> std::io::Result<Vec<Candle>> {
+    let (headers, records) = Self::csv_records(text)?;
+    let date = Self::column(&headers, "date")?;
+    let open = Self::column(&headers, "open")?;
+    let high = Self::column(&headers, "high")?;
+    let low = Self::column(&headers, "low")?;
+    let close = Self::column(&headers, "close")?;
+    let adj_close = match adjusted {
+      true => Some(Self::column(&headers, "adj close")?),
+      false => None,
+    };
+    let volume = headers.get("volume").copied();
+
+    let mut candles = Vec::<Candle>::new();
+    for record in records.iter() {
+      let line = record.position().map_or(0, |position| position.line());
+      // holidays Yahoo lists without prices
+      if record.iter().any(|value| value == "null") {
+        continue;
+      }
+      let number = |index: usize, name: &str| record.get(index).unwrap_or("").parse::<f64>()
+        .map_err(|_| Self::invalid(format!("line {}: invalid {} \"{}\"", line, name, record.get(index).unwrap_or(""))));
+      let timestamp = DateFormat::IsoDate.parse(record.get(date).unwrap_or(""))
+        .map_err(|error| Self::invalid(format!("line {}: {}", line, error)))?;
+      let raw_close = number(close, "close")?;
+      let factor = match adj_close {
+        Some(adj_close) => number(adj_close, "adj close")? / raw_close,
+        None => 1.0,
+      };
+      candles.push(Self::candle(
+        timestamp,
+        number(open, "open")? * factor,
+        number(high, "high")? * factor,
+        number(low, "low")? * factor,
+        raw_close * factor,
+        volume.map(|volume| number(volume, "volume")).transpose()?
+      ));
+    }
+    Ok(candles)
+  }
+
+  fn parse_stooq_csv(text: &str, utc_offset_seconds: i32) -> std::io::Result<Vec<Candle>> {
+    let offset = FixedOffset::east_opt(utc_offset_seconds)
+      .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("invalid UTC offset {}", utc_offset_seconds)))?;
+    let (headers, records) = Self::csv_records(text)?;
+    let date = Self::column(&headers, "date")?;
+    let time = headers.get("time").copied();
+    let open = Self::column(&headers, "open")?;
+    let high = Self::column(&headers, "high")?;
+    let low = Self::column(&headers, "low")?;
+    let close = Self::column(&headers, "close")?;
+    let volume = headers.get("volume").copied();
+
+    let mut candles = Vec::<Candle>::new();
+    for record in records.iter() {
+      let line = record.position().map_or(0, |position| position.line());
+      let field = |index: usize| record.get(index).unwrap_or("");
+      let number = |index: usize, name: &str| field(index).parse::<f64>()
+        .map_err(|_| Self::invalid(format!("line {}: invalid {} \"{}\"", line, name, field(index))));
+      let day = NaiveDate::parse_from_str(field(date), "%Y-%m-%d")
+        .map_err(|_| Self::invalid(format!("line {}: invalid date \"{}\"", line, field(date))))?;
+      // daily bars are trading dates, intraday bars are local times
+      let timestamp = match time {
+        None => Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap()).timestamp(),
+        Some(time) => {
+          let time_of_day = NaiveTime::parse_from_str(field(time), "%H:%M:%S")
+            .map_err(|_| Self::invalid(format!("line {}: invalid time \"{}\"", line, field(time))))?;
+          offset.from_local_datetime(&NaiveDateTime::new(day, time_of_day)).single()
+            .ok_or_else(|| Self::invalid(format!("line {}: ambiguous local time", line)))?
+            .timestamp()
+        }
+      };
+      let volume = match volume {
+        Some(index) if !field(index).is_empty() => Some(number(index, "volume")?),
+        _ => None,
+      };
+      candles.push(Self::candle(
+        timestamp,
+        number(open, "open")?,
+        number(high, "high")?,
+        number(low, "low")?,
+        number(close, "close")?,
+        volume
+      ));
+    }
+    Ok(candles)
+  }
+}
+
+/// Merge candles of the same ticker from several sources, earlier sources taking precedence.
+/// Candles overlap if they share an interval and timestamp, or for daily and coarser candles the
+/// same date, since vendors stamp sessions at different times of day. A lower-precedence source
+/// only fills dates the sources before it lack. Sorted by timestamp.
+pub fn merge_sources(sources: &[Vec<Candle>]) -> Vec<Candle> {
+  let key = |candle: &Candle| match candle.interval.is_intraday() {
+    true => (candle.interval, candle.timestamp),
+    false => (candle.interval, candle.date.to_unix()),
+  };
+  let mut merged = HashMap::<(Interval, i64), Candle>::new();
+  for (index, candles) in sources.iter().enumerate() {
+    let before = merged.len();
+    for candle in candles.iter() {
+      merged.entry(key(candle)).or_insert_with(|| candle.clone());
+    }
+    debug!("Source {} added {} of {} candles", index, merged.len() - before, candles.len());
+  }
+  let mut candles: Vec<Candle> = merged.into_values().collect();
+  candles.sort_by_key(|candle| candle.timestamp);
+  candles
+}
@@ -1,5 +1,6 @@
 use chrono::Duration;
 use chrono::{Local, NaiveDate, TimeZone};
+use log::warn;
 use crate::*;
 use plotters::prelude::*;
 
@@ -39,9 +40,24 @@ impl PlotHDA {
   }
 
   /// Compute Historical Date Analysis
-  /// Compares the same date of each year for similar price action
+  /// Compares the same date of each year for similar price action.
+  /// Intraday candles are resampled to daily first, so `reversal_margin` counts days for any interval.
   pub fn hda(&self, ticker_data: &TickerData) -> Vec<HDA> {
     let mut daily_hda = Vec::<HDA>::new();
+    let daily_candles;
+    let ticker_data = match ticker_data.interval() {
+      Some(interval) if interval.is_intraday() => match ticker_data.resample(&Timeframe::Hours(24)) {
+        Ok(daily) => {
+          daily_candles = daily;
+          &daily_candles
+        },
+        Err(error) => {
+          warn!("HDA on {} candles: {}", interval.to_str(), error);
+          ticker_data
+        },
+      },
+      _ => ticker_data,
+    };
 
     // compute number of cycles possible in candle history
     let earliest_date = ticker_data.earliest_date();
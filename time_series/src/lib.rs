@@ -6,6 +6,21 @@ pub mod rapid_api;
 pub mod time;
 pub mod pfs;
 pub mod hda;
+pub mod event_study;
+pub mod simulation;
+pub mod walk_forward;
+pub mod optimizer;
+pub mod evaluation;
+pub mod swing;
+pub mod calendar;
+pub mod resample;
+pub mod csv_schema;
+pub mod quality;
+pub mod adjustment;
+pub mod market_data;
+pub mod exchange_formats;
+pub mod store;
+pub mod cycles;
 
 pub use ticker_data::*;
 pub use candle::*;
@@ -15,3 +30,18 @@ pub use rapid_api::*;
 pub use time::*;
 pub use pfs::*;
 pub use hda::*;
+pub use event_study::*;
+pub use simulation::*;
+pub use walk_forward::*;
+pub use optimizer::*;
+pub use evaluation::*;
+pub use swing::*;
+pub use calendar::*;
+pub use resample::*;
+pub use csv_schema::*;
+pub use quality::*;
+pub use adjustment::*;
+pub use market_data::*;
+pub use exchange_formats::*;
+pub use store::*;
+pub use cycles::*;
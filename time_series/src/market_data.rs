@@ -0,0 +1,341 @@
+use std::fs::File;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use log::{debug, warn};
+use crate::*;
+
+/// Environment variable naming a JSON config of API keys, e.g. `{"RAPID_API_KEY": "..."}`.
+pub const MARKET_DATA_CONFIG_ENV: &str = "MARKET_DATA_CONFIG";
+/// Config read when `MARKET_DATA_CONFIG` isn't set.
+pub const MARKET_DATA_CONFIG_FILE: &str = "./market_data.json";
+
+/// API key `name` from the environment variable of that name, or else from the config file.
+pub fn api_key(name: &str) -> std::io::Result<String> {
+  if let Ok(key) = std::env::var(name) {
+    if !key.is_empty() {
+      return Ok(key);
+    }
+  }
+  let config_path = std::env::var(MARKET_DATA_CONFIG_ENV).unwrap_or_else(|_| MARKET_DATA_CONFIG_FILE.to_string());
+  let not_found = || std::io::Error::new(
+    std::io::ErrorKind::NotFound,
+    format!("{} is not set in the environment or {}", name, config_path)
+  );
+  let file = File::open(&config_path).map_err(|_| not_found())?;
+  let config: serde_json::Value = serde_json::from_reader(file)
+    .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", config_path, error)))?;
+  config[name].as_str().map(String::from).ok_or_else(not_found)
+}
+
+/// Candles of `symbol` from `start` to `end` inclusive, as UNIX timestamps in seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketDataRequest {
+  pub symbol: String,
+  pub interval: Interval,
+  pub start: i64,
+  pub end: i64,
+}
+
+/// Source of candles. Implementations make one request per page; `MarketDataClient` pages,
+/// retries and rate limits on top.
+pub trait MarketDataProvider {
+  fn name(&self) -> String;
+
+  /// Most candles one request returns.
+  fn page_size(&self) -> usize;
+
+  /// Up to `page_size` candles in the request's range, the latest ones if there are more, in any order.
+  /// Transient failures should use the error kinds `is_transient` retries.
+  fn fetch_page(&self, request: &MarketDataRequest) -> impl Future<Output = std::io::Result<Vec<Candle>>> + Send;
+}
+
+/// Errors worth retrying: timeouts, dropped connections, rate limits (`WouldBlock`) and server errors (`Interrupted`).
+pub fn is_transient(error: &std::io::Error) -> bool {
+  matches!(
+    error.kind(),
+    std::io::ErrorKind::TimedOut
+      | std::io::ErrorKind::ConnectionRefused
+      | std::io::ErrorKind::ConnectionReset
+      | std::io::ErrorKind::ConnectionAborted
+      | std::io::ErrorKind::WouldBlock
+      | std::io::ErrorKind::Interrupted
+  )
+}
+
+/// How `MarketDataClient` retries and paces requests.
+#[derive(Debug, Clone)]
+pub struct FetchPolicy {
+  /// Retries of a page after the first attempt
+  pub max_retries: u32,
+  /// Wait before the first retry, doubled for each one after
+  pub initial_backoff: Duration,
+  pub max_backoff: Duration,
+  /// Least time between the start of two requests
+  pub min_request_interval: Duration,
+  /// Stop paging after this many pages, in case a provider ignores the range
+  pub max_pages: usize,
+}
+
+impl Default for FetchPolicy {
+  fn default() -> Self {
+    Self {
+      max_retries: 3,
+      initial_backoff: Duration::from_secs(1),
+      max_backoff: Duration::from_secs(30),
+      min_request_interval: Duration::from_millis(250),
+      max_pages: 100,
+    }
+  }
+}
+
+impl FetchPolicy {
+  /// No waiting, for fixtures.
+  pub fn immediate() -> Self {
+    Self {
+      initial_backoff: Duration::ZERO,
+      max_backoff: Duration::ZERO,
+      min_request_interval: Duration::ZERO,
+      ..Default::default()
+    }
+  }
+
+  fn backoff(&self, retry: u32) -> Duration {
+    self.initial_backoff.saturating_mul(2u32.saturating_pow(retry)).min(self.max_backoff)
+  }
+}
+
+/// Pages a `MarketDataProvider` back from the end of a range, retrying transient errors with backoff.
+pub struct MarketDataClient<P: MarketDataProvider> {
+  pub provider: P,
+  pub policy: FetchPolicy,
+  last_request: tokio::sync::Mutex<Option<Instant>>,
+}
+
+impl<P: MarketDataProvider> MarketDataClient<P> {
+  pub fn new(provider: P) -> Self {
+    Self::with_policy(provider, FetchPolicy::default())
+  }
+
+  pub fn with_policy(provider: P, policy: FetchPolicy) -> Self {
+    Self {
+      provider,
+      policy,
+      last_request: tokio::sync::Mutex::new(None),
+    }
+  }
+
+  /// Candles of `symbol` from the start of `start` to the end of `end`, sorted by timestamp.
+  pub async fn fetch(&self, symbol: &str, interval: Interval, start: &Time, end: &Time) -> std::io::Result<Vec<Candle>> {
+    self.fetch_range(&MarketDataRequest {
+      symbol: symbol.to_string(),
+      interval,
+      start: start.to_unix(),
+      end: end.delta_date(1).to_unix() - 1,
+    }).await
+  }
+
+  /// The latest page of candles of `symbol`, sorted by timestamp.
+  pub async fn fetch_latest(&self, symbol: &str, interval: Interval) -> std::io::Result<Vec<Candle>> {
+    let mut candles = self.fetch_page(&MarketDataRequest {
+      symbol: symbol.to_string(),
+      interval,
+      start: 0,
+      end: Time::today().delta_date(1).to_unix() - 1,
+    }).await?;
+    candles.sort_by_key(|candle| candle.timestamp);
+    Ok(candles)
+  }
+
+  /// Candles in `request`'s range, one page at a time from the end, sorted by timestamp without duplicates.
+  pub async fn fetch_range(&self, request: &MarketDataRequest) -> std::io::Result<Vec<Candle>> {
+    let mut candles = Vec::<Candle>::new();
+    let mut page_request = request.clone();
+    for page in 0..self.policy.max_pages {
+      let fetched = self.fetch_page(&page_request).await?;
+      let full_page = fetched.len() >= self.provider.page_size();
+      let in_range: Vec<Candle> = fetched.into_iter()
+        .filter(|candle| candle.timestamp >= page_request.start && candle.timestamp <= page_request.end)
+        .collect();
+      let earliest = match in_range.iter().map(|candle| candle.timestamp).min() {
+        Some(earliest) => earliest,
+        None => break,
+      };
+      debug!("{} page {}: {} {} candles", self.provider.name(), page, in_range.len(), request.symbol);
+      candles.extend(in_range);
+      if !full_page || earliest <= request.start {
+        break;
+      }
+      page_request.end = earliest - 1;
+    }
+    candles.sort_by_key(|candle| candle.timestamp);
+    candles.dedup_by_key(|candle| candle.timestamp);
+    Ok(candles)
+  }
+
+  /// One page, retrying transient errors.
+  async fn fetch_page(&self, request: &MarketDataRequest) -> std::io::Result<Vec<Candle>> {
+    let mut retry = 0;
+    loop {
+      self.wait_for_rate_limit().await;
+      match self.provider.fetch_page(request).await {
+        Ok(candles) => return Ok(candles),
+        Err(error) if is_transient(&error) && retry < self.policy.max_retries => {
+          let backoff = self.policy.backoff(retry);
+          warn!("{} request failed ({}), retry {} in {:?}", self.provider.name(), error, retry + 1, backoff);
+          tokio::time::sleep(backoff).await;
+          retry += 1;
+        },
+        Err(error) => return Err(error),
+      }
+    }
+  }
+
+  async fn wait_for_rate_limit(&self) {
+    let mut last_request = self.last_request.lock().await;
+    if let Some(last) = *last_request {
+      let elapsed = last.elapsed();
+      if elapsed < self.policy.min_request_interval {
+        tokio::time::sleep(self.policy.min_request_interval - elapsed).await;
+      }
+    }
+    *last_request = Some(Instant::now());
+  }
+}
+
+/// Replays candles recorded to `directory` as `{symbol}_{interval}.json`, paged like a live provider,
+/// so tests and offline runs go through `MarketDataClient`.
+#[derive(Debug, Clone)]
+pub struct FixtureProvider {
+  pub directory: PathBuf,
+  pub page_size: usize,
+}
+
+impl FixtureProvider {
+  pub fn new(directory: PathBuf) -> Self {
+    Self {
+      directory,
+      page_size: 5000,
+    }
+  }
+
+  pub fn path(&self, symbol: &str, interval: Interval) -> PathBuf {
+    let symbol = symbol.replace(['/', '\\', ':'], "-");
+    self.directory.join(format!("{}_{}.json", symbol, interval.to_str()))
+  }
+
+  /// Save `candles` for replay, replacing any earlier recording.
+  pub fn record(&self, symbol: &str, interval: Interval, candles: &[Candle]) -> std::io::Result<()> {
+    std::fs::create_dir_all(&self.directory)?;
+    let file = File::create(self.path(symbol, interval))?;
+    serde_json::to_writer(file, candles).map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))
+  }
+
+  fn load(&self, symbol: &str, interval: Interval) -> std::io::Result<Vec<Candle>> {
+    let path = self.path(symbol, interval);
+    let file = File::open(&path).map_err(|error| std::io::Error::new(
+      error.kind(),
+      format!("No fixture {}: {}", path.display(), error)
+    ))?;
+    serde_json::from_reader(file)
+      .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{}: {}", path.display(), error)))
+  }
+}
+
+impl MarketDataProvider for FixtureProvider {
+  fn name(&self) -> String {
+    format!("Fixture {}", self.directory.display())
+  }
+
+  fn page_size(&self) -> usize {
+    self.page_size
+  }
+
+  async fn fetch_page(&self, request: &MarketDataRequest) -> std::io::Result<Vec<Candle>> {
+    let mut candles: Vec<Candle> = self.load(&request.symbol, request.interval)?.into_iter()
+      .filter(|candle| candle.timestamp >= request.start && candle.timestamp <= request.end)
+      .collect();
+    candles.sort_by_key(|candle| candle.timestamp);
+    let skip = candles.len().saturating_sub(self.page_size);
+    Ok(candles.split_off(skip))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use super::*;
+
+  /// Fails the first `failures` requests with a timeout, then replays `fixtures`.
+  struct FlakyProvider {
+    fixtures: FixtureProvider,
+    failures: usize,
+    requests: AtomicUsize,
+  }
+
+  impl MarketDataProvider for FlakyProvider {
+    fn name(&self) -> String {
+      format!("Flaky {}", self.fixtures.name())
+    }
+
+    fn page_size(&self) -> usize {
+      self.fixtures.page_size()
+    }
+
+    async fn fetch_page(&self, request: &MarketDataRequest) -> std::io::Result<Vec<Candle>> {
+      if self.requests.fetch_add(1, Ordering::SeqCst) < self.failures {
+        return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "fixture timeout"));
+      }
+      self.fixtures.fetch_page(request).await
+    }
+  }
+
+  fn daily_candle(date: Time, close: f64) -> Candle {
+    Candle {
+      date,
+      timestamp: date.to_unix(),
+      interval: Interval::Daily,
+      open: close,
+      high: close,
+      low: close,
+      close,
+      volume: None,
+    }
+  }
+
+  #[tokio::test]
+  async fn replays_recorded_candles_through_client() {
+    let directory = std::env::temp_dir().join(format!("market_data_fixture_{}", std::process::id()));
+    let start = Time::new(2023, &Month::January, &Day::One);
+    let mut candles: Vec<Candle> = (0..25)
+      .map(|day| daily_candle(start.delta_date(day), 100.0 + day as f64))
+      .collect();
+    // a vendor repeating a day, recorded out of order
+    candles.push(daily_candle(start.delta_date(12), 112.0));
+    candles.reverse();
+    let fixtures = FixtureProvider {
+      directory: directory.clone(),
+      page_size: 10,
+    };
+    fixtures.record("SPX", Interval::Daily, &candles).unwrap();
+
+    let client = MarketDataClient::with_policy(
+      FlakyProvider {
+        fixtures,
+        failures: 1,
+        requests: AtomicUsize::new(0),
+      },
+      FetchPolicy::immediate()
+    );
+    let fetched = client.fetch("SPX", Interval::Daily, &start, &start.delta_date(24)).await;
+    std::fs::remove_dir_all(&directory).unwrap();
+    let fetched = fetched.unwrap();
+
+    assert_eq!(fetched.len(), 25);
+    assert!(fetched.windows(2).all(|pair| pair[0].timestamp < pair[1].timestamp));
+    assert_eq!(fetched.first().unwrap().date, start);
+    assert_eq!(fetched.last().unwrap().close, 124.0);
+    // one timeout retried, then three pages of at most 10
+    assert_eq!(client.provider.requests.load(Ordering::SeqCst), 4);
+  }
+}
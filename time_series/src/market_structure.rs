@@ -1,7 +1,9 @@
 use std::path::PathBuf;
-use crate::{Candle, Reversal, ReversalType, TickerData};
+use csv::WriterBuilder;
+use serde::{Deserialize, Serialize};
+use crate::{Candle, Reversal, ReversalType, SwingDetector, TickerData, Time};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum Direction {
   Up,
   Down,
@@ -30,16 +32,17 @@ pub struct MarketStructure {
   pub trends: Vec<Trend>,
   pub latest_high: Option<Candle>,
   pub latest_low: Option<Candle>,
-  pub reversal_candle_range: usize,
+  /// How the reversals were found
+  pub swing_detector: SwingDetector,
 }
 
 impl MarketStructure {
   /// Identify market structure in vector of reversals .
   /// by finding higher highs and higher lows for positive market structure,
   /// and lower highs and lower lows for negative market structure.
-  pub fn new(ticker_data: &TickerData, candle_range: usize) -> Self {
+  pub fn new(ticker_data: &TickerData, swing_detector: &SwingDetector) -> Self {
     let mut trends = Vec::<Trend>::new();
-    let reversals = ticker_data.find_reversals(candle_range);
+    let reversals = ticker_data.detect_reversals(swing_detector);
     println!("First Candle: {:?}", ticker_data.candles[0].date.as_string());
     println!("Last Candle: {:?}", ticker_data.candles[ticker_data.candles.len() - 1].date.as_string());
     println!("First Reversal: {:?}", reversals[0].candle.date.as_string());
@@ -227,14 +230,14 @@ impl MarketStructure {
       trends,
       latest_high,
       latest_low,
-      reversal_candle_range: candle_range
+      swing_detector: swing_detector.clone()
     }
   }
 
-  pub fn test_market_structure(candle_range: usize, results_file: &PathBuf) {
+  pub fn test_market_structure(swing_detector: &SwingDetector, results_file: &PathBuf) {
     let mut ticker_data = TickerData::new();
     ticker_data.add_csv_series(results_file).expect("Failed to create TickerData");
-    let market_structure = MarketStructure::new(&ticker_data, candle_range);
+    let market_structure = MarketStructure::new(&ticker_data, swing_detector);
 
     match &market_structure.latest_high {
       Some(high) => println!("Latest High: {}", high.date.as_string()),
@@ -263,4 +266,230 @@ impl MarketStructure {
       println!();
     }
   }
-}
\ No newline at end of file
+}
+
+/// Position of a swing relative to the previous swing of the same type.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum SwingLabel {
+  HigherHigh,
+  LowerHigh,
+  HigherLow,
+  LowerLow,
+}
+
+impl SwingLabel {
+  pub fn as_string(&self) -> &str {
+    match self {
+      SwingLabel::HigherHigh => "HH",
+      SwingLabel::LowerHigh => "LH",
+      SwingLabel::HigherLow => "HL",
+      SwingLabel::LowerLow => "LL",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LabeledSwing {
+  pub reversal: Reversal,
+  /// High of a top, low of a bottom
+  pub price: f64,
+  /// `None` for the first top and first bottom
+  pub label: Option<SwingLabel>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum StructureEventKind {
+  /// Close beyond the last swing in the direction of the trend
+  BreakOfStructure,
+  /// Close beyond the last swing against the trend, the first sign of a reversal
+  ChangeOfCharacter,
+}
+
+impl StructureEventKind {
+  pub fn as_string(&self) -> &str {
+    match self {
+      StructureEventKind::BreakOfStructure => "BOS",
+      StructureEventKind::ChangeOfCharacter => "CHoCH",
+    }
+  }
+}
+
+/// A candle closing through a swing level.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StructureEvent {
+  pub kind: StructureEventKind,
+  /// Direction of the break, and of the trend after it
+  pub direction: Direction,
+  /// The candle that closed through the level
+  pub breaking_candle: Candle,
+  /// The swing whose level was broken
+  pub broken_swing: LabeledSwing,
+}
+
+impl StructureEvent {
+  pub fn date(&self) -> Time {
+    self.breaking_candle.date
+  }
+}
+
+/// Swings and structure events for one swing definition.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StructureDegree {
+  pub swing_detector: SwingDetector,
+  pub swings: Vec<LabeledSwing>,
+  pub events: Vec<StructureEvent>,
+}
+
+impl StructureDegree {
+  /// Label swings HH/LH/HL/LL, then walk the candles: a close above the last unbroken top or below the last unbroken bottom is an event.
+  /// The first break, and every break in the direction of the trend, is a BOS; a break against the trend is a CHoCH.
+  /// Each swing level can be broken once. Swings are dated at their extreme candle, not when the detector confirms them.
+  pub fn new(ticker_data: &TickerData, swing_detector: &SwingDetector) -> Self {
+    let mut swings = Vec::<LabeledSwing>::new();
+    let mut previous_top: Option<f64> = None;
+    let mut previous_bottom: Option<f64> = None;
+    for reversal in ticker_data.detect_reversals(swing_detector).into_iter() {
+      let (price, label) = match reversal.reversal_type {
+        ReversalType::Top => {
+          let price = reversal.candle.high;
+          let label = previous_top.map(|previous| if price > previous { SwingLabel::HigherHigh } else { SwingLabel::LowerHigh });
+          previous_top = Some(price);
+          (price, label)
+        },
+        ReversalType::Bottom => {
+          let price = reversal.candle.low;
+          let label = previous_bottom.map(|previous| if price < previous { SwingLabel::LowerLow } else { SwingLabel::HigherLow });
+          previous_bottom = Some(price);
+          (price, label)
+        },
+      };
+      swings.push(LabeledSwing { reversal, price, label });
+    }
+
+    let mut events = Vec::<StructureEvent>::new();
+    let mut trend: Option<Direction> = None;
+    let mut unbroken_top: Option<&LabeledSwing> = None;
+    let mut unbroken_bottom: Option<&LabeledSwing> = None;
+    let mut next_swing = swings.iter().peekable();
+    for candle in ticker_data.get_candles().iter() {
+      let broken = match (unbroken_top, unbroken_bottom) {
+        (Some(top), _) if candle.close > top.price => {
+          unbroken_top = None;
+          Some((Direction::Up, top))
+        },
+        (_, Some(bottom)) if candle.close < bottom.price => {
+          unbroken_bottom = None;
+          Some((Direction::Down, bottom))
+        },
+        _ => None,
+      };
+      if let Some((direction, swing)) = broken {
+        let kind = match &trend {
+          Some(trend) if *trend != direction => StructureEventKind::ChangeOfCharacter,
+          _ => StructureEventKind::BreakOfStructure,
+        };
+        events.push(StructureEvent {
+          kind,
+          direction: direction.clone(),
+          breaking_candle: candle.clone(),
+          broken_swing: swing.clone(),
+        });
+        trend = Some(direction);
+      }
+      // a swing's level applies from the candle after its extreme
+      while let Some(swing) = next_swing.next_if(|swing| swing.reversal.candle.date == candle.date) {
+        match swing.reversal.reversal_type {
+          ReversalType::Top => unbroken_top = Some(swing),
+          ReversalType::Bottom => unbroken_bottom = Some(swing),
+        }
+      }
+    }
+
+    Self {
+      swing_detector: swing_detector.clone(),
+      swings,
+      events,
+    }
+  }
+
+  /// Dates of events of `kind` in `direction`, for joining to astro signals or `Significance`.
+  /// `None` matches any kind or direction.
+  pub fn event_dates(&self, kind: Option<StructureEventKind>, direction: Option<Direction>) -> Vec<Time> {
+    self.events.iter()
+      .filter(|event| kind.is_none_or(|kind| event.kind == kind))
+      .filter(|event| direction.as_ref().is_none_or(|direction| event.direction == *direction))
+      .map(|event| event.date())
+      .collect()
+  }
+
+  /// Events whose breaking candle is within `error_margin_days` of `date`.
+  pub fn events_near(&self, date: &Time, error_margin_days: i64) -> Vec<&StructureEvent> {
+    self.events.iter()
+      .filter(|event| date.diff_days(&event.date()).abs() <= error_margin_days)
+      .collect()
+  }
+}
+
+/// Market structure events in multiple swing degrees, e.g. minor, intermediate and major swings.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MarketStructureEvents {
+  pub degrees: Vec<StructureDegree>,
+}
+
+impl MarketStructureEvents {
+  pub fn new(ticker_data: &TickerData, swing_detectors: &[SwingDetector]) -> Self {
+    Self {
+      degrees: swing_detectors.iter().map(|swing_detector| StructureDegree::new(ticker_data, swing_detector)).collect()
+    }
+  }
+
+  pub fn print(&self) {
+    println!("DEGREE\t\tDATE\t\tEVENT\tDIRECTION\tLEVEL\t\tSWING DATE\tSWING");
+    for degree in self.degrees.iter() {
+      for event in degree.events.iter() {
+        println!(
+          "{}\t{}\t{}\t{}\t\t{:.2}\t\t{}\t{}",
+          degree.swing_detector.as_string(),
+          event.date().as_string(),
+          event.kind.as_string(),
+          event.direction.as_string(),
+          event.broken_swing.price,
+          event.broken_swing.reversal.candle.date.as_string(),
+          event.broken_swing.label.as_ref().map_or("-", |label| label.as_string())
+        );
+      }
+    }
+  }
+
+  /// One row per event across all degrees, sorted by date.
+  pub fn write_csv(&self, out_file: &PathBuf) -> std::io::Result<()> {
+    let mut rows: Vec<(&StructureDegree, &StructureEvent)> = self.degrees.iter()
+      .flat_map(|degree| degree.events.iter().map(move |event| (degree, event)))
+      .collect();
+    rows.sort_by(|a, b| a.1.date().partial_cmp(&b.1.date()).unwrap());
+    let mut wtr = WriterBuilder::new().from_path(out_file)?;
+    wtr.write_record([
+      "degree", "date", "event", "direction", "close", "level", "swing_date", "swing_type", "swing_label"
+    ])?;
+    for (degree, event) in rows.into_iter() {
+      wtr.write_record(&[
+        degree.swing_detector.as_string(),
+        event.date().as_string(),
+        event.kind.as_string().to_string(),
+        event.direction.as_string().to_string(),
+        event.breaking_candle.close.to_string(),
+        event.broken_swing.price.to_string(),
+        event.broken_swing.reversal.candle.date.as_string(),
+        event.broken_swing.reversal.reversal_type.as_string(),
+        event.broken_swing.label.map_or(String::new(), |label| label.as_string().to_string())
+      ])?;
+    }
+    wtr.flush()
+  }
+
+  /// Full structure (swings and events per degree) as JSON.
+  pub fn write_json(&self, out_file: &PathBuf) -> std::io::Result<()> {
+    let json = serde_json::to_string_pretty(self)?;
+    std::fs::write(out_file, json)
+  }
+}
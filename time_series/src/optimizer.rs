@@ -0,0 +1,201 @@
+use std::thread;
+use log::debug;
+use plotters::prelude::*;
+
+/// One axis of a parameter grid.
+#[derive(Debug, Clone)]
+pub struct Parameter {
+  pub name: String,
+  pub values: Vec<f64>,
+}
+
+impl Parameter {
+  pub fn list(name: &str, values: &[f64]) -> Self {
+    Self {
+      name: name.to_string(),
+      values: values.to_vec()
+    }
+  }
+
+  /// `start` to `end` inclusive in increments of `step`.
+  pub fn range(name: &str, start: f64, end: f64, step: f64) -> Self {
+    let mut values = Vec::<f64>::new();
+    if step > 0.0 {
+      let mut value = start;
+      // tolerance so float steps don't drop the end value
+      while value <= end + step * 1e-9 {
+        values.push(value);
+        value += step;
+      }
+    }
+    Self {
+      name: name.to_string(),
+      values
+    }
+  }
+}
+
+/// Objective score of one combination, with values in the order of `GridSearch::parameters`.
+#[derive(Debug, Clone)]
+pub struct GridResult {
+  pub values: Vec<f64>,
+  pub score: f64,
+}
+
+/// Exhaustive search of every parameter combination.
+#[derive(Debug, Clone)]
+pub struct GridSearch {
+  pub parameters: Vec<Parameter>,
+  /// Sorted by score, best first
+  pub results: Vec<GridResult>,
+}
+
+impl GridSearch {
+  /// Cartesian product of all parameter values.
+  pub fn combinations(parameters: &[Parameter]) -> Vec<Vec<f64>> {
+    let mut combinations: Vec<Vec<f64>> = vec![Vec::new()];
+    for parameter in parameters.iter() {
+      let mut next = Vec::<Vec<f64>>::new();
+      for combination in combinations.iter() {
+        for value in parameter.values.iter() {
+          let mut extended = combination.clone();
+          extended.push(*value);
+          next.push(extended);
+        }
+      }
+      combinations = next;
+    }
+    combinations
+  }
+
+  /// Score every combination with `objective` (higher is better) across all available threads.
+  /// `objective` receives values in the order of `parameters`. Query ephemeris and compute reversals
+  /// before calling, so each evaluation only reads shared data.
+  pub fn run<F>(parameters: Vec<Parameter>, objective: F) -> Self
+  where
+    F: Fn(&[f64]) -> f64 + Sync
+  {
+    let combinations = Self::combinations(&parameters);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(combinations.len().max(1));
+    let chunk_size = combinations.len().div_ceil(threads).max(1);
+    let objective = &objective;
+    let mut results: Vec<GridResult> = thread::scope(|scope| {
+      let handles: Vec<_> = combinations.chunks(chunk_size)
+        .map(|chunk| scope.spawn(move || {
+          chunk.iter()
+            .map(|values| {
+              let score = objective(values);
+              debug!("{:?}\t{}", values, score);
+              GridResult { values: values.clone(), score }
+            })
+            .collect::<Vec<GridResult>>()
+        }))
+        .collect();
+      handles.into_iter()
+        .flat_map(|handle| handle.join().expect("grid search thread panicked"))
+        .collect()
+    });
+    // NaN scores rank last
+    results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or_else(|| a.score.is_nan().cmp(&b.score.is_nan())));
+    Self { parameters, results }
+  }
+
+  pub fn best(&self) -> Option<&GridResult> {
+    self.results.first()
+  }
+
+  fn parameter_index(&self, name: &str) -> std::io::Result<usize> {
+    self.parameters.iter().position(|parameter| parameter.name == name).ok_or_else(|| std::io::Error::new(
+      std::io::ErrorKind::InvalidInput,
+      format!("Unknown parameter: {}", name)
+    ))
+  }
+
+  /// Print the `top` best combinations.
+  pub fn print(&self, top: usize) {
+    let header = self.parameters.iter().map(|parameter| parameter.name.to_uppercase()).collect::<Vec<String>>().join("\t");
+    println!("RANK\t{}\tSCORE", header);
+    for (rank, result) in self.results.iter().take(top).enumerate() {
+      let values = result.values.iter().map(|value| format!("{}", value)).collect::<Vec<String>>().join("\t");
+      println!("{}\t{}\t{:.4}", rank + 1, values, result.score);
+    }
+  }
+
+  /// Best score for each pair of `x_parameter` and `y_parameter` values, over all values of the other parameters.
+  pub fn heatmap(&self, x_parameter: &str, y_parameter: &str) -> std::io::Result<Vec<Vec<Option<f64>>>> {
+    let x_index = self.parameter_index(x_parameter)?;
+    let y_index = self.parameter_index(y_parameter)?;
+    let x_values = &self.parameters[x_index].values;
+    let y_values = &self.parameters[y_index].values;
+    let mut cells: Vec<Vec<Option<f64>>> = vec![vec![None; x_values.len()]; y_values.len()];
+    for result in self.results.iter() {
+      if result.score.is_nan() {
+        continue;
+      }
+      let x = x_values.iter().position(|value| *value == result.values[x_index]);
+      let y = y_values.iter().position(|value| *value == result.values[y_index]);
+      if let (Some(x), Some(y)) = (x, y) {
+        let cell = &mut cells[y][x];
+        if cell.is_none_or(|score| result.score > score) {
+          *cell = Some(result.score);
+        }
+      }
+    }
+    Ok(cells)
+  }
+
+  /// Parameter value at an integer axis coordinate, blank between cells.
+  fn axis_label(values: &[f64], coordinate: f64) -> String {
+    if coordinate < 0.0 || (coordinate - coordinate.round()).abs() > 1e-6 {
+      return String::new();
+    }
+    values.get(coordinate.round() as usize).map_or(String::new(), |value| format!("{}", value))
+  }
+
+  /// Plot `heatmap` of two parameters: blue is the lowest score, red the highest.
+  pub fn plot_heatmap(&self, x_parameter: &str, y_parameter: &str, out_file: &str, plot_title: &str) -> std::io::Result<()> {
+    let cells = self.heatmap(x_parameter, y_parameter)?;
+    let x_values = self.parameters[self.parameter_index(x_parameter)?].values.clone();
+    let y_values = self.parameters[self.parameter_index(y_parameter)?].values.clone();
+    let scores: Vec<f64> = cells.iter().flatten().filter_map(|score| *score).collect();
+    let min_score = scores.iter().fold(f64::MAX, |min, score| min.min(*score));
+    let max_score = scores.iter().fold(f64::MIN, |max, score| max.max(*score));
+
+    let root = BitMapBackend::new(out_file, (2048, 1024)).into_drawing_area();
+    root.fill(&WHITE).unwrap();
+    let mut chart = ChartBuilder::on(&root)
+      .x_label_area_size(60)
+      .y_label_area_size(80)
+      .caption(plot_title, ("sans-serif", 50.0).into_font())
+      // cells centered on integer coordinates so labels sit in the middle of each cell
+      .build_cartesian_2d(-0.5..x_values.len() as f64 - 0.5, -0.5..y_values.len() as f64 - 0.5).unwrap();
+    chart.configure_mesh()
+      .disable_mesh()
+      .x_desc(x_parameter)
+      .y_desc(y_parameter)
+      .x_labels(x_values.len())
+      .y_labels(y_values.len())
+      .x_label_formatter(&|index| Self::axis_label(&x_values, *index))
+      .y_label_formatter(&|index| Self::axis_label(&y_values, *index))
+      .draw().unwrap();
+    chart.draw_series(
+      cells.iter().enumerate().flat_map(|(y, row)| {
+        row.iter().enumerate().map(move |(x, score)| {
+          let color = match score {
+            Some(score) => {
+              let fraction = if max_score > min_score { (score - min_score) / (max_score - min_score) } else { 1.0 };
+              // hue from blue (low) to red (high)
+              HSLColor(0.66 * (1.0 - fraction), 0.8, 0.5).filled()
+            },
+            None => WHITE.filled()
+          };
+          Rectangle::new([(x as f64 - 0.5, y as f64 - 0.5), (x as f64 + 0.5, y as f64 + 0.5)], color)
+        })
+      })
+    ).unwrap();
+    // To avoid the IO failure being ignored silently, we manually call the present function
+    root.present().expect("Unable to write result to file, please make sure the output dir exists");
+    println!("Result has been saved to {}", out_file);
+    Ok(())
+  }
+}
@@ -55,7 +55,12 @@ impl PlotPFS {
           let cycle_date = Time::new(date.year - self.cycle_years as i32 * cycle, &date.month, &date.day);
           // found candle X cycles back
           if prev_candle.date < cycle_date && candle.date >= cycle_date {
-            let change = candle.percent_change(prev_candle.close);
+            // intraday series: change from the previous day's last close to this day's last close
+            let day_close = ticker_data.candles[index..].iter()
+              .take_while(|day_candle| day_candle.date == candle.date)
+              .last()
+              .unwrap_or(candle);
+            let change = day_close.percent_change(prev_candle.close);
             pfs = (pfs.0 + change, pfs.1 + 1);
             found_cycle_date = true;
             break;
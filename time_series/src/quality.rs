@@ -0,0 +1,303 @@
+use std::collections::HashSet;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+/// Problem found in a price history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum AnomalyKind {
+  /// Zero, negative or NaN open, high, low or close
+  NonPositivePrice,
+  HighBelowLow,
+  OpenOutsideRange,
+  CloseOutsideRange,
+  /// More than one daily (or coarser) candle on the same date
+  DuplicateSession,
+  /// Session of the calendar, or intraday bar within a day, with no candle
+  Gap,
+}
+
+impl AnomalyKind {
+  pub fn as_string(&self) -> &str {
+    match self {
+      AnomalyKind::NonPositivePrice => "Non-positive price",
+      AnomalyKind::HighBelowLow => "High below low",
+      AnomalyKind::OpenOutsideRange => "Open outside range",
+      AnomalyKind::CloseOutsideRange => "Close outside range",
+      AnomalyKind::DuplicateSession => "Duplicate session",
+      AnomalyKind::Gap => "Gap",
+    }
+  }
+
+  /// Anomalies of a single candle's prices, as opposed to the series.
+  pub fn is_bad_tick(&self) -> bool {
+    !matches!(self, AnomalyKind::DuplicateSession | AnomalyKind::Gap)
+  }
+
+  const ALL: [AnomalyKind; 6] = [
+    AnomalyKind::NonPositivePrice, AnomalyKind::HighBelowLow, AnomalyKind::OpenOutsideRange,
+    AnomalyKind::CloseOutsideRange, AnomalyKind::DuplicateSession, AnomalyKind::Gap
+  ];
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Anomaly {
+  pub kind: AnomalyKind,
+  pub date: Time,
+  /// Timestamp of the bad or duplicate candle, or of the missing one for a gap
+  pub timestamp: i64,
+  pub detail: String,
+}
+
+/// Anomalies of a `TickerData` in timestamp order, from `TickerData::validate`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DataQualityReport {
+  /// Calendar gaps in daily candles were checked against
+  pub calendar: TradingCalendar,
+  pub candles: usize,
+  pub anomalies: Vec<Anomaly>,
+}
+
+impl DataQualityReport {
+  pub fn is_clean(&self) -> bool {
+    self.anomalies.is_empty()
+  }
+
+  pub fn of_kind(&self, kind: &AnomalyKind) -> Vec<&Anomaly> {
+    self.anomalies.iter().filter(|anomaly| anomaly.kind == *kind).collect()
+  }
+
+  /// Dates with at least one anomaly.
+  pub fn dates(&self) -> Vec<Time> {
+    let mut dates: Vec<Time> = self.anomalies.iter().map(|anomaly| anomaly.date).collect();
+    dates.dedup();
+    dates
+  }
+
+  pub fn print(&self) {
+    println!("{} candles checked against the {} calendar", self.candles, self.calendar.as_string());
+    println!("ANOMALY\t\t\tCOUNT");
+    for kind in AnomalyKind::ALL.iter() {
+      println!("{}\t{}", kind.as_string(), self.of_kind(kind).len());
+    }
+    if self.is_clean() {
+      return;
+    }
+    println!();
+    println!("DATE\t\tANOMALY\t\t\tDETAIL");
+    for anomaly in self.anomalies.iter() {
+      println!("{}\t{}\t{}", anomaly.date.as_string(), anomaly.kind.as_string(), anomaly.detail);
+    }
+  }
+}
+
+/// What `TickerData::repair` does with the anomalies of a report.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum Repair {
+  /// Remove bad ticks and all but the first candle of a duplicated session. Gaps stay.
+  Drop,
+  /// Replace bad ticks and fill gaps with flat candles at the previous close, without volume.
+  /// Duplicates are dropped, as are bad ticks before the first good candle.
+  ForwardFill,
+  /// Keep every candle, flagging bad ticks and duplicates for `TickerData::is_flagged`.
+  Mark,
+}
+
+impl TickerData {
+  /// Check each candle for bad prices, and the series for duplicated sessions and gaps.
+  /// Gaps between daily candles are sessions of `calendar` with no candle; gaps in intraday
+  /// candles are missing bars within a UTC day, since sessions end overnight. Weekly and coarser
+  /// candles aren't checked for gaps.
+  pub fn validate(&self, calendar: &TradingCalendar) -> DataQualityReport {
+    let mut anomalies = Vec::<Anomaly>::new();
+    let mut previous: Option<&Candle> = None;
+    for candle in self.candles.iter() {
+      if let Some(previous) = previous {
+        anomalies.extend(Self::series_anomalies(previous, candle, calendar));
+      }
+      anomalies.extend(Self::candle_anomalies(candle));
+      previous = Some(candle);
+    }
+    anomalies.sort_by_key(|anomaly| anomaly.timestamp);
+    debug!("Validated {} candles, {} anomalies", self.candles.len(), anomalies.len());
+    DataQualityReport {
+      calendar: *calendar,
+      candles: self.candles.len(),
+      anomalies
+    }
+  }
+
+  fn candle_anomalies(candle: &Candle) -> Vec<Anomaly> {
+    let anomaly = |kind: AnomalyKind, detail: String| Anomaly {
+      kind,
+      date: candle.date,
+      timestamp: candle.timestamp,
+      detail
+    };
+    let prices = [candle.open, candle.high, candle.low, candle.close];
+    if prices.iter().any(|price| price.is_nan() || *price <= 0.0) {
+      // range checks are meaningless against a zero or NaN price
+      return vec![anomaly(
+        AnomalyKind::NonPositivePrice,
+        format!("O {} H {} L {} C {}", candle.open, candle.high, candle.low, candle.close)
+      )];
+    }
+    let mut anomalies = Vec::<Anomaly>::new();
+    if candle.high < candle.low {
+      anomalies.push(anomaly(AnomalyKind::HighBelowLow, format!("high {} < low {}", candle.high, candle.low)));
+    }
+    if candle.open > candle.high || candle.open < candle.low {
+      anomalies.push(anomaly(
+        AnomalyKind::OpenOutsideRange,
+        format!("open {} outside {} - {}", candle.open, candle.low, candle.high)
+      ));
+    }
+    if candle.close > candle.high || candle.close < candle.low {
+      anomalies.push(anomaly(
+        AnomalyKind::CloseOutsideRange,
+        format!("close {} outside {} - {}", candle.close, candle.low, candle.high)
+      ));
+    }
+    anomalies
+  }
+
+  fn series_anomalies(previous: &Candle, candle: &Candle, calendar: &TradingCalendar) -> Vec<Anomaly> {
+    if candle.interval != previous.interval {
+      return Vec::new();
+    }
+    if !candle.interval.is_intraday() {
+      if candle.date == previous.date {
+        return vec![Anomaly {
+          kind: AnomalyKind::DuplicateSession,
+          date: candle.date,
+          timestamp: candle.timestamp,
+          detail: format!("also at timestamp {}", previous.timestamp)
+        }];
+      }
+      if candle.interval != Interval::Daily {
+        return Vec::new();
+      }
+      return Self::missing_sessions(previous, candle, calendar).into_iter()
+        .map(|(date, timestamp)| Anomaly {
+          kind: AnomalyKind::Gap,
+          date,
+          timestamp,
+          detail: format!("no {} session", calendar.as_string())
+        })
+        .collect();
+    }
+    Self::missing_bars(previous, candle).into_iter()
+      .map(|timestamp| Anomaly {
+        kind: AnomalyKind::Gap,
+        date: candle.date,
+        timestamp,
+        detail: format!("no {} bar at timestamp {}", candle.interval.to_str(), timestamp)
+      })
+      .collect()
+  }
+
+  /// Sessions strictly between two daily candles, timestamped at the earlier candle's time of day.
+  fn missing_sessions(previous: &Candle, candle: &Candle, calendar: &TradingCalendar) -> Vec<(Time, i64)> {
+    if previous.date.diff_days(&candle.date) < 2 {
+      return Vec::new();
+    }
+    let time_of_day = previous.timestamp - previous.date.to_unix();
+    calendar.sessions_between(&previous.date.delta_date(1), &candle.date.delta_date(-1)).into_iter()
+      .map(|session| (session, session.to_unix() + time_of_day))
+      .collect()
+  }
+
+  /// Timestamps of intraday bars missing between two candles of the same day.
+  fn missing_bars(previous: &Candle, candle: &Candle) -> Vec<i64> {
+    let seconds = match candle.interval.seconds() {
+      Some(seconds) if previous.date == candle.date => seconds,
+      _ => return Vec::new(),
+    };
+    (1..)
+      .map(|bar| previous.timestamp + bar * seconds)
+      .take_while(|timestamp| *timestamp < candle.timestamp)
+      .collect()
+  }
+
+  /// Copy of the candles with the anomalies of `report` repaired. `report` should come from
+  /// `validate` on these candles; anomalies that don't match a candle are ignored.
+  pub fn repair(&self, report: &DataQualityReport, repair: &Repair) -> TickerData {
+    let bad_ticks: HashSet<i64> = report.anomalies.iter()
+      .filter(|anomaly| anomaly.kind.is_bad_tick())
+      .map(|anomaly| anomaly.timestamp)
+      .collect();
+    let duplicates: HashSet<i64> = report.of_kind(&AnomalyKind::DuplicateSession).iter()
+      .map(|anomaly| anomaly.timestamp)
+      .collect();
+    let mut gaps = report.of_kind(&AnomalyKind::Gap).into_iter().peekable();
+
+    let mut candles = Vec::<Candle>::new();
+    let mut flagged = HashSet::<i64>::new();
+    for candle in self.candles.iter() {
+      if *repair == Repair::ForwardFill {
+        while let Some(gap) = gaps.next_if(|gap| gap.timestamp < candle.timestamp) {
+          if let Some(last) = candles.last() {
+            let fill = Self::flat_candle(last, gap.timestamp);
+            candles.push(fill);
+          }
+        }
+      }
+      let is_duplicate = duplicates.contains(&candle.timestamp);
+      let is_bad_tick = bad_ticks.contains(&candle.timestamp);
+      if !is_duplicate && !is_bad_tick {
+        candles.push(candle.clone());
+        continue;
+      }
+      match repair {
+        Repair::Mark => {
+          flagged.insert(candle.timestamp);
+          candles.push(candle.clone());
+        },
+        Repair::ForwardFill if !is_duplicate => {
+          if let Some(last) = candles.last() {
+            let fill = Self::flat_candle(last, candle.timestamp);
+            candles.push(fill);
+          }
+        },
+        _ => (),
+      }
+    }
+    debug!(
+      "Repaired {} candles into {} with {:?}, {} flagged",
+      self.candles.len(),
+      candles.len(),
+      repair,
+      flagged.len()
+    );
+
+    let mut ticker_data = TickerData::new();
+    for candle in candles.iter() {
+      ticker_data.append_candle(candle);
+    }
+    ticker_data.flagged = flagged;
+    ticker_data
+  }
+
+  /// Candle at `timestamp` with every price at `previous`'s close.
+  fn flat_candle(previous: &Candle, timestamp: i64) -> Candle {
+    Candle {
+      date: Time::from_unix(timestamp),
+      timestamp,
+      interval: previous.interval,
+      open: previous.close,
+      high: previous.close,
+      low: previous.close,
+      close: previous.close,
+      volume: None
+    }
+  }
+
+  /// Whether `repair` with `Repair::Mark` flagged the candle as suspect.
+  pub fn is_flagged(&self, candle: &Candle) -> bool {
+    self.flagged.contains(&candle.timestamp)
+  }
+
+  pub fn flagged_candles(&self) -> Vec<&Candle> {
+    self.candles.iter().filter(|candle| self.is_flagged(candle)).collect()
+  }
+}
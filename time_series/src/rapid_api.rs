@@ -1,101 +1,146 @@
 use std::str::FromStr;
-use reqwest::Client;
+use chrono::{TimeZone, Utc};
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
-use crate::{Candle, Time};
+use crate::{api_key, Candle, DateFormat, Interval, MarketDataClient, MarketDataProvider, MarketDataRequest};
 
 pub const RAPID_API_URL: &str = "https://twelve-data1.p.rapidapi.com";
-pub const RAPID_API_KEY: &str = "687a11f943msh17ef6a6b5f4da77p11b9dajsnd13fdf102ceb";
+/// Environment variable or `market_data.json` key holding the RapidAPI key.
+pub const RAPID_API_KEY_ENV: &str = "RAPID_API_KEY";
+/// Largest `outputsize` Twelve Data accepts.
+pub const RAPID_API_PAGE_SIZE: usize = 5000;
 
+/// Twelve Data time series through RapidAPI.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RapidApi {
-  pub symbol: String
+  pub symbol: String,
+  /// Read from `RAPID_API_KEY` or the market data config on each request if `None`
+  #[serde(skip)]
+  api_key: Option<String>
 }
 
-#[derive(Debug, Clone)]
-pub enum Interval {
-  OneMinute,
-  FiveMinutes,
-  FifteenMinutes,
-  ThirtyMinutes,
-  FourtyFiveMinutes,
-  OneHour,
-  TwoHour,
-  FourHour,
-  Daily,
-  Weekly,
-  Monthly,
-}
+impl RapidApi {
+  pub fn new(symbol: String) -> Self {
+    Self { symbol, api_key: None }
+  }
+
+  pub fn with_api_key(symbol: String, api_key: String) -> Self {
+    Self { symbol, api_key: Some(api_key) }
+  }
+
+  /// Latest page of candles of `self.symbol`, retried on transient errors.
+  pub async fn query(&self, interval: Interval) -> std::io::Result<Vec<Candle>> {
+    let candles = MarketDataClient::new(self.clone()).fetch_latest(&self.symbol, interval).await?;
+    println!("{} candles retrieved from RapidApi for symbol {}", candles.len(), &self.symbol);
+    Ok(candles)
+  }
+
+  /// Candles from a Twelve Data `time_series` response body.
+  pub fn parse_response(response: &str, interval: Interval) -> std::io::Result<Vec<Candle>> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("RapidApi: {}", message));
+    let json: serde_json::Value = serde_json::from_str(response).map_err(|error| invalid(error.to_string()))?;
+    if json["status"].as_str() == Some("error") {
+      let code = json["code"].as_u64().unwrap_or(0) as u16;
+      let message = json["message"].as_str().unwrap_or("unknown error");
+      // paging past the start of the history
+      if message.starts_with("No data is available") {
+        return Ok(Vec::new());
+      }
+      return Err(Self::status_error(StatusCode::from_u16(code).unwrap_or(StatusCode::BAD_REQUEST), message));
+    }
+    let values = json["values"].as_array().ok_or_else(|| invalid("response has no values".to_string()))?;
 
-impl Interval {
-  pub fn to_str(&self) -> &str {
-    match self {
-      Interval::OneMinute => "1min",
-      Interval::FiveMinutes => "5min",
-      Interval::FifteenMinutes => "15min",
-      Interval::ThirtyMinutes => "30min",
-      Interval::FourtyFiveMinutes => "45min",
-      Interval::OneHour => "1h",
-      Interval::TwoHour => "2h",
-      Interval::FourHour => "4h",
-      Interval::Daily => "1day",
-      Interval::Weekly => "1week",
-      Interval::Monthly => "1month",
+    let mut candles = Vec::<Candle>::new();
+    for value in values.iter() {
+      let field = |name: &str| value[name].as_str().ok_or_else(|| invalid(format!("{} missing from {}", name, value)));
+      let price = |name: &str| field(name).and_then(|price| f64::from_str(price)
+        .map_err(|_| invalid(format!("invalid {} \"{}\"", name, price))));
+
+      // requested in UTC, so a datetime without an offset is UTC
+      let timestamp = DateFormat::Auto.parse(field("datetime")?).map_err(invalid)?;
+      // indices have no volume
+      let volume = match value["volume"].as_str() {
+        None => None,
+        Some(volume) => Some(f64::from_str(volume).map_err(|_| invalid(format!("invalid volume \"{}\"", volume)))?),
+      };
+      candles.push(Candle {
+        date: crate::Time::from_unix(timestamp),
+        timestamp,
+        interval,
+        open: price("open")?,
+        high: price("high")?,
+        low: price("low")?,
+        close: price("close")?,
+        volume,
+      });
     }
+    Ok(candles)
+  }
+
+  /// Rate limits and server errors map to kinds `is_transient` retries.
+  fn status_error(status: StatusCode, message: &str) -> std::io::Error {
+    let kind = match status {
+      StatusCode::TOO_MANY_REQUESTS => std::io::ErrorKind::WouldBlock,
+      StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => std::io::ErrorKind::PermissionDenied,
+      StatusCode::NOT_FOUND => std::io::ErrorKind::NotFound,
+      status if status.is_server_error() => std::io::ErrorKind::Interrupted,
+      _ => std::io::ErrorKind::InvalidInput,
+    };
+    std::io::Error::new(kind, format!("RapidApi {}: {}", status, message))
+  }
+
+  fn request_error(error: reqwest::Error) -> std::io::Error {
+    let kind = if error.is_timeout() {
+      std::io::ErrorKind::TimedOut
+    } else if error.is_connect() {
+      std::io::ErrorKind::ConnectionRefused
+    } else {
+      std::io::ErrorKind::ConnectionAborted
+    };
+    std::io::Error::new(kind, format!("RapidApi request failed: {}", error))
+  }
+
+  fn api_datetime(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0).unwrap().format("%Y-%m-%d %H:%M:%S").to_string()
   }
 }
 
-impl RapidApi {
-  pub fn new(symbol: String) -> Self {
-    Self { symbol }
+impl MarketDataProvider for RapidApi {
+  fn name(&self) -> String {
+    "RapidApi".to_string()
   }
 
-  pub async fn query(&self, interval: Interval) -> Vec<Candle> {
-    let client = Client::new();
-    // output size accepts values [1, 5000] inclusive
-    let output_size = 5000;
+  fn page_size(&self) -> usize {
+    RAPID_API_PAGE_SIZE
+  }
 
+  async fn fetch_page(&self, request: &MarketDataRequest) -> std::io::Result<Vec<Candle>> {
+    let key = match &self.api_key {
+      Some(key) => key.clone(),
+      None => api_key(RAPID_API_KEY_ENV)?,
+    };
     let url = format!(
-      "{}/time_series?symbol={}&interval={}&outputsize={}&format=json",
+      "{}/time_series?symbol={}&interval={}&outputsize={}&start_date={}&end_date={}&timezone=UTC&format=json",
       RAPID_API_URL,
-      &self.symbol,
-      interval.to_str(),
-      output_size
+      &request.symbol,
+      request.interval.to_str(),
+      RAPID_API_PAGE_SIZE,
+      Self::api_datetime(request.start),
+      Self::api_datetime(request.end)
     );
 
-    let response = client
+    let response = Client::new()
       .get(url)
-      .header("X-RapidAPI-Key",RAPID_API_KEY)
+      .header("X-RapidAPI-Key", key)
       .header("X-RapidAPI-Host", "twelve-data1.p.rapidapi.com")
       .send()
       .await
-      .expect("Failed to send RapidApi request")
-      .text()
-      .await
-      .expect("Failed to read RapidApi response into text");
-    let json: serde_json::Value = serde_json::from_str(&response)
-      .expect("Failed to parse RapidApi response into JSON");
-
-    let values = json["values"].as_array().unwrap();
-    let mut candles = Vec::<Candle>::new();
-    for value in values.iter() {
-      let date = Time::from_api_format(value["datetime"].as_str().unwrap());
-
-      let mut volume = None;
-      match value["volume"].as_str() {
-        None => println!("date no volume: {}", date.as_string()),
-        Some(vol) => volume = Some(f64::from_str(vol).expect("Failed to parse open price to f64"))
-      };
-
-      candles.push(Candle {
-        date,
-        close: f64::from_str(value["close"].as_str().unwrap()).expect("Failed to parse close price to f64"),
-        high: f64::from_str(value["high"].as_str().unwrap()).expect("Failed to parse high price to f64"),
-        low: f64::from_str(value["low"].as_str().unwrap()).expect("Failed to parse low price to f64"),
-        open: f64::from_str(value["open"].as_str().unwrap()).expect("Failed to parse open price to f64"),
-        volume,
-      });
+      .map_err(Self::request_error)?;
+    let status = response.status();
+    let body = response.text().await.map_err(Self::request_error)?;
+    if !status.is_success() {
+      return Err(Self::status_error(status, &body));
     }
-    println!("{} candles retrieved from RapidApi for symbol {}", candles.len(), &self.symbol);
-    candles
+    Self::parse_response(&body, request.interval)
   }
-}
\ No newline at end of file
+}
@@ -0,0 +1,183 @@
+use chrono::{Datelike, Weekday};
+use log::debug;
+use crate::*;
+
+/// Coarser timeframe to aggregate candles into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timeframe {
+  /// N-hour candles aligned to midnight UTC, from intraday candles
+  Hours(u32),
+  /// Weeks starting on the given day
+  Weekly(Weekday),
+  Monthly,
+  Quarterly,
+  Yearly,
+}
+
+impl Timeframe {
+  pub fn as_string(&self) -> String {
+    match self {
+      Timeframe::Hours(hours) => format!("{}h", hours),
+      Timeframe::Weekly(week_start) => format!("Weekly ({})", week_start),
+      Timeframe::Monthly => "Monthly".to_string(),
+      Timeframe::Quarterly => "Quarterly".to_string(),
+      Timeframe::Yearly => "Yearly".to_string(),
+    }
+  }
+
+  pub fn interval(&self) -> Interval {
+    match self {
+      Timeframe::Hours(hours) => Interval::hours(*hours),
+      Timeframe::Weekly(_) => Interval::Weekly,
+      Timeframe::Monthly => Interval::Monthly,
+      Timeframe::Quarterly => Interval::Quarterly,
+      Timeframe::Yearly => Interval::Yearly,
+    }
+  }
+
+  /// Timestamp of the start of the period `candle` belongs to.
+  fn period_start(&self, candle: &Candle) -> i64 {
+    let date = candle.date.to_naive_date();
+    let start = match self {
+      Timeframe::Hours(hours) => {
+        let seconds = *hours as i64 * 3600;
+        return candle.timestamp - candle.timestamp.rem_euclid(seconds);
+      },
+      Timeframe::Weekly(week_start) => {
+        let days_into_week = (date.weekday().num_days_from_monday() + 7 - week_start.num_days_from_monday()) % 7;
+        candle.date.delta_date(-(days_into_week as i64))
+      },
+      Timeframe::Monthly => Time::new(date.year(), &Month::from_num(date.month()), &Day::One),
+      Timeframe::Quarterly => Time::new(date.year(), &Month::from_num((date.month() - 1) / 3 * 3 + 1), &Day::One),
+      Timeframe::Yearly => Time::new(date.year(), &Month::January, &Day::One),
+    };
+    start.to_unix()
+  }
+}
+
+impl TickerData {
+  /// Aggregate candles into `timeframe`: first open, highest high, lowest low, last close and summed volume.
+  /// Candles are labeled with the start of their period, even if the first session is later.
+  /// Volume is `None` if no candle in the period had volume.
+  /// Errors if `timeframe` is not coarser than the candles, or N hours isn't a multiple of the intraday interval.
+  pub fn resample(&self, timeframe: &Timeframe) -> std::io::Result<TickerData> {
+    let target = timeframe.interval();
+    if let Some(interval) = self.interval() {
+      if interval.approximate_seconds() >= target.approximate_seconds() {
+        return Err(std::io::Error::new(
+          std::io::ErrorKind::InvalidInput,
+          format!("Cannot resample {} candles into {}", interval.to_str(), timeframe.as_string())
+        ));
+      }
+      if let (Timeframe::Hours(_), Some(seconds), Some(target_seconds)) = (timeframe, interval.seconds(), target.seconds()) {
+        if target_seconds % seconds != 0 {
+          return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} is not a multiple of {} candles", timeframe.as_string(), interval.to_str())
+          ));
+        }
+      }
+    } else if !self.candles.is_empty() {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Cannot resample candles of mixed intervals"));
+    }
+    if let Timeframe::Hours(0) = timeframe {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Hours must be at least 1"));
+    }
+
+    let mut resampled = Vec::<Candle>::new();
+    let mut period_start: Option<i64> = None;
+    for candle in self.candles.iter() {
+      let start = timeframe.period_start(candle);
+      match resampled.last_mut() {
+        Some(bar) if period_start == Some(start) => {
+          bar.high = bar.high.max(candle.high);
+          bar.low = bar.low.min(candle.low);
+          bar.close = candle.close;
+          bar.volume = match (bar.volume, candle.volume) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+          };
+        },
+        _ => {
+          period_start = Some(start);
+          resampled.push(Candle {
+            date: Time::from_unix(start),
+            timestamp: start,
+            interval: target,
+            open: candle.open,
+            high: candle.high,
+            low: candle.low,
+            close: candle.close,
+            volume: candle.volume,
+          });
+        }
+      }
+    }
+    debug!("Resampled {} candles into {} {} candles", self.candles.len(), resampled.len(), timeframe.as_string());
+    let mut ticker_data = TickerData::new();
+    ticker_data.add_series(resampled)?;
+    Ok(ticker_data)
+  }
+}
+
+/// The same ticker at several timeframes, so analysis can run on each at once.
+#[derive(Debug, Clone)]
+pub struct MultiTimeframe {
+  /// Candles as loaded
+  pub base: TickerData,
+  /// `base` resampled into each timeframe, in the order given
+  pub timeframes: Vec<(Timeframe, TickerData)>,
+}
+
+impl MultiTimeframe {
+  pub fn new(base: TickerData, timeframes: &[Timeframe]) -> std::io::Result<Self> {
+    let mut resampled = Vec::<(Timeframe, TickerData)>::new();
+    for timeframe in timeframes.iter() {
+      resampled.push((*timeframe, base.resample(timeframe)?));
+    }
+    Ok(Self {
+      base,
+      timeframes: resampled
+    })
+  }
+
+  pub fn get(&self, timeframe: &Timeframe) -> Option<&TickerData> {
+    self.timeframes.iter().find(|(other, _)| other == timeframe).map(|(_, ticker_data)| ticker_data)
+  }
+
+  /// Reversals of the base candles (`None`) and of each timeframe.
+  pub fn detect_reversals(&self, swing_detector: &SwingDetector) -> Vec<(Option<Timeframe>, Vec<Reversal>)> {
+    let mut reversals = vec![(None, self.base.detect_reversals(swing_detector))];
+    reversals.extend(self.timeframes.iter()
+      .map(|(timeframe, ticker_data)| (Some(*timeframe), ticker_data.detect_reversals(swing_detector))));
+    reversals
+  }
+
+  /// Swings of the base candles (`None`) and of each timeframe.
+  pub fn find_swings(&self, swing_detector: &SwingDetector) -> Vec<(Option<Timeframe>, Vec<Swing>)> {
+    let mut swings = vec![(None, self.base.find_swings(swing_detector))];
+    swings.extend(self.timeframes.iter()
+      .map(|(timeframe, ticker_data)| (Some(*timeframe), ticker_data.find_swings(swing_detector))));
+    swings
+  }
+
+  pub fn print(&self) {
+    println!("TIMEFRAME\t\tCANDLES\tFIRST\t\tLAST");
+    let base_label = self.base.interval().map_or("Mixed".to_string(), |interval| interval.to_str());
+    let rows = std::iter::once((base_label, &self.base))
+      .chain(self.timeframes.iter().map(|(timeframe, ticker_data)| (timeframe.as_string(), ticker_data)));
+    for (label, ticker_data) in rows {
+      if ticker_data.candles.is_empty() {
+        println!("{}\t\t0\t-\t\t-", label);
+        continue;
+      }
+      println!(
+        "{}\t\t{}\t{}\t{}",
+        label,
+        ticker_data.candles.len(),
+        ticker_data.earliest_date().as_string(),
+        ticker_data.latest_date().as_string()
+      );
+    }
+  }
+}
@@ -0,0 +1,548 @@
+use std::path::PathBuf;
+use chrono::NaiveDate;
+use csv::WriterBuilder;
+use plotters::prelude::*;
+use crate::*;
+
+/// Days per year used to annualize CAGR
+const DAYS_PER_YEAR: f64 = 365.25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+  Long,
+  Short
+}
+
+impl Side {
+  pub fn as_string(&self) -> String {
+    match self {
+      Side::Long => "Long".to_string(),
+      Side::Short => "Short".to_string()
+    }
+  }
+
+  /// +1 for long, -1 for short
+  pub fn direction(&self) -> f64 {
+    match self {
+      Side::Long => 1.0,
+      Side::Short => -1.0
+    }
+  }
+
+  pub fn opposite(&self) -> Self {
+    match self {
+      Side::Long => Side::Short,
+      Side::Short => Side::Long
+    }
+  }
+}
+
+/// Request to open a position on a date.
+#[derive(Debug, Clone)]
+pub struct TradeSignal {
+  pub date: Time,
+  pub side: Side,
+}
+
+impl TradeSignal {
+  pub fn new(date: Time, side: Side) -> Self {
+    Self { date, side }
+  }
+
+  /// Every date opens a position on the same side, e.g. long on each eclipse.
+  pub fn from_dates(dates: &[Time], side: Side) -> Vec<Self> {
+    dates.iter().map(|date| Self::new(*date, side)).collect()
+  }
+
+  /// Long at bottoms, short at tops.
+  pub fn from_reversals(reversals: &[Reversal]) -> Vec<Self> {
+    reversals.iter()
+      .map(|reversal| match reversal.reversal_type {
+        ReversalType::Top => Self::new(reversal.candle.date, Side::Short),
+        ReversalType::Bottom => Self::new(reversal.candle.date, Side::Long),
+      })
+      .collect()
+  }
+
+  /// Like `from_reversals`, but dated `confirmation_candles` candles after each reversal, the first close
+  /// at which a detector looking that far ahead (e.g. `find_reversals`) could have seen it.
+  /// Reversals without that many later candles are dropped, so backtests never trade on future prices.
+  pub fn from_confirmed_reversals(ticker_data: &TickerData, reversals: &[Reversal], confirmation_candles: usize) -> Vec<Self> {
+    let candles = ticker_data.get_candles();
+    Self::from_reversals(reversals).into_iter()
+      .zip(reversals.iter())
+      .filter_map(|(signal, reversal)| {
+        let index = candles.iter().position(|candle| candle.timestamp == reversal.candle.timestamp)?;
+        let confirmed = candles.get(index + confirmation_candles)?;
+        Some(Self::new(confirmed.date, signal.side))
+      })
+      .collect()
+  }
+}
+
+/// When to close a position if no stop or target is hit first.
+#[derive(Debug, Clone)]
+pub enum ExitRule {
+  /// Close at the close of the Nth candle after entry
+  HoldCandles(usize),
+  /// Close when a signal for the other side arrives, then open that side
+  OppositeSignal,
+}
+
+/// Distance of a stop or target from the entry price.
+#[derive(Debug, Clone)]
+pub enum PriceOffset {
+  /// Percent of the entry price
+  Percent(f64),
+  /// Multiple of the average true range on the entry candle
+  Atr { period: usize, multiple: f64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Strategy {
+  pub exit_rule: ExitRule,
+  pub stop: Option<PriceOffset>,
+  pub target: Option<PriceOffset>,
+  /// Percent of notional paid on entry and again on exit
+  pub commission_percent: f64,
+  /// Percent of price lost on each fill
+  pub slippage_percent: f64,
+  pub initial_equity: f64,
+  /// Take short signals, otherwise ignore them (and exit longs on them with `ExitRule::OppositeSignal`)
+  pub allow_short: bool,
+}
+
+impl Default for Strategy {
+  fn default() -> Self {
+    Self {
+      exit_rule: ExitRule::HoldCandles(10),
+      stop: None,
+      target: None,
+      commission_percent: 0.0,
+      slippage_percent: 0.0,
+      initial_equity: 10_000.0,
+      allow_short: true,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitReason {
+  Hold,
+  OppositeSignal,
+  Stop,
+  Target,
+  EndOfData,
+}
+
+impl ExitReason {
+  pub fn as_string(&self) -> String {
+    match self {
+      ExitReason::Hold => "Hold".to_string(),
+      ExitReason::OppositeSignal => "Opposite".to_string(),
+      ExitReason::Stop => "Stop".to_string(),
+      ExitReason::Target => "Target".to_string(),
+      ExitReason::EndOfData => "End".to_string(),
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+pub struct Trade {
+  pub side: Side,
+  pub signal_date: Time,
+  pub entry_date: Time,
+  /// Fill price including slippage
+  pub entry_price: f64,
+  pub exit_date: Time,
+  /// Fill price including slippage
+  pub exit_price: f64,
+  pub exit_reason: ExitReason,
+  pub candles_held: usize,
+  /// Percent return after commissions and slippage
+  pub return_percent: f64,
+  pub equity_after: f64,
+}
+
+/// Open position while simulating
+struct Position {
+  side: Side,
+  signal_date: Time,
+  entry_date: Time,
+  entry_index: usize,
+  entry_price: f64,
+  stop_price: Option<f64>,
+  target_price: Option<f64>,
+  /// Equity after the entry commission
+  equity_at_entry: f64,
+}
+
+/// Result of running a `Strategy` over `TickerData` candles with a list of signals.
+/// Each position uses the full equity, with no leverage or pyramiding.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+  pub strategy: Strategy,
+  pub trades: Vec<Trade>,
+  /// Marked-to-market equity at each candle close
+  pub equity_curve: Vec<(Time, f64)>,
+  /// Candles with an open position at the close
+  pub candles_in_market: usize,
+  /// Signals ignored because a position was open, a later signal replaced them on the same candle,
+  /// or they were outside the candle history
+  pub skipped_signals: usize,
+}
+
+impl Simulation {
+  /// Signals fill at the close of the first candle on or after the signal date.
+  /// Stops and targets are checked against each later candle's high and low, and fill at the
+  /// stop/target or at the open if price gapped through it. If both are hit in one candle the stop fills first.
+  pub fn run(ticker_data: &TickerData, signals: &[TradeSignal], strategy: &Strategy) -> Self {
+    let candles = ticker_data.get_candles();
+    // each ATR offset uses its own period
+    let atr_for = |offset: &Option<PriceOffset>| match offset {
+      Some(PriceOffset::Atr { period, .. }) => ticker_data.average_true_range(*period),
+      _ => Vec::new()
+    };
+    let stop_atr = atr_for(&strategy.stop);
+    let target_atr = atr_for(&strategy.target);
+
+    // signal for each candle, a later signal on the same candle replaces (and skips) an earlier one
+    let mut candle_signals: Vec<Option<&TradeSignal>> = vec![None; candles.len()];
+    let mut skipped_signals = 0;
+    for signal in signals.iter() {
+      if candles.first().is_none_or(|first| signal.date < first.date) {
+        skipped_signals += 1;
+        continue;
+      }
+      match candles.iter().position(|candle| candle.date >= signal.date) {
+        Some(index) => {
+          if candle_signals[index].replace(signal).is_some() {
+            skipped_signals += 1;
+          }
+        },
+        None => skipped_signals += 1,
+      }
+    }
+
+    let slippage = strategy.slippage_percent / 100.0;
+    let commission = strategy.commission_percent / 100.0;
+    let mut equity = strategy.initial_equity;
+    let mut position: Option<Position> = None;
+    let mut trades = Vec::<Trade>::new();
+    let mut equity_curve = Vec::<(Time, f64)>::new();
+    let mut candles_in_market = 0;
+
+    for (index, candle) in candles.iter().enumerate() {
+      let signal = candle_signals[index];
+
+      // exits
+      if let Some(open) = &position {
+        let mut exit: Option<(f64, ExitReason)> = None;
+        if index > open.entry_index {
+          exit = Self::stop_or_target(open, candle);
+        }
+        if exit.is_none() {
+          match strategy.exit_rule {
+            ExitRule::HoldCandles(candles_to_hold) if index - open.entry_index >= candles_to_hold => {
+              exit = Some((candle.close, ExitReason::Hold));
+            },
+            ExitRule::OppositeSignal => {
+              if let Some(signal) = signal {
+                if signal.side == open.side.opposite() {
+                  exit = Some((candle.close, ExitReason::OppositeSignal));
+                }
+              }
+            },
+            _ => {}
+          }
+        }
+        if exit.is_none() && index == candles.len() - 1 {
+          exit = Some((candle.close, ExitReason::EndOfData));
+        }
+        if let Some((price, exit_reason)) = exit {
+          let trade = Self::close_position(open, candle, index, price, exit_reason, slippage, commission);
+          equity = trade.equity_after;
+          trades.push(trade);
+          position = None;
+        } else if signal.is_some() {
+          skipped_signals += 1;
+        }
+      }
+
+      // entries
+      if position.is_none() && equity > 0.0 && index < candles.len() - 1 {
+        if let Some(signal) = signal {
+          if signal.side == Side::Long || strategy.allow_short {
+            let entry_price = candle.close * (1.0 + signal.side.direction() * slippage);
+            let offset_price = |offset: &PriceOffset, atr: &[f64]| match offset {
+              PriceOffset::Percent(percent) => entry_price * percent / 100.0,
+              PriceOffset::Atr { multiple, .. } => atr[index] * multiple,
+            };
+            let direction = signal.side.direction();
+            position = Some(Position {
+              side: signal.side,
+              signal_date: signal.date,
+              entry_date: candle.date,
+              entry_index: index,
+              entry_price,
+              stop_price: strategy.stop.as_ref().map(|stop| entry_price - direction * offset_price(stop, &stop_atr)),
+              target_price: strategy.target.as_ref().map(|target| entry_price + direction * offset_price(target, &target_atr)),
+              equity_at_entry: equity * (1.0 - commission),
+            });
+          }
+        }
+      }
+
+      // mark to market
+      let marked_equity = match &position {
+        Some(open) => {
+          candles_in_market += 1;
+          (open.equity_at_entry * (1.0 + open.side.direction() * (candle.close / open.entry_price - 1.0))).max(0.0)
+        },
+        None => equity
+      };
+      equity_curve.push((candle.date, marked_equity));
+    }
+
+    Self {
+      strategy: strategy.clone(),
+      trades,
+      equity_curve,
+      candles_in_market,
+      skipped_signals,
+    }
+  }
+
+  fn stop_or_target(position: &Position, candle: &Candle) -> Option<(f64, ExitReason)> {
+    let long = position.side == Side::Long;
+    if let Some(stop) = position.stop_price {
+      let hit = if long { candle.low <= stop } else { candle.high >= stop };
+      if hit {
+        // gapped through the stop
+        let gapped = if long { candle.open < stop } else { candle.open > stop };
+        return Some((if gapped { candle.open } else { stop }, ExitReason::Stop));
+      }
+    }
+    if let Some(target) = position.target_price {
+      let hit = if long { candle.high >= target } else { candle.low <= target };
+      if hit {
+        let gapped = if long { candle.open > target } else { candle.open < target };
+        return Some((if gapped { candle.open } else { target }, ExitReason::Target));
+      }
+    }
+    None
+  }
+
+  fn close_position(
+    position: &Position,
+    candle: &Candle,
+    index: usize,
+    price: f64,
+    exit_reason: ExitReason,
+    slippage: f64,
+    commission: f64
+  ) -> Trade {
+    let direction = position.side.direction();
+    let exit_price = price * (1.0 - direction * slippage);
+    let gross_return = direction * (exit_price / position.entry_price - 1.0);
+    // a short can lose more than the position, the account is wiped out at zero
+    let equity_after = (position.equity_at_entry * (1.0 + gross_return) * (1.0 - commission)).max(0.0);
+    let equity_before = position.equity_at_entry / (1.0 - commission);
+    Trade {
+      side: position.side,
+      signal_date: position.signal_date,
+      entry_date: position.entry_date,
+      entry_price: position.entry_price,
+      exit_date: candle.date,
+      exit_price,
+      exit_reason,
+      candles_held: index - position.entry_index,
+      return_percent: (equity_after / equity_before - 1.0) * 100.0,
+      equity_after,
+    }
+  }
+
+  pub fn final_equity(&self) -> f64 {
+    match self.equity_curve.last() {
+      Some((_, equity)) => *equity,
+      None => self.strategy.initial_equity
+    }
+  }
+
+  /// Percent return over the whole simulation
+  pub fn total_return(&self) -> f64 {
+    (self.final_equity() / self.strategy.initial_equity - 1.0) * 100.0
+  }
+
+  fn years(&self) -> f64 {
+    match (self.equity_curve.first(), self.equity_curve.last()) {
+      (Some((start, _)), Some((end, _))) => start.diff_days(end) as f64 / DAYS_PER_YEAR,
+      _ => 0.0
+    }
+  }
+
+  /// Compound annual growth rate in percent
+  pub fn cagr(&self) -> f64 {
+    let years = self.years();
+    if years <= 0.0 {
+      return 0.0;
+    }
+    if self.final_equity() <= 0.0 {
+      return -100.0;
+    }
+    ((self.final_equity() / self.strategy.initial_equity).powf(1.0 / years) - 1.0) * 100.0
+  }
+
+  /// Largest percent decline of the equity curve from a previous peak
+  pub fn max_drawdown(&self) -> f64 {
+    let mut peak = self.strategy.initial_equity;
+    let mut max_drawdown: f64 = 0.0;
+    for (_, equity) in self.equity_curve.iter() {
+      peak = peak.max(*equity);
+      max_drawdown = max_drawdown.max((1.0 - equity / peak) * 100.0);
+    }
+    max_drawdown
+  }
+
+  /// Returns of the equity curve from one candle to the next
+  fn period_returns(&self) -> Vec<f64> {
+    self.equity_curve.windows(2)
+      .map(|pair| if pair[0].1 > 0.0 { pair[1].1 / pair[0].1 - 1.0 } else { 0.0 })
+      .collect()
+  }
+
+  /// Candles per year, e.g. ~252 for SPX and 365 for BTCUSD
+  fn periods_per_year(&self) -> f64 {
+    let years = self.years();
+    if years <= 0.0 {
+      return 0.0;
+    }
+    (self.equity_curve.len() - 1) as f64 / years
+  }
+
+  /// Annualized Sharpe ratio with a zero risk-free rate
+  pub fn sharpe_ratio(&self) -> f64 {
+    let returns = self.period_returns();
+    if returns.len() < 2 {
+      return 0.0;
+    }
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let variance = returns.iter().map(|r| (r - mean) * (r - mean)).sum::<f64>() / (returns.len() - 1) as f64;
+    if variance == 0.0 {
+      return 0.0;
+    }
+    mean / variance.sqrt() * self.periods_per_year().sqrt()
+  }
+
+  /// Annualized Sortino ratio: like Sharpe, but only penalizes downside deviation
+  pub fn sortino_ratio(&self) -> f64 {
+    let returns = self.period_returns();
+    if returns.is_empty() {
+      return 0.0;
+    }
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let downside = returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / returns.len() as f64;
+    if downside == 0.0 {
+      return 0.0;
+    }
+    mean / downside.sqrt() * self.periods_per_year().sqrt()
+  }
+
+  /// Percent of candles with an open position
+  pub fn exposure(&self) -> f64 {
+    if self.equity_curve.is_empty() {
+      return 0.0;
+    }
+    self.candles_in_market as f64 / self.equity_curve.len() as f64 * 100.0
+  }
+
+  /// Fraction of trades with a positive net return
+  pub fn win_rate(&self) -> f64 {
+    if self.trades.is_empty() {
+      return 0.0;
+    }
+    self.trades.iter().filter(|trade| trade.return_percent > 0.0).count() as f64 / self.trades.len() as f64
+  }
+
+  pub fn print(&self) {
+    println!("TRADES\tWIN RATE\tTOTAL RETURN\tCAGR\tMAX DRAWDOWN\tSHARPE\tSORTINO\tEXPOSURE");
+    println!(
+      "{}\t{:.1}%\t\t{:.1}%\t\t{:.2}%\t{:.1}%\t\t{:.2}\t{:.2}\t{:.1}%",
+      self.trades.len(),
+      self.win_rate() * 100.0,
+      self.total_return(),
+      self.cagr(),
+      self.max_drawdown(),
+      self.sharpe_ratio(),
+      self.sortino_ratio(),
+      self.exposure()
+    );
+  }
+
+  pub fn print_trades(&self) {
+    println!("SIDE\tSIGNAL\t\tENTRY\t\tENTRY PRICE\tEXIT\t\tEXIT PRICE\tREASON\tCANDLES\tRETURN\tEQUITY");
+    for trade in self.trades.iter() {
+      println!(
+        "{}\t{}\t{}\t{:.2}\t\t{}\t{:.2}\t\t{}\t{}\t{:.2}%\t{:.2}",
+        trade.side.as_string(),
+        trade.signal_date.as_string(),
+        trade.entry_date.as_string(),
+        trade.entry_price,
+        trade.exit_date.as_string(),
+        trade.exit_price,
+        trade.exit_reason.as_string(),
+        trade.candles_held,
+        trade.return_percent,
+        trade.equity_after
+      );
+    }
+  }
+
+  /// Write the per-trade log to CSV.
+  pub fn write_trades_csv(&self, out_file: &PathBuf) -> std::io::Result<()> {
+    let mut wtr = WriterBuilder::new().from_path(out_file)?;
+    wtr.write_record([
+      "side", "signal_date", "entry_date", "entry_price", "exit_date", "exit_price", "exit_reason", "candles_held", "return_percent", "equity_after"
+    ])?;
+    for trade in self.trades.iter() {
+      wtr.write_record(&[
+        trade.side.as_string(),
+        trade.signal_date.as_string(),
+        trade.entry_date.as_string(),
+        trade.entry_price.to_string(),
+        trade.exit_date.as_string(),
+        trade.exit_price.to_string(),
+        trade.exit_reason.as_string(),
+        trade.candles_held.to_string(),
+        trade.return_percent.to_string(),
+        trade.equity_after.to_string()
+      ])?;
+    }
+    wtr.flush()
+  }
+
+  pub fn plot_equity(&self, out_file: &str, plot_title: &str, plot_color: &RGBColor) {
+    if self.equity_curve.is_empty() {
+      return;
+    }
+    let data: Vec<(NaiveDate, f64)> = self.equity_curve.iter()
+      .map(|(date, equity)| (date.to_naive_date(), *equity))
+      .collect();
+    let root = BitMapBackend::new(out_file, (2048, 1024)).into_drawing_area();
+    root.fill(&WHITE).unwrap();
+    let from_date = data[0].0;
+    let to_date = data[data.len() - 1].0;
+    let min_equity = data.iter().fold(f64::MAX, |min, (_, equity)| min.min(*equity));
+    let max_equity = data.iter().fold(f64::MIN, |max, (_, equity)| max.max(*equity));
+    let mut chart = ChartBuilder::on(&root)
+      .x_label_area_size(40)
+      .y_label_area_size(80)
+      .caption(plot_title, ("sans-serif", 50.0).into_font())
+      .build_cartesian_2d(from_date..to_date, (min_equity * 0.95)..(max_equity * 1.05)).unwrap();
+    chart.configure_mesh().light_line_style(WHITE).draw().unwrap();
+    chart.draw_series(LineSeries::new(data, plot_color)).unwrap();
+    // To avoid the IO failure being ignored silently, we manually call the present function
+    root.present().expect("Unable to write result to file, please make sure the output dir exists");
+    println!("Result has been saved to {}", out_file);
+  }
+}
@@ -334,6 +334,26 @@ impl SquareOfNine {
     &self.values
   }
 
+  /// Degree on the Square of Nine of the cell nearest `value`, the middle of the cell's arc.
+  /// `None` if `value` is the origin or outside the square.
+  pub fn angle_of_value(&self, value: f64) -> Option<f32> {
+    if self.step <= 0.0 || value < self.origin {
+      return None;
+    }
+    // values increase by `step` along the spiral
+    let index = ((value - self.origin) / self.step).round() as usize;
+    let (start, end) = self.values.get(index)?.arc?;
+    let width = if end >= start { end - start } else { end + 360.0 - start };
+    Some(Angle::new(start).increment(width / 2.0))
+  }
+
+  /// Smallest odd dimension with a cell for every value from `origin` to `max_value`.
+  pub fn dimension_for(origin: f64, step: f64, max_value: f64) -> u32 {
+    let cells = ((max_value - origin) / step).max(0.0).ceil() + 1.0;
+    let dimension = cells.sqrt().ceil() as u32;
+    if dimension.is_multiple_of(2) { dimension + 1 } else { dimension }
+  }
+
   /// Search SquareOfNine for all prices (harmonic points) along longitude.
   /// Price=Time
   pub fn find_price_equals_time(&self, angle: f32) -> Vec<Point> {
@@ -0,0 +1,260 @@
+use std::path::PathBuf;
+use log::debug;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+const SCHEMA: &str = "
+  CREATE TABLE IF NOT EXISTS candles (
+    symbol TEXT NOT NULL,
+    interval TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    open REAL NOT NULL,
+    high REAL NOT NULL,
+    low REAL NOT NULL,
+    close REAL NOT NULL,
+    volume REAL,
+    PRIMARY KEY (symbol, interval, timestamp)
+  );
+  CREATE TABLE IF NOT EXISTS body_positions (
+    target TEXT NOT NULL,
+    origin TEXT NOT NULL,
+    coordinate TEXT NOT NULL,
+    date INTEGER NOT NULL,
+    value REAL NOT NULL,
+    PRIMARY KEY (target, origin, coordinate, date)
+  );
+  CREATE TABLE IF NOT EXISTS signal_events (
+    signal TEXT NOT NULL,
+    parameters TEXT NOT NULL,
+    date INTEGER NOT NULL,
+    label TEXT NOT NULL,
+    value REAL,
+    detail TEXT,
+    PRIMARY KEY (signal, parameters, date, label)
+  );
+";
+
+/// One occurrence of a computed signal, keyed by the signal's name and parameters.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SignalEvent {
+  /// e.g. "retrograde" or "planet_matrix"
+  pub signal: String,
+  /// Parameters the signal was computed with, as JSON so reruns with the same settings match
+  pub parameters: String,
+  pub date: Time,
+  /// What happened on `date`, e.g. "Start" or "Conjunct"
+  pub label: String,
+  pub value: Option<f64>,
+  /// Anything else needed to rebuild the event, as JSON
+  pub detail: Option<String>,
+}
+
+/// Local SQLite database of candles per symbol and interval, daily body positions per origin and
+/// coordinate, and computed signal events. Writes are upserts, so data can be added incrementally.
+pub struct Store {
+  connection: Connection,
+}
+
+impl Store {
+  /// Open or create the database at `path`.
+  pub fn open(path: &PathBuf) -> std::io::Result<Self> {
+    Self::init(Connection::open(path).map_err(Self::error)?)
+  }
+
+  /// Database that lives as long as the `Store`.
+  pub fn in_memory() -> std::io::Result<Self> {
+    Self::init(Connection::open_in_memory().map_err(Self::error)?)
+  }
+
+  fn init(connection: Connection) -> std::io::Result<Self> {
+    connection.execute_batch(SCHEMA).map_err(Self::error)?;
+    Ok(Self { connection })
+  }
+
+  fn error(error: rusqlite::Error) -> std::io::Error {
+    std::io::Error::other(format!("Store: {}", error))
+  }
+
+  /// Insert candles of `symbol`, replacing any with the same interval and timestamp. Returns rows written.
+  /// Daily and coarser candles also replace any of the same interval on the same date, as `TickerData` keys them,
+  /// since vendors stamp sessions at different times of day.
+  pub fn upsert_candles(&self, symbol: &str, candles: &[Candle]) -> std::io::Result<usize> {
+    let transaction = self.connection.unchecked_transaction().map_err(Self::error)?;
+    {
+      let mut delete_date = transaction.prepare_cached(
+        "DELETE FROM candles WHERE symbol = ?1 AND interval = ?2 AND timestamp >= ?3 AND timestamp < ?4"
+      ).map_err(Self::error)?;
+      let mut statement = transaction.prepare_cached(
+        "INSERT OR REPLACE INTO candles (symbol, interval, timestamp, open, high, low, close, volume)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+      ).map_err(Self::error)?;
+      for candle in candles.iter() {
+        if !candle.interval.is_intraday() {
+          delete_date.execute(params![
+            symbol,
+            candle.interval.to_str(),
+            candle.date.to_unix(),
+            candle.date.delta_date(1).to_unix()
+          ]).map_err(Self::error)?;
+        }
+        statement.execute(params![
+          symbol,
+          candle.interval.to_str(),
+          candle.timestamp,
+          candle.open,
+          candle.high,
+          candle.low,
+          candle.close,
+          candle.volume
+        ]).map_err(Self::error)?;
+      }
+    }
+    transaction.commit().map_err(Self::error)?;
+    debug!("Stored {} {} candles", candles.len(), symbol);
+    Ok(candles.len())
+  }
+
+  pub fn save_ticker_data(&self, symbol: &str, ticker_data: &TickerData) -> std::io::Result<usize> {
+    self.upsert_candles(symbol, &ticker_data.candles)
+  }
+
+  /// Every stored candle of `symbol` at `interval`, empty if there are none.
+  pub fn load_ticker_data(&self, symbol: &str, interval: Interval) -> std::io::Result<TickerData> {
+    let mut statement = self.connection.prepare_cached(
+      "SELECT timestamp, open, high, low, close, volume FROM candles
+       WHERE symbol = ?1 AND interval = ?2 ORDER BY timestamp"
+    ).map_err(Self::error)?;
+    let candles = statement.query_map(params![symbol, interval.to_str()], |row| {
+      let timestamp: i64 = row.get(0)?;
+      Ok(Candle {
+        date: Time::from_unix(timestamp),
+        timestamp,
+        interval,
+        open: row.get(1)?,
+        high: row.get(2)?,
+        low: row.get(3)?,
+        close: row.get(4)?,
+        volume: row.get(5)?
+      })
+    }).map_err(Self::error)?
+      .collect::<Result<Vec<Candle>, _>>()
+      .map_err(Self::error)?;
+
+    let mut ticker_data = TickerData::new();
+    ticker_data.add_series(candles)?;
+    Ok(ticker_data)
+  }
+
+  /// Timestamp of the latest stored candle, to fetch only what's newer.
+  pub fn latest_candle_timestamp(&self, symbol: &str, interval: Interval) -> std::io::Result<Option<i64>> {
+    self.connection.query_row(
+      "SELECT MAX(timestamp) FROM candles WHERE symbol = ?1 AND interval = ?2",
+      params![symbol, interval.to_str()],
+      |row| row.get(0)
+    ).map_err(Self::error)
+  }
+
+  /// Symbols and intervals with stored candles.
+  pub fn candle_series(&self) -> std::io::Result<Vec<(String, Interval)>> {
+    let mut statement = self.connection.prepare("SELECT DISTINCT symbol, interval FROM candles ORDER BY symbol, interval")
+      .map_err(Self::error)?;
+    let series = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+      .map_err(Self::error)?
+      .collect::<Result<Vec<(String, String)>, _>>()
+      .map_err(Self::error)?;
+    Ok(series.into_iter()
+      .filter_map(|(symbol, interval)| Interval::from_string(&interval).map(|interval| (symbol, interval)))
+      .collect())
+  }
+
+  /// Insert daily `coordinate` values of `target` seen from `origin`, replacing any on the same date.
+  pub fn upsert_positions(&self, target: &str, origin: &str, coordinate: &str, positions: &[(Time, f32)]) -> std::io::Result<usize> {
+    let transaction = self.connection.unchecked_transaction().map_err(Self::error)?;
+    {
+      let mut statement = transaction.prepare_cached(
+        "INSERT OR REPLACE INTO body_positions (target, origin, coordinate, date, value) VALUES (?1, ?2, ?3, ?4, ?5)"
+      ).map_err(Self::error)?;
+      for (date, value) in positions.iter() {
+        statement.execute(params![target, origin, coordinate, date.to_unix(), *value as f64]).map_err(Self::error)?;
+      }
+    }
+    transaction.commit().map_err(Self::error)?;
+    debug!("Stored {} {} {} {} positions", positions.len(), origin, target, coordinate);
+    Ok(positions.len())
+  }
+
+  /// Stored positions from `start` to `end` inclusive, by date. Dates never stored are missing.
+  pub fn load_positions(&self, target: &str, origin: &str, coordinate: &str, start: &Time, end: &Time) -> std::io::Result<Vec<(Time, f32)>> {
+    let mut statement = self.connection.prepare_cached(
+      "SELECT date, value FROM body_positions
+       WHERE target = ?1 AND origin = ?2 AND coordinate = ?3 AND date BETWEEN ?4 AND ?5 ORDER BY date"
+    ).map_err(Self::error)?;
+    let positions = statement.query_map(
+      params![target, origin, coordinate, start.to_unix(), end.to_unix()],
+      |row| Ok((Time::from_unix(row.get(0)?), row.get::<_, f64>(1)? as f32))
+    ).map_err(Self::error)?
+      .collect::<Result<Vec<(Time, f32)>, _>>()
+      .map_err(Self::error)?;
+    Ok(positions)
+  }
+
+  /// Insert signal events, replacing any with the same signal, parameters, date and label.
+  pub fn upsert_signal_events(&self, events: &[SignalEvent]) -> std::io::Result<usize> {
+    let transaction = self.connection.unchecked_transaction().map_err(Self::error)?;
+    {
+      let mut statement = transaction.prepare_cached(
+        "INSERT OR REPLACE INTO signal_events (signal, parameters, date, label, value, detail) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
+      ).map_err(Self::error)?;
+      for event in events.iter() {
+        statement.execute(params![
+          event.signal,
+          event.parameters,
+          event.date.to_unix(),
+          event.label,
+          event.value,
+          event.detail
+        ]).map_err(Self::error)?;
+      }
+    }
+    transaction.commit().map_err(Self::error)?;
+    debug!("Stored {} signal events", events.len());
+    Ok(events.len())
+  }
+
+  /// Events of `signal` by date, only those computed with `parameters` if given.
+  pub fn load_signal_events(&self, signal: &str, parameters: Option<&str>) -> std::io::Result<Vec<SignalEvent>> {
+    let mut statement = self.connection.prepare_cached(
+      "SELECT signal, parameters, date, label, value, detail FROM signal_events
+       WHERE signal = ?1 AND (?2 IS NULL OR parameters = ?2) ORDER BY date, label"
+    ).map_err(Self::error)?;
+    let events = statement.query_map(params![signal, parameters], |row| Ok(SignalEvent {
+      signal: row.get(0)?,
+      parameters: row.get(1)?,
+      date: Time::from_unix(row.get(2)?),
+      label: row.get(3)?,
+      value: row.get(4)?,
+      detail: row.get(5)?
+    })).map_err(Self::error)?
+      .collect::<Result<Vec<SignalEvent>, _>>()
+      .map_err(Self::error)?;
+    Ok(events)
+  }
+
+  /// Whether any events of `signal` were stored with `parameters`.
+  pub fn has_signal_events(&self, signal: &str, parameters: &str) -> std::io::Result<bool> {
+    self.connection.query_row(
+      "SELECT 1 FROM signal_events WHERE signal = ?1 AND parameters = ?2 LIMIT 1",
+      params![signal, parameters],
+      |_| Ok(())
+    ).optional().map(|row| row.is_some()).map_err(Self::error)
+  }
+
+  /// Remove the events of `signal` computed with `parameters`, before storing a recomputation.
+  pub fn delete_signal_events(&self, signal: &str, parameters: &str) -> std::io::Result<usize> {
+    self.connection.execute(
+      "DELETE FROM signal_events WHERE signal = ?1 AND parameters = ?2",
+      params![signal, parameters]
+    ).map_err(Self::error)
+  }
+}
@@ -0,0 +1,224 @@
+use log::debug;
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+/// How to define a price reversal.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum SwingDetector {
+  /// Close is the min/max of +/- N candles (`TickerData::find_reversals`)
+  Window(usize),
+  /// Price reverses by at least this percent from the last extreme
+  ZigZag(f64),
+  /// Price reverses by at least `multiple` average true ranges from the last extreme
+  Atr { period: usize, multiple: f64 },
+  /// High/low is beyond the highs/lows of `strength` candles on each side
+  Fractal(usize),
+}
+
+impl SwingDetector {
+  pub fn as_string(&self) -> String {
+    match self {
+      SwingDetector::Window(candle_range) => format!("Window {}", candle_range),
+      SwingDetector::ZigZag(percent) => format!("ZigZag {}%", percent),
+      SwingDetector::Atr { period, multiple } => format!("ATR {}x{}", multiple, period),
+      SwingDetector::Fractal(strength) => format!("Fractal {}", strength),
+    }
+  }
+}
+
+/// A reversal with the move that led into it.
+#[derive(Debug, Clone)]
+pub struct Swing {
+  pub reversal: Reversal,
+  /// High of a top, low of a bottom
+  pub price: f64,
+  /// Percent change from the previous swing's price, 0 for the first swing
+  pub magnitude_percent: f64,
+  /// Candles since the previous swing, 0 for the first swing
+  pub duration_candles: usize,
+  /// Calendar days since the previous swing, 0 for the first swing
+  pub duration_days: i64,
+}
+
+impl Swing {
+  fn price(reversal: &Reversal) -> f64 {
+    match reversal.reversal_type {
+      ReversalType::Top => reversal.candle.high,
+      ReversalType::Bottom => reversal.candle.low,
+    }
+  }
+
+  /// Measure each reversal against the one before it.
+  /// Consecutive reversals of the same type are merged, keeping the more extreme, so tops and bottoms alternate.
+  pub fn from_reversals(ticker_data: &TickerData, reversals: &[Reversal]) -> Vec<Swing> {
+    let mut alternating = Vec::<Reversal>::new();
+    for reversal in reversals.iter() {
+      match alternating.last_mut() {
+        Some(last) if last.reversal_type == reversal.reversal_type => {
+          let more_extreme = match reversal.reversal_type {
+            ReversalType::Top => Self::price(reversal) > Self::price(last),
+            ReversalType::Bottom => Self::price(reversal) < Self::price(last),
+          };
+          if more_extreme {
+            *last = reversal.clone();
+          }
+        },
+        _ => alternating.push(reversal.clone()),
+      }
+    }
+
+    let candles = ticker_data.get_candles();
+    let index_of = |timestamp: i64| candles.iter().position(|candle| candle.timestamp == timestamp).unwrap_or(0);
+    let mut swings = Vec::<Swing>::new();
+    for reversal in alternating.into_iter() {
+      let price = Self::price(&reversal);
+      let (magnitude_percent, duration_candles, duration_days) = match swings.last() {
+        Some(previous) => (
+          (price / previous.price - 1.0) * 100.0,
+          index_of(reversal.candle.timestamp).saturating_sub(index_of(previous.reversal.candle.timestamp)),
+          previous.reversal.candle.date.diff_days(&reversal.candle.date),
+        ),
+        None => (0.0, 0, 0),
+      };
+      swings.push(Swing {
+        reversal,
+        price,
+        magnitude_percent,
+        duration_candles,
+        duration_days,
+      });
+    }
+    swings
+  }
+
+  pub fn print(swings: &[Swing]) {
+    println!("DATE\t\tTYPE\tPRICE\t\tMAGNITUDE\tCANDLES\tDAYS");
+    for swing in swings.iter() {
+      println!(
+        "{}\t{}\t{:.2}\t\t{:.2}%\t\t{}\t{}",
+        swing.reversal.candle.date.as_string(),
+        swing.reversal.reversal_type.as_string(),
+        swing.price,
+        swing.magnitude_percent,
+        swing.duration_candles,
+        swing.duration_days
+      );
+    }
+  }
+}
+
+impl TickerData {
+  /// Reversals by any `SwingDetector`.
+  /// `Window` matches `find_reversals`; the other detectors alternate tops and bottoms
+  /// and use highs for tops and lows for bottoms.
+  pub fn detect_reversals(&self, detector: &SwingDetector) -> Vec<Reversal> {
+    match detector {
+      SwingDetector::Window(candle_range) => self.find_reversals(*candle_range),
+      SwingDetector::ZigZag(percent) => self.find_threshold_reversals(|_, price| price * percent / 100.0),
+      SwingDetector::Atr { period, multiple } => {
+        let atr = self.average_true_range(*period);
+        self.find_threshold_reversals(|index, _| atr[index] * multiple)
+      },
+      SwingDetector::Fractal(strength) => self.find_fractal_reversals(*strength),
+    }
+  }
+
+  /// Reversals with magnitude and duration.
+  pub fn find_swings(&self, detector: &SwingDetector) -> Vec<Swing> {
+    Swing::from_reversals(self, &self.detect_reversals(detector))
+  }
+
+  /// ZigZag: confirm the last extreme as a reversal once price moves against it by `threshold(extreme_index, extreme_price)`.
+  /// The final extreme is unconfirmed and not returned.
+  fn find_threshold_reversals<F: Fn(usize, f64) -> f64>(&self, threshold: F) -> Vec<Reversal> {
+    let candles = &self.candles;
+    let mut reversals = Vec::<Reversal>::new();
+    if candles.is_empty() {
+      return reversals;
+    }
+    let reversal = |index: usize, reversal_type: ReversalType| {
+      debug!("{}: {:?}", reversal_type.as_string(), candles[index].date.as_string());
+      Reversal { candle: candles[index].clone(), reversal_type }
+    };
+    // None until the first move exceeds the threshold
+    let mut direction: Option<ReversalType> = None;
+    let mut high_index = 0;
+    let mut low_index = 0;
+    for (index, candle) in candles.iter().enumerate().skip(1) {
+      match direction {
+        None => {
+          if candle.high > candles[high_index].high {
+            high_index = index;
+          }
+          if candle.low < candles[low_index].low {
+            low_index = index;
+          }
+          if low_index < index && candle.high - candles[low_index].low >= threshold(low_index, candles[low_index].low) {
+            reversals.push(reversal(low_index, ReversalType::Bottom));
+            direction = Some(ReversalType::Top);
+            high_index = index;
+          } else if high_index < index && candles[high_index].high - candle.low >= threshold(high_index, candles[high_index].high) {
+            reversals.push(reversal(high_index, ReversalType::Top));
+            direction = Some(ReversalType::Bottom);
+            low_index = index;
+          }
+        },
+        // rising toward a top
+        Some(ReversalType::Top) => {
+          if candle.high >= candles[high_index].high {
+            high_index = index;
+          } else if candles[high_index].high - candle.low >= threshold(high_index, candles[high_index].high) {
+            reversals.push(reversal(high_index, ReversalType::Top));
+            direction = Some(ReversalType::Bottom);
+            low_index = index;
+          }
+        },
+        // falling toward a bottom
+        Some(ReversalType::Bottom) => {
+          if candle.low <= candles[low_index].low {
+            low_index = index;
+          } else if candle.high - candles[low_index].low >= threshold(low_index, candles[low_index].low) {
+            reversals.push(reversal(low_index, ReversalType::Bottom));
+            direction = Some(ReversalType::Top);
+            high_index = index;
+          }
+        },
+      }
+    }
+    reversals
+  }
+
+  /// Fractal pivots: a high above the highs of `strength` candles on both sides is a top,
+  /// a low below the lows of `strength` candles on both sides is a bottom.
+  /// Consecutive pivots of the same type keep the more extreme.
+  fn find_fractal_reversals(&self, strength: usize) -> Vec<Reversal> {
+    let candles = &self.candles;
+    let mut pivots = Vec::<Reversal>::new();
+    if strength == 0 || candles.len() < 2 * strength + 1 {
+      return pivots;
+    }
+    for index in strength..(candles.len() - strength) {
+      let neighbors = candles[index - strength..index].iter().chain(candles[index + 1..=index + strength].iter());
+      let (is_top, is_bottom) = neighbors.fold((true, true), |(top, bottom), neighbor| (
+        top && candles[index].high > neighbor.high,
+        bottom && candles[index].low < neighbor.low
+      ));
+      let last_type = pivots.last().map(|pivot| pivot.reversal_type.clone());
+      let reversal_type = match (is_top, is_bottom) {
+        (true, false) => ReversalType::Top,
+        (false, true) => ReversalType::Bottom,
+        // outside candle: continue the alternation
+        (true, true) => match last_type {
+          Some(ReversalType::Top) => ReversalType::Bottom,
+          _ => ReversalType::Top,
+        },
+        (false, false) => continue,
+      };
+      pivots.push(Reversal {
+        candle: candles[index].clone(),
+        reversal_type
+      });
+    }
+    Swing::from_reversals(self, &pivots).into_iter().map(|swing| swing.reversal).collect()
+  }
+}
@@ -1,13 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::collections::hash_map::Entry;
-use std::fs::File;
 use std::path::PathBuf;
-use csv;
 use log::debug;
+use serde::{Deserialize, Serialize};
 use crate::Time;
 use crate::*;
 use std::io::Error;
-use std::str::FromStr;
 
 
 #[derive(Debug, Clone)]
@@ -22,7 +20,7 @@ pub enum FirstMove {
   EngulfingLow
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum ReversalType {
   Top,
   Bottom
@@ -42,7 +40,7 @@ impl PartialEq for ReversalType {
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Reversal {
   pub candle: Candle,
   pub reversal_type: ReversalType,
@@ -53,7 +51,9 @@ pub struct TickerData {
   /// Candlestick history of a ticker.
   pub candles: Vec<Candle>,
   hashmap: HashMap<u64, Candle>,
-  hasher: CandleHasher
+  hasher: CandleHasher,
+  /// Timestamps of candles marked suspect by `repair`
+  pub(crate) flagged: HashSet<i64>
 }
 
 impl Default for TickerData {
@@ -61,7 +61,8 @@ impl Default for TickerData {
     Self {
       candles: Vec::<Candle>::new(),
       hashmap: HashMap::new(),
-      hasher: CandleHasher::new()
+      hasher: CandleHasher::new(),
+      flagged: HashSet::new()
     }
   }
 }
@@ -72,50 +73,46 @@ impl TickerData {
   }
 
   /// Read candles from CSV file.
-  /// Handles duplicate candles and sorts candles by date.
+  /// Handles duplicate candles and sorts candles by timestamp.
   /// Expects date of candle to be in UNIX timestamp format.
-  /// CSV format: date,open,high,low,close,volume
+  /// The interval is inferred from the most common gap between timestamps, daily if there is only one candle.
+  /// CSV format: date,open,high,low,close,volume (see `CsvSchema` for other layouts)
   pub fn add_csv_series(&mut self, csv_path: &PathBuf) -> Result<(), Error> {
-    let file_buffer = File::open(csv_path).unwrap();
-    let mut csv = csv::Reader::from_reader(file_buffer);
+    self.add_csv_with_schema(csv_path, &CsvSchema::default())
+  }
 
-    let mut headers = Vec::new();
-    if let Ok(result) = csv.headers() {
-      for header in result {
-        headers.push(String::from(header));
+  /// Interval of the most common gap between candle timestamps (the smallest if tied), daily if it isn't a known interval.
+  /// 23 and 25 hour gaps count as a day, from sessions stamped at local midnight across a daylight saving change.
+  pub(crate) fn infer_interval(candles: &[Candle]) -> Interval {
+    let mut timestamps: Vec<i64> = candles.iter().map(|candle| candle.timestamp).collect();
+    timestamps.sort();
+    let mut gap_counts = HashMap::<i64, usize>::new();
+    for pair in timestamps.windows(2) {
+      let gap = match pair[1] - pair[0] {
+        82_800 | 90_000 => 86_400,
+        gap => gap
+      };
+      if gap > 0 {
+        *gap_counts.entry(gap).or_insert(0) += 1;
       }
     }
+    gap_counts.into_iter()
+      .max_by_key(|(gap, count)| (*count, std::cmp::Reverse(*gap)))
+      .and_then(|(gap, _)| Interval::from_seconds(gap))
+      .unwrap_or(Interval::Daily)
+  }
 
-    for record in csv.records().flatten() {
-      let date = Time::from_unix(
-        record[0].parse::<i64>().expect("failed to parse candle UNIX timestamp into i64")
-      );
-      let volume = match record[5].parse::<String>() {
-        Err(_) => None,
-        Ok(vol) => {
-          if vol == "NaN" {
-            None
-          } else {
-            let vol = f64::from_str(&vol).expect("failed to parse candle volume into f64");
-            Some(vol)
-          }
-        }
-      };
-      let candle = Candle {
-        date,
-        open: f64::from_str(&record[1]).expect("failed to parse candle volume into f64"),
-        high: f64::from_str(&record[2]).expect("failed to parse candle volume into f64"),
-        low: f64::from_str(&record[3]).expect("failed to parse candle volume into f64"),
-        close: f64::from_str(&record[4]).expect("failed to parse candle volume into f64"),
-        volume
-      };
-      self.append_candle(&candle);
+  /// Interval shared by every candle, `None` if empty or mixed.
+  pub fn interval(&self) -> Option<Interval> {
+    let interval = self.candles.first()?.interval;
+    match self.candles.iter().all(|candle| candle.interval == interval) {
+      true => Some(interval),
+      false => None
     }
-    Ok(())
   }
 
   /// Append vector of candles received from an API to existing candles.
-  /// Handles duplicate candles and sorts candles by date.
+  /// Handles duplicate candles and sorts candles by timestamp.
   pub fn add_series(&mut self, new_candles: Vec<Candle>) -> Result<(), Error> {
     for candle in new_candles.into_iter() {
       self.append_candle(&candle);
@@ -123,14 +120,14 @@ impl TickerData {
     Ok(())
   }
 
-  /// If candle does not exist in self.candles, append candle to self.candles.
-  /// Sort candles by date.
-  fn append_candle(&mut self, candle: &Candle) {
+  /// If no candle with the same key (see `CandleHasher::hash_candle`) exists in self.candles, append candle to self.candles.
+  /// Sort candles by timestamp.
+  pub(crate) fn append_candle(&mut self, candle: &Candle) {
     let key = self.hasher.hash_candle(candle);
     if let Entry::Vacant(e) = self.hashmap.entry(key) {
       e.insert(candle.clone());
       self.candles.push(candle.clone());
-      self.candles.sort_by(|a, b| a.date.partial_cmp(&b.date).expect("Failed to partial compare candle dates"));
+      self.candles.sort_by_key(|candle| candle.timestamp);
     }
   }
 
@@ -148,6 +145,46 @@ impl TickerData {
     Ok(())
   }
 
+  /// Copy of the candles from `start_date` to `end_date` inclusive.
+  pub fn between(&self, start_date: &Time, end_date: &Time) -> Self {
+    let mut ticker_data = Self::new();
+    for candle in self.candles.iter().filter(|candle| candle.date >= *start_date && candle.date <= *end_date) {
+      let key = ticker_data.hasher.hash_candle(candle);
+      ticker_data.hashmap.insert(key, candle.clone());
+      ticker_data.candles.push(candle.clone());
+      if self.flagged.contains(&candle.timestamp) {
+        ticker_data.flagged.insert(candle.timestamp);
+      }
+    }
+    ticker_data
+  }
+
+  /// Wilder's average true range for each candle.
+  /// The first `period` candles average the true ranges available so far.
+  pub fn average_true_range(&self, period: usize) -> Vec<f64> {
+    let mut atr = Vec::<f64>::with_capacity(self.candles.len());
+    for (index, candle) in self.candles.iter().enumerate() {
+      let true_range = match index {
+        0 => candle.high - candle.low,
+        _ => {
+          let prev_close = self.candles[index - 1].close;
+          (candle.high - candle.low)
+            .max((candle.high - prev_close).abs())
+            .max((candle.low - prev_close).abs())
+        }
+      };
+      let value = match atr.last() {
+        None => true_range,
+        Some(prev_atr) => {
+          let smoothing = (index + 1).min(period.max(1)) as f64;
+          prev_atr + (true_range - prev_atr) / smoothing
+        }
+      };
+      atr.push(value);
+    }
+    atr
+  }
+
   /// Get reference to `Vec<Candle>` from `TickerData`.
   pub fn get_candles(&self) -> &Vec<Candle> {
     &self.candles
@@ -299,10 +336,9 @@ impl TickerData {
     }
   }
 
-  pub fn square_price_reversals(&self, candle_range: usize) -> Vec<ReversalPrediction> {
+  pub fn square_price_reversals(&self, swing_detector: &SwingDetector) -> Vec<ReversalPrediction> {
     let mut time_cycle_reversals = Vec::<ReversalPrediction>::new();
-    let reversals = self.find_reversals(candle_range);
-    // finds all reversals defined as +/- candle_range, which is 20 right now.
+    let reversals = self.detect_reversals(swing_detector);
     for reversal in reversals.iter() {
       // get price extreme for that reversal, which is high or low depending
       // 1-2 periods. $15000, then it returns 15. If $60000, then it returns 60 and 6
@@ -518,8 +554,8 @@ impl TickerData {
 
   /// Remove duplicate Candles from the data set.
   pub fn remove_duplicate_reversals(mut signals: Vec<Reversal>) -> Vec<Reversal> {
-    signals.sort_by(|a, b| a.candle.date.partial_cmp(&b.candle.date).expect("failed to compare dates"));
-    signals.dedup_by(|a, b| a.candle.date == b.candle.date);
+    signals.sort_by_key(|signal| signal.candle.timestamp);
+    signals.dedup_by(|a, b| a.candle.timestamp == b.candle.timestamp);
     signals
   }
 }
\ No newline at end of file
@@ -59,6 +59,21 @@ impl Time {
     }
   }
 
+  /// Parse the date and time of greatest eclipse from the NASA eclipse catalog.
+  /// Example: ("2024 April 8", "18:18:29") -> 2024-04-08T18:18:29Z
+  pub fn from_eclipse_datetime_format(date: &str, time: &str) -> std::io::Result<DateTime<Utc>> {
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid eclipse time: {} {}", date, time));
+    let date = Self::from_eclipse_date_format(date);
+    let hms = time.split(':').map(|value| value.parse::<u32>()).collect::<Result<Vec<u32>, _>>().map_err(|_| invalid())?;
+    match hms[..] {
+      [hour, minute, second] => Utc.with_ymd_and_hms(
+        date.year, date.month.to_num(), date.day.to_num(),
+        hour, minute, second
+      ).single().ok_or_else(invalid),
+      _ => Err(invalid()),
+    }
+  }
+
   pub fn from_api_format(date: &str) -> Self {
     let year = date[..4].parse::<i32>().unwrap();
     let month = Month::from_num(date[5..7].parse::<u32>().unwrap());
@@ -71,6 +86,15 @@ impl Time {
     }
   }
 
+  /// UNIX timestamp of an API datetime, with or without a time of day.
+  /// Example: "2023-03-10 15:30:00" or "2023-03-10"
+  pub fn timestamp_from_api_format(datetime: &str) -> i64 {
+    match chrono::NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S") {
+      Ok(datetime) => Utc.from_utc_datetime(&datetime).timestamp(),
+      Err(_) => Self::from_api_format(datetime).to_unix(),
+    }
+  }
+
   pub fn as_string(&self) -> String {
     format!("{}-{}-{}", self.year, self.month.to_string(), self.day.to_string())
   }
@@ -0,0 +1,238 @@
+use std::fmt::Debug;
+use log::debug;
+use crate::*;
+
+/// How the training window moves between folds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowMode {
+  /// Fixed-length training window that slides forward with the test window
+  Rolling,
+  /// Training window always starts at the first candle and grows each fold
+  Anchored,
+}
+
+/// Score used to select parameters on the training window. Higher is better.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Objective {
+  TotalReturn,
+  Cagr,
+  Sharpe,
+  Sortino,
+  WinRate,
+  /// CAGR divided by max drawdown
+  Calmar,
+}
+
+impl Objective {
+  pub fn as_string(&self) -> String {
+    match self {
+      Objective::TotalReturn => "Total Return".to_string(),
+      Objective::Cagr => "CAGR".to_string(),
+      Objective::Sharpe => "Sharpe".to_string(),
+      Objective::Sortino => "Sortino".to_string(),
+      Objective::WinRate => "Win Rate".to_string(),
+      Objective::Calmar => "Calmar".to_string(),
+    }
+  }
+
+  pub fn score(&self, simulation: &Simulation) -> f64 {
+    match self {
+      Objective::TotalReturn => simulation.total_return(),
+      Objective::Cagr => simulation.cagr(),
+      Objective::Sharpe => simulation.sharpe_ratio(),
+      Objective::Sortino => simulation.sortino_ratio(),
+      Objective::WinRate => simulation.win_rate(),
+      Objective::Calmar => {
+        let max_drawdown = simulation.max_drawdown();
+        if max_drawdown > 0.0 { simulation.cagr() / max_drawdown } else { simulation.cagr() }
+      }
+    }
+  }
+}
+
+/// Train and test date ranges of one fold.
+#[derive(Debug, Clone)]
+pub struct WalkForwardWindow {
+  pub train_start: Time,
+  pub train_end: Time,
+  pub test_start: Time,
+  pub test_end: Time,
+}
+
+impl WalkForwardWindow {
+  /// Split candles into consecutive folds of `train_candles` followed by `test_candles`.
+  /// Test windows don't overlap and step forward by `test_candles`.
+  pub fn split(
+    ticker_data: &TickerData,
+    train_candles: usize,
+    test_candles: usize,
+    mode: WindowMode
+  ) -> Vec<Self> {
+    let candles = ticker_data.get_candles();
+    let mut windows = Vec::<Self>::new();
+    if train_candles == 0 || test_candles == 0 {
+      return windows;
+    }
+    let mut test_start = train_candles;
+    while test_start < candles.len() {
+      let test_end = (test_start + test_candles).min(candles.len()) - 1;
+      let train_start = match mode {
+        WindowMode::Rolling => test_start - train_candles,
+        WindowMode::Anchored => 0,
+      };
+      windows.push(Self {
+        train_start: candles[train_start].date,
+        train_end: candles[test_start - 1].date,
+        test_start: candles[test_start].date,
+        test_end: candles[test_end].date,
+      });
+      test_start += test_candles;
+    }
+    windows
+  }
+}
+
+/// Fold sizes and selection rule for `WalkForward::run`.
+#[derive(Debug, Clone)]
+pub struct WalkForwardConfig {
+  pub train_candles: usize,
+  pub test_candles: usize,
+  pub mode: WindowMode,
+  pub objective: Objective,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkForwardFold<P> {
+  pub window: WalkForwardWindow,
+  /// Parameters with the best objective on the training window
+  pub parameters: P,
+  pub train_score: f64,
+  pub test_score: f64,
+  pub test_simulation: Simulation,
+}
+
+/// Out-of-sample validation: choose parameters on each training window, trade them on the following test window.
+#[derive(Debug, Clone)]
+pub struct WalkForward<P> {
+  pub objective: Objective,
+  pub folds: Vec<WalkForwardFold<P>>,
+  /// Test windows chained end to end: each fold starts with the equity the previous fold ended with
+  pub out_of_sample: Simulation,
+}
+
+impl<P: Clone + Debug> WalkForward<P> {
+  /// Run `generator` with every entry of `parameters` on each training window, keep the best by `config.objective`,
+  /// then score those parameters on the test window.
+  /// `generator` gets the candles of the window and should only return signals inside it.
+  /// Toolkit signal generators that need ephemeris data should query it once up front and filter it here.
+  pub fn run<F>(
+    ticker_data: &TickerData,
+    parameters: &[P],
+    generator: F,
+    strategy: &Strategy,
+    config: &WalkForwardConfig
+  ) -> std::io::Result<Self>
+  where
+    F: Fn(&TickerData, &P) -> Vec<TradeSignal>
+  {
+    if parameters.is_empty() {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "No parameters to select from"));
+    }
+    let windows = WalkForwardWindow::split(ticker_data, config.train_candles, config.test_candles, config.mode);
+    let objective = config.objective;
+    if windows.is_empty() {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Not enough candles for one train and test window"));
+    }
+
+    let mut folds = Vec::<WalkForwardFold<P>>::new();
+    for window in windows.into_iter() {
+      let train_data = ticker_data.between(&window.train_start, &window.train_end);
+      let mut best: Option<(&P, f64)> = None;
+      for parameter in parameters.iter() {
+        let simulation = Simulation::run(&train_data, &generator(&train_data, parameter), strategy);
+        let score = objective.score(&simulation);
+        debug!("Train {} to {}\t{:?}\t{}", window.train_start.as_string(), window.train_end.as_string(), parameter, score);
+        if best.is_none() || score > best.unwrap().1 {
+          best = Some((parameter, score));
+        }
+      }
+      let (parameter, train_score) = best.unwrap();
+
+      let test_data = ticker_data.between(&window.test_start, &window.test_end);
+      let test_simulation = Simulation::run(&test_data, &generator(&test_data, parameter), strategy);
+      folds.push(WalkForwardFold {
+        window,
+        parameters: parameter.clone(),
+        train_score,
+        test_score: objective.score(&test_simulation),
+        test_simulation,
+      });
+    }
+
+    let out_of_sample = Self::stitch(&folds, strategy);
+    Ok(Self {
+      objective,
+      folds,
+      out_of_sample
+    })
+  }
+
+  /// Chain test simulations into one, compounding equity across folds.
+  fn stitch(folds: &[WalkForwardFold<P>], strategy: &Strategy) -> Simulation {
+    let mut trades = Vec::<Trade>::new();
+    let mut equity_curve = Vec::<(Time, f64)>::new();
+    let mut candles_in_market = 0;
+    let mut skipped_signals = 0;
+    let mut equity = strategy.initial_equity;
+    for fold in folds.iter() {
+      let simulation = &fold.test_simulation;
+      let scale = equity / strategy.initial_equity;
+      for trade in simulation.trades.iter() {
+        let mut trade = trade.clone();
+        trade.equity_after *= scale;
+        trades.push(trade);
+      }
+      for (date, fold_equity) in simulation.equity_curve.iter() {
+        equity_curve.push((*date, fold_equity * scale));
+      }
+      candles_in_market += simulation.candles_in_market;
+      skipped_signals += simulation.skipped_signals;
+      equity = simulation.final_equity() * scale;
+    }
+    Simulation {
+      strategy: strategy.clone(),
+      trades,
+      equity_curve,
+      candles_in_market,
+      skipped_signals,
+    }
+  }
+
+  /// Mean of test scores divided by mean of train scores.
+  /// Near 1 means the training performance carried over, near 0 or negative suggests overfitting.
+  pub fn efficiency(&self) -> f64 {
+    let train = self.folds.iter().map(|fold| fold.train_score).sum::<f64>();
+    let test = self.folds.iter().map(|fold| fold.test_score).sum::<f64>();
+    if train == 0.0 { 0.0 } else { test / train }
+  }
+
+  pub fn print(&self) {
+    println!("OBJECTIVE: {}", self.objective.as_string());
+    println!("TRAIN START\tTRAIN END\tTEST START\tTEST END\tTRAIN\tTEST\tPARAMETERS");
+    for fold in self.folds.iter() {
+      println!(
+        "{}\t{}\t{}\t{}\t{:.2}\t{:.2}\t{:?}",
+        fold.window.train_start.as_string(),
+        fold.window.train_end.as_string(),
+        fold.window.test_start.as_string(),
+        fold.window.test_end.as_string(),
+        fold.train_score,
+        fold.test_score,
+        fold.parameters
+      );
+    }
+    println!("Walk-forward efficiency: {:.2}", self.efficiency());
+    println!("### OUT OF SAMPLE ###");
+    self.out_of_sample.print();
+  }
+}
@@ -1,7 +1,8 @@
 use std::path::PathBuf;
 use log::debug;
 use ephemeris::*;
-use time_series::{Direction, ReversalType, TickerData, Time};
+use time_series::{Direction, Reversal, ReversalType, SwingDetector, TickerData, Time, Tolerance};
+use crate::*;
 
 
 #[derive(Clone, Debug)]
@@ -64,17 +65,25 @@ impl PlanetEquatorCrosses {
     Self { equator_crosses }
   }
 
-  pub async fn test_declinations(start_date: Time, stop_date: Time, candle_range: usize, error_margin_days: i64) {
+  pub async fn test_declinations(start_date: Time, stop_date: Time, swing_detector: &SwingDetector, error_margin: Tolerance) {
     let mut ticker_data = TickerData::new();
     ticker_data.add_csv_series(&PathBuf::from(TICKER_DATA_PATH)).expect("Failed to add CSV to TickerData");
-    let reversals = ticker_data.find_reversals(candle_range);
+    let reversals = ticker_data.detect_reversals(swing_detector);
     let declinations = PlanetEquatorCrosses::new(start_date, stop_date).await;
 
-    // iterate over lunar_declinations, identify if it is within +/- error_margin_days of a reversal
+    // iterate over lunar_declinations, identify if it is within +/- error_margin of a reversal
     // if so, increment win count
     println!("DATE\t\tDECLINATION\tDIRECTION\tPLANET");
     let mut win_count = 0;
     let total_count = declinations.equator_crosses.len();
+    let up_dates: Vec<Time> = declinations.equator_crosses.iter()
+      .filter(|cross| matches!(cross.direction, Direction::Up))
+      .map(|cross| cross.date)
+      .collect();
+    let down_dates: Vec<Time> = declinations.equator_crosses.iter()
+      .filter(|cross| matches!(cross.direction, Direction::Down))
+      .map(|cross| cross.date)
+      .collect();
     for equator_cross in declinations.equator_crosses {
       let time = equator_cross.date;
       let declination = equator_cross.declination;
@@ -83,8 +92,7 @@ impl PlanetEquatorCrosses {
 
       println!("{}\t{}°\t{:?}\t{}", time.as_string(), declination, direction, planet.to_str());
       for reversal in reversals.iter() {
-        let range_start = time.delta_date(-(error_margin_days));
-        let range_end = time.delta_date(error_margin_days);
+        let (range_start, range_end) = error_margin.window(&time);
         if reversal.candle.date.within_range(range_start, range_end) {
           debug!("{}\t{}°\t{:?}\t{}", time.as_string(), declination, direction, planet.to_str());
 
@@ -109,5 +117,27 @@ impl PlanetEquatorCrosses {
     }
     let win_rate = win_count as f64 / total_count as f64 * 100.0;
     println!("Win Rate: {}%\t\tWin Events: {}\t\tTotal Events: {}", win_rate, win_count, total_count);
+
+    // compare to random dates, each direction against the reversal type it predicts
+    if ticker_data.candles.is_empty() {
+      return;
+    }
+    let tops: Vec<Reversal> = reversals.iter()
+      .filter(|reversal| reversal.reversal_type == ReversalType::Top)
+      .cloned()
+      .collect();
+    let bottoms: Vec<Reversal> = reversals.iter()
+      .filter(|reversal| reversal.reversal_type == ReversalType::Bottom)
+      .cloned()
+      .collect();
+    let (earliest_date, latest_date) = (*ticker_data.earliest_date(), *ticker_data.latest_date());
+    let mut tests = vec![
+      Significance::new(&tops, earliest_date, latest_date, error_margin, DEFAULT_PERMUTATIONS)
+        .test("Up\tTop", &up_dates),
+      Significance::new(&bottoms, earliest_date, latest_date, error_margin, DEFAULT_PERMUTATIONS)
+        .test("Down\tBottom", &down_dates),
+    ];
+    Significance::bonferroni(&mut tests);
+    Significance::print(&tests);
   }
 }
\ No newline at end of file
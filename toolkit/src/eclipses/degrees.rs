@@ -0,0 +1,232 @@
+use log::debug;
+use ephemeris::*;
+use time_series::{Reversal, Time, Tolerance};
+use crate::*;
+
+/// Zodiac degree of an eclipse: Sun's position for a solar eclipse, Moon's for a lunar eclipse.
+#[derive(Debug, Clone)]
+pub struct EclipseDegree {
+  pub eclipse: EclipseEvent,
+  /// Geocentric ecliptic longitude in degrees at the time of greatest eclipse
+  pub degree: f32,
+}
+
+/// A later planet transit over (or aspect to) an eclipse degree.
+#[derive(Debug, Clone)]
+pub struct EclipseDegreeTransit {
+  pub eclipse_degree: EclipseDegree,
+  pub planet: Planet,
+  pub date: Time,
+  /// Angle from the eclipse degree to the planet on `date`
+  pub angle: f32,
+  pub alignment: Alignment,
+}
+
+#[derive(Debug, Clone)]
+pub struct EclipseDegrees {
+  pub degrees: Vec<EclipseDegree>,
+  /// Transits sorted by date
+  pub transits: Vec<EclipseDegreeTransit>,
+  pub start_date: Time,
+  pub end_date: Time,
+}
+
+impl EclipseDegrees {
+  /// Record the degree of each eclipse between `start_date` and `end_date`,
+  /// then find every later transit of `planets` within `orb` degrees of an `Alignment` in `harmonics`.
+  /// Positions are sampled daily, so `orb` should exceed the daily motion of the fastest planet.
+  pub async fn new(
+    eclipses: &Eclipses,
+    start_date: Time,
+    end_date: Time,
+    orb: f32,
+    planets: &[Planet],
+    harmonics: &[Alignment]
+  ) -> std::io::Result<Self> {
+    if start_date.diff_days(&end_date) < 1 {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Start date must be before end date"));
+    }
+    // one extra day to interpolate eclipses late on the last date
+    let query_end_date = end_date.delta_date(1);
+    let sun = Query::query(Origin::Geocentric, &Planet::Sun, DataType::EclipticLongitude, start_date, query_end_date).await?;
+    let moon = Query::query(Origin::Geocentric, &Planet::Moon, DataType::EclipticLongitude, start_date, query_end_date).await?;
+
+    let mut degrees = Vec::<EclipseDegree>::new();
+    for eclipse in eclipses.events.iter() {
+      if !eclipse.date.within_range(start_date, end_date) {
+        continue;
+      }
+      let daily_angles = match eclipse.kind.class() {
+        EclipseClass::Solar => &sun,
+        EclipseClass::Lunar => &moon,
+      };
+      if let Some(degree) = Self::degree_at_eclipse(daily_angles, eclipse) {
+        degrees.push(EclipseDegree {
+          eclipse: eclipse.clone(),
+          degree,
+        });
+      }
+    }
+
+    let mut transits = Vec::<EclipseDegreeTransit>::new();
+    for planet in planets.iter() {
+      let daily_angles = Query::query(Origin::Geocentric, planet, DataType::EclipticLongitude, start_date, end_date).await?;
+      for eclipse_degree in degrees.iter() {
+        let mut hits = Vec::<(Time, f32, Alignment)>::new();
+        for (date, angle) in daily_angles.iter() {
+          // only transits after the eclipse can activate its degree
+          if date <= &eclipse_degree.eclipse.date {
+            continue;
+          }
+          if let Some(alignment) = Alignment::find_alignment(*angle, eclipse_degree.degree, orb) {
+            if harmonics.contains(&alignment) {
+              hits.push((*date, Alignment::normalize(angle - eclipse_degree.degree), alignment));
+            }
+          }
+        }
+        for (date, angle, alignment) in Self::closest_in_each_pass(hits) {
+          debug!(
+            "{}\t{}\t{}\t{}",
+            eclipse_degree.eclipse.date.as_string(), planet.to_str(), date.as_string(), alignment.to_str()
+          );
+          transits.push(EclipseDegreeTransit {
+            eclipse_degree: eclipse_degree.clone(),
+            planet: planet.clone(),
+            date,
+            angle,
+            alignment,
+          });
+        }
+      }
+    }
+    transits.sort_by(|a, b| a.date.partial_cmp(&b.date).unwrap());
+
+    Ok(Self {
+      degrees,
+      transits,
+      start_date,
+      end_date
+    })
+  }
+
+  /// Interpolate daily positions to the time of greatest eclipse.
+  fn degree_at_eclipse(daily_angles: &[(Time, f32)], eclipse: &EclipseEvent) -> Option<f32> {
+    let index = daily_angles.iter().position(|(date, _)| date == &eclipse.date)?;
+    let (_, angle) = daily_angles[index];
+    let (_, mut next_angle) = match daily_angles.get(index + 1) {
+      Some(next) => *next,
+      None => return Some(angle),
+    };
+    // position passed through 360
+    if next_angle < angle - 180.0 {
+      next_angle += 360.0;
+    }
+    let seconds_into_day = (eclipse.datetime() - eclipse.date.to_datetime()).num_seconds() as f32;
+    let fraction = seconds_into_day / 86_400.0;
+    Some((angle + (next_angle - angle) * fraction) % 360.0)
+  }
+
+  /// Consecutive days within orb of the same alignment are one pass of the planet.
+  /// Keep the day of each pass closest to the exact alignment.
+  /// A retrograde planet can make several passes over the same degree.
+  fn closest_in_each_pass(hits: Vec<(Time, f32, Alignment)>) -> Vec<(Time, f32, Alignment)> {
+    let mut passes = Vec::<(Time, f32, Alignment)>::new();
+    let mut previous_date: Option<Time> = None;
+    for hit in hits.into_iter() {
+      let orb_error = Self::orb_error(&hit);
+      let same_pass = match (previous_date, passes.last()) {
+        (Some(date), Some(last)) => date.diff_days(&hit.0) == 1 && last.2 == hit.2,
+        _ => false
+      };
+      previous_date = Some(hit.0);
+      if same_pass {
+        let last = passes.last_mut().unwrap();
+        if orb_error < Self::orb_error(last) {
+          *last = hit;
+        }
+      } else {
+        passes.push(hit);
+      }
+    }
+    passes
+  }
+
+  fn orb_error(hit: &(Time, f32, Alignment)) -> f32 {
+    let diff = (hit.1 - hit.2.to_num()).abs();
+    diff.min(360.0 - diff)
+  }
+
+  /// Date the eclipse degree stops being sensitive: the first later conjunction by any tracked planet.
+  /// `None` if no planet has crossed the degree yet.
+  pub fn sensitive_until(&self, eclipse_degree: &EclipseDegree) -> Option<Time> {
+    self.transits.iter()
+      .find(|transit| {
+        transit.alignment == Alignment::Conjunct
+          && transit.eclipse_degree.eclipse.datetime() == eclipse_degree.eclipse.datetime()
+      })
+      .map(|transit| transit.date)
+  }
+
+  /// Transits of a degree while it is still sensitive, up to and including the transit that crosses it.
+  pub fn sensitive_transits(&self) -> Vec<EclipseDegreeTransit> {
+    self.transits.iter()
+      .filter(|transit| match self.sensitive_until(&transit.eclipse_degree) {
+        Some(until) => transit.date <= until,
+        None => true
+      })
+      .cloned()
+      .collect()
+  }
+
+  /// Backtest transits against price reversals (from `TickerData::detect_reversals`).
+  /// A "win" is a reversal within `error_margin` of the transit.
+  /// Returns one `Backtest` per planet, in the order of `planets`.
+  pub fn backtest(
+    &self,
+    reversals: &[Reversal],
+    error_margin: Tolerance,
+    planets: &[Planet]
+  ) -> Vec<(Planet, Backtest)> {
+    let mut results: Vec<(Planet, Backtest)> = planets.iter()
+      .map(|planet| (planet.clone(), Backtest::default()))
+      .collect();
+    for transit in self.transits.iter() {
+      let backtest = match results.iter_mut().find(|(planet, _)| planet == &transit.planet) {
+        Some((_, backtest)) => backtest,
+        None => continue,
+      };
+      backtest.increment_total_count();
+      let (range_start, range_end) = error_margin.window(&transit.date);
+      if let Some(reversal) = reversals.iter().find(|reversal| reversal.candle.date.within_range(range_start, range_end)) {
+        backtest.increment_win_count();
+        backtest.add_signal((transit.planet.clone(), transit.angle, reversal.candle.clone()));
+      }
+    }
+
+    println!("PLANET\tWIN RATE\tWIN EVENTS\tTOTAL EVENTS");
+    for (planet, backtest) in results.iter() {
+      println!(
+        "{}\t{}%\t\t{}\t\t{}",
+        planet.to_str(),
+        (backtest.get_win_rate() * 100.0).round(),
+        backtest.get_win_count(),
+        backtest.get_total_count()
+      );
+    }
+    results
+  }
+
+  pub fn print(&self) {
+    println!("ECLIPSE\t\tDEGREE\tPLANET\tTRANSIT\t\tALIGNMENT");
+    for transit in self.transits.iter() {
+      println!(
+        "{}\t{:.2}\t{}\t{}\t{}",
+        transit.eclipse_degree.eclipse.date.as_string(),
+        transit.eclipse_degree.degree,
+        transit.planet.to_str(),
+        transit.date.as_string(),
+        transit.alignment.to_str()
+      );
+    }
+  }
+}
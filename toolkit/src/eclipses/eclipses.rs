@@ -1,10 +1,11 @@
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
+use chrono::Duration;
 use log::debug;
 use ephemeris::*;
 use crate::*;
-use time_series::Time;
+use time_series::{Reversal, SwingDetector, TickerData, Time, Tolerance};
 
 #[derive(Debug, Clone)]
 pub struct Eclipses {
@@ -15,6 +16,8 @@ impl Eclipses {
   pub fn new(solar_eclipse_csv: &PathBuf, lunar_eclipse_csv: &PathBuf) -> Self {
     //
     // read Solar Eclipse CSV and load into Vec<EclipseEvent>
+    // columns: catalog number, date, time, delta T, lunation, saros, type, gamma, magnitude,
+    // latitude, longitude, sun altitude, sun azimuth, path width, central duration
     let solar_buffer = File::open(solar_eclipse_csv).unwrap();
     let mut solar = csv::Reader::from_reader(solar_buffer);
     let mut solar_events = Vec::<EclipseEvent>::new();
@@ -23,10 +26,23 @@ impl Eclipses {
       let date = Time::from_eclipse_date_format(date_unformatted);
       let eclipse_type = &record[6];
       let eclipse_phase = EclipseType::from_symbol(eclipse_type, EclipseClass::Solar);
-      solar_events.push(EclipseEvent::new(date, eclipse_phase));
+      let mut event = EclipseEvent::new(date, eclipse_phase);
+      event.time = Time::from_eclipse_datetime_format(date_unformatted, &record[2]).ok();
+      event.delta_t = record[3].parse::<i64>().ok();
+      event.lunation = record[4].parse::<i32>().ok();
+      event.saros = record[5].parse::<i32>().ok();
+      event.gamma = record[7].parse::<f64>().ok();
+      event.magnitude = record[8].parse::<f64>().ok();
+      event.latitude = Self::parse_coordinate(&record[9], 'N', 'S');
+      event.longitude = Self::parse_coordinate(&record[10], 'E', 'W');
+      event.duration = Self::parse_central_duration(&record[14]);
+      solar_events.push(event);
     }
     //
     // read Lunar Eclipse CSV and load into Vec<EclipseEvent>
+    // columns: catalog number, date, time, delta T, lunation, saros, type, quincena solar eclipse,
+    // gamma, penumbral magnitude, umbral magnitude, latitude, longitude,
+    // penumbral duration, partial duration, total duration
     let lunar_buffer = File::open(lunar_eclipse_csv).unwrap();
     let mut lunar = csv::Reader::from_reader(lunar_buffer);
     let mut lunar_events = Vec::<EclipseEvent>::new();
@@ -35,17 +51,185 @@ impl Eclipses {
       let date = Time::from_eclipse_date_format(date_unformatted);
       let eclipse_type = &record[6];
       let eclipse_phase = EclipseType::from_symbol(eclipse_type, EclipseClass::Lunar);
-      lunar_events.push(EclipseEvent::new(date, eclipse_phase));
+      let mut event = EclipseEvent::new(date, eclipse_phase);
+      event.time = Time::from_eclipse_datetime_format(date_unformatted, &record[2]).ok();
+      event.delta_t = record[3].parse::<i64>().ok();
+      event.lunation = record[4].parse::<i32>().ok();
+      event.saros = record[5].parse::<i32>().ok();
+      event.gamma = record[8].parse::<f64>().ok();
+      event.penumbral_magnitude = record[9].parse::<f64>().ok();
+      event.magnitude = record[10].parse::<f64>().ok();
+      event.latitude = Self::parse_coordinate(&record[11], 'N', 'S');
+      event.longitude = Self::parse_coordinate(&record[12], 'E', 'W');
+      // deepest phase is the last duration present: total, then partial, then penumbral
+      event.duration = [&record[15], &record[14], &record[13]].iter()
+        .find_map(|minutes| minutes.parse::<f64>().ok())
+        .map(|minutes| Duration::seconds((minutes * 60.0).round() as i64));
+      lunar_events.push(event);
     }
     //
     // concatenate solar and lunar events into one Vec<(Time, EclipseType)>
     let mut events = [solar_events, lunar_events].concat();
     // sort events by Time
-    events.sort_by(|eclipse_1, eclipse_2| eclipse_1.date.partial_cmp(&eclipse_2.date).unwrap());
+    events.sort_by_key(|eclipse| eclipse.datetime());
 
     Self { events }
   }
 
+  /// Parse catalog latitude or longitude, such as "6.0N" or "33.3W".
+  /// `positive` and `negative` are the hemisphere suffixes.
+  fn parse_coordinate(value: &str, positive: char, negative: char) -> Option<f64> {
+    let hemisphere = value.chars().last()?;
+    let degrees = value[..value.len() - 1].parse::<f64>().ok()?;
+    if hemisphere == positive {
+      Some(degrees)
+    } else if hemisphere == negative {
+      Some(-degrees)
+    } else {
+      None
+    }
+  }
+
+  /// Parse solar catalog central duration, such as "06m37s". Partial eclipses have none.
+  fn parse_central_duration(value: &str) -> Option<Duration> {
+    let minutes_end = value.find('m')?;
+    let seconds_end = value.find('s')?;
+    let minutes = value[..minutes_end].parse::<i64>().ok()?;
+    let seconds = value[minutes_end + 1..seconds_end].parse::<i64>().ok()?;
+    Some(Duration::seconds(minutes * 60 + seconds))
+  }
+
+  /// Compute eclipses between `start_date` and `end_date` without the NASA catalogs.
+  pub fn from_ephemeris(start_date: Time, end_date: Time) -> std::io::Result<Self> {
+    let finder = EclipseFinder::new(start_date, end_date)?;
+    Ok(Self { events: finder.find() })
+  }
+
+  /// Validate computed eclipses (`self`) against a catalog loaded with `Eclipses::new`.
+  /// Events of the same class (solar/lunar) within `error_margin_days` are considered the same eclipse.
+  /// Only catalog events within the period of `self` are compared.
+  pub fn cross_check(&self, catalog: &Eclipses, error_margin_days: i64) -> EclipseCrossCheck {
+    let mut cross_check = EclipseCrossCheck::default();
+    let (start_date, end_date) = match (self.events.first(), self.events.last()) {
+      (Some(first), Some(last)) => (first.date, last.date),
+      _ => return cross_check,
+    };
+    let catalog_events: Vec<&EclipseEvent> = catalog.events.iter()
+      .filter(|event| event.date.within_range(start_date, end_date))
+      .collect();
+
+    let mut matched_catalog = vec![false; catalog_events.len()];
+    for event in self.events.iter() {
+      let range_start = event.date.delta_date(-error_margin_days);
+      let range_end = event.date.delta_date(error_margin_days);
+      let found = catalog_events.iter().enumerate().find(|(index, catalog_event)| {
+        !matched_catalog[*index]
+          && catalog_event.kind.class() == event.kind.class()
+          && catalog_event.date.within_range(range_start, range_end)
+      });
+      match found {
+        Some((index, catalog_event)) => {
+          matched_catalog[index] = true;
+          if catalog_event.kind != event.kind {
+            debug!(
+              "{}\tcomputed {:?}\tcatalog {:?}",
+              event.date.as_string(), event.kind, catalog_event.kind
+            );
+            cross_check.kind_mismatch_count += 1;
+          }
+          cross_check.matched.push((event.clone(), (*catalog_event).clone()));
+        },
+        None => cross_check.extra.push(event.clone()),
+      }
+    }
+    for (index, catalog_event) in catalog_events.into_iter().enumerate() {
+      if !matched_catalog[index] {
+        cross_check.missed.push(catalog_event.clone());
+      }
+    }
+    cross_check
+  }
+
+  /// Group eclipses by Saros series.
+  pub fn saros_series(&self) -> Vec<SarosSeries> {
+    SarosSeries::group(&self.events)
+  }
+
+  /// Group eclipses by eclipse season.
+  pub fn seasons(&self) -> Vec<EclipseSeason> {
+    EclipseSeason::group(&self.events)
+  }
+
+  /// Previous eclipse in the same Saros series, one Saros (18 years 11 days) before `event`.
+  pub fn previous_in_saros(&self, event: &EclipseEvent) -> Option<&EclipseEvent> {
+    self.events.iter()
+      .filter(|other| other.same_saros(event) && other.datetime() < event.datetime())
+      .max_by_key(|other| other.datetime())
+  }
+
+  /// Next eclipse in the same Saros series, one Saros (18 years 11 days) after `event`.
+  pub fn next_in_saros(&self, event: &EclipseEvent) -> Option<&EclipseEvent> {
+    self.events.iter()
+      .filter(|other| other.same_saros(event) && other.datetime() > event.datetime())
+      .min_by_key(|other| other.datetime())
+  }
+
+  /// Test whether markets echo behaviour one Saros apart.
+  /// For each eclipse whose previous Saros member is also within the price history,
+  /// find the reversal within `error_margin` of each eclipse.
+  /// An echo is when both eclipses land on a reversal of the same type.
+  pub fn test_saros_echo(&self, ticker_data: &TickerData, swing_detector: &SwingDetector, error_margin: Tolerance) -> Vec<SarosEcho> {
+    let mut echoes = Vec::<SarosEcho>::new();
+    if ticker_data.candles.is_empty() {
+      return echoes;
+    }
+    let reversals = ticker_data.detect_reversals(swing_detector);
+    let nearest_reversal = |date: &Time| -> Option<Reversal> {
+      let (range_start, range_end) = error_margin.window(date);
+      reversals.iter()
+        .filter(|reversal| reversal.candle.date.within_range(range_start, range_end))
+        .min_by_key(|reversal| date.diff_days(&reversal.candle.date).abs())
+        .cloned()
+    };
+
+    for eclipse in self.events.iter() {
+      if !eclipse.date.within_range(*ticker_data.earliest_date(), *ticker_data.latest_date()) {
+        continue;
+      }
+      let previous = match self.previous_in_saros(eclipse) {
+        Some(previous) if &previous.date >= ticker_data.earliest_date() => previous,
+        _ => continue,
+      };
+      echoes.push(SarosEcho {
+        eclipse: eclipse.clone(),
+        previous: previous.clone(),
+        reversal: nearest_reversal(&eclipse.date),
+        previous_reversal: nearest_reversal(&previous.date),
+      });
+    }
+
+    println!("DATE\t\tPREVIOUS\tSAROS\tREVERSAL\tPREVIOUS REVERSAL");
+    for echo in echoes.iter() {
+      println!(
+        "{}\t{}\t{}\t{}\t\t{}",
+        echo.eclipse.date.as_string(),
+        echo.previous.date.as_string(),
+        echo.eclipse.saros.unwrap_or_default(),
+        echo.reversal.as_ref().map_or("-".to_string(), |r| r.reversal_type.as_string()),
+        echo.previous_reversal.as_ref().map_or("-".to_string(), |r| r.reversal_type.as_string()),
+      );
+    }
+    let echo_count = echoes.iter().filter(|echo| echo.is_echo()).count();
+    let both_count = echoes.iter()
+      .filter(|echo| echo.reversal.is_some() && echo.previous_reversal.is_some())
+      .count();
+    println!(
+      "Echo Events: {}\t\tBoth Reversal Events: {}\t\tTotal Events: {}",
+      echo_count, both_count, echoes.len()
+    );
+    echoes
+  }
+
   pub fn print(&self, file: &PathBuf, start_date: &Time, end_date: &Time) {
     let mut file = File::create(file).unwrap();
     println!("DATE\tRANK");
@@ -335,4 +519,42 @@ impl Eclipses {
     Ok(signals)
   }
 
+  /// Compare eclipse win rates from `test_confluence` to random dates.
+  /// Hypotheses are all eclipses, eclipses with confluent signals, and each eclipse rank,
+  /// Bonferroni corrected.
+  pub fn test_confluence_significance(
+    signals: &[EclipseSignals],
+    reversals: &[Reversal],
+    start_time: Time,
+    end_time: Time,
+    error_margin: Tolerance
+  ) -> Vec<SignificanceTest> {
+    let significance = Significance::new(reversals, start_time, end_time, error_margin, DEFAULT_PERMUTATIONS);
+    let is_confluent = |signal: &EclipseSignals| {
+      signal.retrograde_signals.is_some()
+        || signal.planet_pair_alignment_signals.is_some()
+        || signal.self_alignment_signals.is_some()
+        || signal.equator_cross_signals.is_some()
+    };
+    let all_dates: Vec<Time> = signals.iter().map(|signal| signal.eclipse.date).collect();
+    let confluent_dates: Vec<Time> = signals.iter()
+      .filter(|signal| is_confluent(signal))
+      .map(|signal| signal.eclipse.date)
+      .collect();
+    let mut tests = vec![
+      significance.test("All eclipses", &all_dates),
+      significance.test("Confluent eclipses", &confluent_dates),
+    ];
+    for rank in 1..=4 {
+      let rank_dates: Vec<Time> = signals.iter()
+        .filter(|signal| signal.eclipse.kind.to_rank() == rank)
+        .map(|signal| signal.eclipse.date)
+        .collect();
+      tests.push(significance.test(&format!("Rank {}", rank), &rank_dates));
+    }
+    Significance::bonferroni(&mut tests);
+    Significance::print(&tests);
+    tests
+  }
+
 }
\ No newline at end of file
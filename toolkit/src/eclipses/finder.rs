@@ -0,0 +1,318 @@
+use chrono::{DateTime, TimeZone, Utc};
+use log::debug;
+use time_series::Time;
+use crate::*;
+
+/// Julian Ephemeris Day of the first New Moon of 2000 (k = 0).
+const LUNATION_EPOCH_JDE: f64 = 2451550.09766;
+/// Mean length of a synodic month in days.
+const SYNODIC_MONTH: f64 = 29.530588861;
+/// Julian Day of the UNIX epoch (1970-01-01 00:00).
+const UNIX_EPOCH_JD: f64 = 2440587.5;
+/// No eclipse is possible if the Moon's argument of latitude is further than this from a node.
+const MAX_SIN_ARGUMENT_OF_LATITUDE: f64 = 0.36;
+/// Lunations in one Saros (18 years 11 days): eclipses one Saros apart share a series number.
+pub const SAROS_LUNATIONS: i32 = 223;
+/// Lunations in one Inex (29 years): eclipses one Inex apart are in consecutive series.
+pub const INEX_LUNATIONS: i32 = 358;
+/// Multiplicative inverse of `INEX_LUNATIONS` modulo `SAROS_LUNATIONS`.
+const INEX_INVERSE: i32 = 38;
+
+/// Result of comparing computed eclipses to the NASA catalog.
+#[derive(Debug, Clone, Default)]
+pub struct EclipseCrossCheck {
+  /// Computed eclipse paired with the catalog eclipse it was matched to
+  pub matched: Vec<(EclipseEvent, EclipseEvent)>,
+  /// Catalog eclipses that were not computed
+  pub missed: Vec<EclipseEvent>,
+  /// Computed eclipses that are not in the catalog
+  pub extra: Vec<EclipseEvent>,
+  /// Matched eclipses classified as a different `EclipseType` than the catalog
+  pub kind_mismatch_count: usize,
+}
+
+impl EclipseCrossCheck {
+  /// Mean absolute difference in minutes between computed and catalog time of greatest eclipse.
+  /// `None` if no matched pair has both times.
+  pub fn mean_time_error_minutes(&self) -> Option<f64> {
+    let errors: Vec<f64> = self.matched.iter()
+      .filter_map(|(computed, catalog)| match (computed.time, catalog.time) {
+        (Some(a), Some(b)) => Some((a - b).num_seconds().abs() as f64 / 60.0),
+        _ => None
+      })
+      .collect();
+    if errors.is_empty() {
+      None
+    } else {
+      Some(errors.iter().sum::<f64>() / errors.len() as f64)
+    }
+  }
+
+  /// Mean absolute difference between computed and catalog magnitude.
+  pub fn mean_magnitude_error(&self) -> Option<f64> {
+    let errors: Vec<f64> = self.matched.iter()
+      .filter_map(|(computed, catalog)| match (computed.magnitude, catalog.magnitude) {
+        (Some(a), Some(b)) => Some((a - b).abs()),
+        _ => None
+      })
+      .collect();
+    if errors.is_empty() {
+      None
+    } else {
+      Some(errors.iter().sum::<f64>() / errors.len() as f64)
+    }
+  }
+
+  pub fn print(&self) {
+    println!("MATCHED\tMISSED\tEXTRA\tKIND MISMATCH");
+    println!(
+      "{}\t{}\t{}\t{}",
+      self.matched.len(), self.missed.len(), self.extra.len(), self.kind_mismatch_count
+    );
+    if let Some(minutes) = self.mean_time_error_minutes() {
+      println!("Mean time error: {:.1} minutes", minutes);
+    }
+    if let Some(magnitude) = self.mean_magnitude_error() {
+      println!("Mean magnitude error: {:.4}", magnitude);
+    }
+    for event in self.missed.iter() {
+      println!("Missed\t{}\t{:?}", event.date.as_string(), event.kind);
+    }
+    for event in self.extra.iter() {
+      println!("Extra\t{}\t{:?}", event.date.as_string(), event.kind);
+    }
+  }
+}
+
+/// Find solar and lunar eclipses by scanning each New and Full Moon
+/// and testing the Moon's distance from its node.
+///
+/// [Algorithm](https://archive.org/details/astronomicalalgorithmsjeanmeeus1991) is
+/// chapter 54 of Meeus' "Astronomical Algorithms". Times are Terrestrial Dynamical Time,
+/// the same time scale as the NASA eclipse catalogs.
+#[derive(Debug, Clone)]
+pub struct EclipseFinder {
+  pub start_date: Time,
+  pub end_date: Time,
+}
+
+impl EclipseFinder {
+  pub fn new(start_date: Time, end_date: Time) -> std::io::Result<Self> {
+    if start_date > end_date {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Start date must be before end date"));
+    }
+    Ok(Self {
+      start_date,
+      end_date
+    })
+  }
+
+  /// Iterate every New Moon (solar eclipse candidate) and Full Moon (lunar eclipse candidate)
+  /// between `start_date` and `end_date`, sorted by time of greatest eclipse.
+  pub fn find(&self) -> Vec<EclipseEvent> {
+    let start_k = ((Self::julian_day(&self.start_date) - LUNATION_EPOCH_JDE) / SYNODIC_MONTH).floor() - 1.0;
+    let end_k = ((Self::julian_day(&self.end_date) - LUNATION_EPOCH_JDE) / SYNODIC_MONTH).ceil() + 1.0;
+
+    let mut events = Vec::<EclipseEvent>::new();
+    let mut k = start_k;
+    while k <= end_k {
+      for (lunation, class) in [(k, EclipseClass::Solar), (k + 0.5, EclipseClass::Lunar)] {
+        if let Some(event) = Self::eclipse_at_lunation(lunation, class) {
+          if event.date.within_range(self.start_date, self.end_date) {
+            debug!(
+              "{}\t{:?}\tgamma: {:?}\tmagnitude: {:?}",
+              event.date.as_string(), event.kind, event.gamma, event.magnitude
+            );
+            events.push(event);
+          }
+        }
+      }
+      k += 1.0;
+    }
+    events.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    events
+  }
+
+  /// Test a single lunation for an eclipse.
+  /// `k` is an integer for a New Moon and an integer + 0.5 for a Full Moon.
+  fn eclipse_at_lunation(k: f64, class: EclipseClass) -> Option<EclipseEvent> {
+    let t = k / 1236.85;
+    let t2 = t * t;
+    let t3 = t2 * t;
+    let t4 = t3 * t;
+
+    // Moon's argument of latitude (distance from ascending node)
+    let f = Self::degrees(160.7108 + 390.67050284 * k - 0.0016118 * t2 - 0.00000227 * t3 + 0.000000011 * t4);
+    if f.sin().abs() > MAX_SIN_ARGUMENT_OF_LATITUDE {
+      return None;
+    }
+    let jde = LUNATION_EPOCH_JDE + SYNODIC_MONTH * k + 0.00015437 * t2 - 0.000000150 * t3 + 0.00000000073 * t4;
+    // Sun's mean anomaly
+    let m = Self::degrees(2.5534 + 29.10535670 * k - 0.0000014 * t2 - 0.00000011 * t3);
+    // Moon's mean anomaly
+    let mp = Self::degrees(201.5643 + 385.81693528 * k + 0.0107582 * t2 + 0.00001238 * t3 - 0.000000058 * t4);
+    // longitude of the Moon's ascending node
+    let omega = Self::degrees(124.7746 - 1.56375588 * k + 0.0020672 * t2 + 0.00000215 * t3);
+    // eccentricity of Earth's orbit
+    let e = 1.0 - 0.002516 * t - 0.0000074 * t2;
+    let f1 = f - 0.02665_f64.to_radians() * omega.sin();
+    let a1 = Self::degrees(299.77 + 0.107408 * k - 0.009173 * t2);
+
+    let mut correction = match class {
+      EclipseClass::Solar => -0.4075 * mp.sin() + 0.1721 * e * m.sin(),
+      EclipseClass::Lunar => -0.4065 * mp.sin() + 0.1727 * e * m.sin(),
+    };
+    correction += 0.0161 * (2.0 * mp).sin()
+      - 0.0097 * (2.0 * f1).sin()
+      + 0.0073 * e * (mp - m).sin()
+      - 0.0050 * e * (mp + m).sin()
+      - 0.0023 * (mp - 2.0 * f1).sin()
+      + 0.0021 * e * (2.0 * m).sin()
+      + 0.0012 * (mp + 2.0 * f1).sin()
+      + 0.0006 * e * (2.0 * mp + m).sin()
+      - 0.0004 * (3.0 * mp).sin()
+      - 0.0003 * e * (m + 2.0 * f1).sin()
+      + 0.0003 * a1.sin()
+      - 0.0002 * e * (m - 2.0 * f1).sin()
+      - 0.0002 * e * (2.0 * mp - m).sin()
+      - 0.0002 * omega.sin();
+    let jde = jde + correction;
+
+    let p = 0.2070 * e * m.sin()
+      + 0.0024 * e * (2.0 * m).sin()
+      - 0.0392 * mp.sin()
+      + 0.0116 * (2.0 * mp).sin()
+      - 0.0073 * e * (mp + m).sin()
+      + 0.0067 * e * (mp - m).sin()
+      + 0.0118 * (2.0 * f1).sin();
+    let q = 5.2207
+      - 0.0048 * e * m.cos()
+      + 0.0020 * e * (2.0 * m).cos()
+      - 0.3299 * mp.cos()
+      - 0.0060 * e * (mp + m).cos()
+      + 0.0041 * e * (mp - m).cos();
+    let w = f1.cos().abs();
+    // least distance from the shadow axis to the center of the Earth, in Earth radii
+    let gamma = (p * f1.cos() + q * f1.sin()) * (1.0 - 0.0048 * w);
+    // radius of the Moon's umbral cone in the fundamental plane, in Earth radii
+    let u = 0.0059
+      + 0.0046 * e * m.cos()
+      - 0.0182 * mp.cos()
+      + 0.0004 * (2.0 * mp).cos()
+      - 0.0005 * (m + mp).cos();
+
+    let time = Self::to_datetime(jde);
+    let (kind, magnitude, penumbral_magnitude) = match class {
+      EclipseClass::Solar => {
+        let (kind, magnitude) = Self::classify_solar(gamma, u)?;
+        (kind, magnitude, None)
+      },
+      EclipseClass::Lunar => {
+        let (kind, umbral_magnitude, penumbral_magnitude) = Self::classify_lunar(gamma, u)?;
+        (kind, umbral_magnitude, Some(penumbral_magnitude))
+      }
+    };
+    let lunation = k.floor() as i32;
+    let mut event = EclipseEvent::new(Time::from_datetime(time), kind);
+    event.time = Some(time);
+    event.magnitude = Some(magnitude);
+    event.penumbral_magnitude = penumbral_magnitude;
+    event.gamma = Some(gamma);
+    event.lunation = Some(lunation);
+    event.saros = Some(Self::saros(lunation, class));
+    Some(event)
+  }
+
+  /// Saros series of an eclipse from its lunation number.
+  /// Any lunation is `a * SAROS_LUNATIONS + b * INEX_LUNATIONS` from a reference eclipse,
+  /// and each Inex advances the series number by one.
+  pub fn saros(lunation: i32, class: EclipseClass) -> i32 {
+    // reference eclipses: 2024-04-08 total solar, 2024-03-25 penumbral lunar
+    let (reference_lunation, reference_saros) = match class {
+      EclipseClass::Solar => (300, 139),
+      EclipseClass::Lunar => (299, 113),
+    };
+    let delta = lunation - reference_lunation;
+    let inex = (INEX_INVERSE * delta).rem_euclid(SAROS_LUNATIONS);
+    // choose the Inex count closest to the series active at this time
+    let center = delta as f64 / INEX_LUNATIONS as f64;
+    let cycles = ((center - inex as f64) / SAROS_LUNATIONS as f64).round() as i32;
+    reference_saros + inex + cycles * SAROS_LUNATIONS
+  }
+
+  /// Classify a solar eclipse from `gamma` and the umbral radius `u`.
+  /// Return the eclipse type and magnitude, or `None` if the penumbra misses the Earth.
+  fn classify_solar(gamma: f64, u: f64) -> Option<(EclipseType, f64)> {
+    let abs_gamma = gamma.abs();
+    if abs_gamma > 1.5433 + u {
+      return None;
+    }
+    // axis of the shadow cone touches the Earth: central eclipse
+    if abs_gamma < 0.9972 {
+      // shadow radii at the Earth's surface, which is closer to the Moon than the fundamental plane
+      let depth = (1.0 - gamma * gamma).sqrt();
+      let umbra = u - 0.00464 * depth;
+      let penumbra = 0.5461 + u + 0.00466 * depth;
+      // ratio of apparent lunar to solar diameter
+      let magnitude = (penumbra - umbra) / (penumbra + umbra);
+      let kind = if u < 0.0 {
+        EclipseType::TotalSolar
+      } else if u > 0.0047 {
+        EclipseType::AnnularSolar
+      } else if umbra < 0.0 {
+        EclipseType::HybridSolar
+      } else {
+        EclipseType::AnnularSolar
+      };
+      Some((kind, magnitude))
+    }
+    // shadow cone grazes the Earth, but its axis misses: non-central total or annular eclipse
+    else if abs_gamma < 0.9972 + u.abs() {
+      let magnitude = 0.5461 / (0.5461 + 2.0 * u);
+      let kind = if u < 0.0 {
+        EclipseType::TotalSolar
+      } else {
+        EclipseType::AnnularSolar
+      };
+      Some((kind, magnitude))
+    } else {
+      let magnitude = (1.5433 + u - abs_gamma) / (0.5461 + 2.0 * u);
+      Some((EclipseType::PartialSolar, magnitude))
+    }
+  }
+
+  /// Classify a lunar eclipse from `gamma` and the umbral radius `u`.
+  /// Return the eclipse type, umbral magnitude and penumbral magnitude,
+  /// or `None` if the Moon misses the Earth's penumbra.
+  fn classify_lunar(gamma: f64, u: f64) -> Option<(EclipseType, f64, f64)> {
+    let abs_gamma = gamma.abs();
+    let penumbral_magnitude = (1.5573 + u - abs_gamma) / 0.5450;
+    if penumbral_magnitude < 0.0 {
+      return None;
+    }
+    let umbral_magnitude = (1.0128 - u - abs_gamma) / 0.5450;
+    let kind = if umbral_magnitude < 0.0 {
+      EclipseType::PenumbralLunar
+    } else if umbral_magnitude < 1.0 {
+      EclipseType::PartialLunar
+    } else {
+      EclipseType::TotalLunar
+    };
+    Some((kind, umbral_magnitude, penumbral_magnitude))
+  }
+
+  /// Normalize degrees to [0, 360) and convert to radians
+  fn degrees(angle: f64) -> f64 {
+    angle.rem_euclid(360.0).to_radians()
+  }
+
+  /// Julian Day at 00:00 of `date`
+  fn julian_day(date: &Time) -> f64 {
+    date.to_unix() as f64 / 86_400.0 + UNIX_EPOCH_JD
+  }
+
+  fn to_datetime(julian_day: f64) -> DateTime<Utc> {
+    let unix = ((julian_day - UNIX_EPOCH_JD) * 86_400.0).round() as i64;
+    Utc.timestamp_opt(unix, 0).unwrap()
+  }
+}
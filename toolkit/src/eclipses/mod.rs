@@ -1,5 +1,11 @@
 pub mod eclipses;
 pub mod signals;
+pub mod finder;
+pub mod saros;
+pub mod degrees;
 
 pub use eclipses::*;
 pub use signals::*;
+pub use finder::*;
+pub use saros::*;
+pub use degrees::*;
\ No newline at end of file
@@ -0,0 +1,92 @@
+use time_series::{Reversal, Time};
+use crate::*;
+
+/// Days between eclipses above which they belong to different eclipse seasons.
+/// Seasons last ~34 days and recur every ~173 days.
+const ECLIPSE_SEASON_GAP_DAYS: i64 = 60;
+
+/// All eclipses of one Saros series sorted by time.
+/// Consecutive members are one Saros (223 lunations, 18 years 11 days) apart.
+#[derive(Debug, Clone)]
+pub struct SarosSeries {
+  pub class: EclipseClass,
+  pub saros: i32,
+  pub events: Vec<EclipseEvent>,
+}
+
+impl SarosSeries {
+  /// Group eclipses by class (solar/lunar) and Saros number.
+  /// Eclipses without a Saros number are skipped.
+  pub fn group(events: &[EclipseEvent]) -> Vec<SarosSeries> {
+    let mut series = Vec::<SarosSeries>::new();
+    for event in events.iter() {
+      let saros = match event.saros {
+        Some(saros) => saros,
+        None => continue,
+      };
+      let class = event.kind.class();
+      match series.iter_mut().find(|s| s.saros == saros && s.class == class) {
+        Some(existing) => existing.events.push(event.clone()),
+        None => series.push(SarosSeries {
+          class,
+          saros,
+          events: vec![event.clone()],
+        }),
+      }
+    }
+    for s in series.iter_mut() {
+      s.events.sort_by_key(|event| event.datetime());
+    }
+    series.sort_by_key(|s| s.saros);
+    series
+  }
+}
+
+/// Eclipses during one passage of the Sun through a lunar node.
+#[derive(Debug, Clone)]
+pub struct EclipseSeason {
+  pub start_date: Time,
+  pub end_date: Time,
+  pub events: Vec<EclipseEvent>,
+}
+
+impl EclipseSeason {
+  /// Split time-sorted eclipses into seasons wherever consecutive eclipses are far apart.
+  pub fn group(events: &[EclipseEvent]) -> Vec<EclipseSeason> {
+    let mut seasons = Vec::<EclipseSeason>::new();
+    for event in events.iter() {
+      match seasons.last_mut() {
+        Some(season) if season.end_date.diff_days(&event.date) <= ECLIPSE_SEASON_GAP_DAYS => {
+          season.end_date = event.date;
+          season.events.push(event.clone());
+        },
+        _ => seasons.push(EclipseSeason {
+          start_date: event.date,
+          end_date: event.date,
+          events: vec![event.clone()],
+        }),
+      }
+    }
+    seasons
+  }
+}
+
+/// An eclipse paired with the previous eclipse of its Saros series,
+/// and the price reversal (if any) near each.
+#[derive(Debug, Clone)]
+pub struct SarosEcho {
+  pub eclipse: EclipseEvent,
+  pub previous: EclipseEvent,
+  pub reversal: Option<Reversal>,
+  pub previous_reversal: Option<Reversal>,
+}
+
+impl SarosEcho {
+  /// Both eclipses landed near a reversal of the same type (top or bottom).
+  pub fn is_echo(&self) -> bool {
+    match (&self.reversal, &self.previous_reversal) {
+      (Some(a), Some(b)) => a.reversal_type == b.reversal_type,
+      _ => false
+    }
+  }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Duration, Utc};
 use ephemeris::*;
 use time_series::Time;
 use crate::*;
@@ -87,16 +88,17 @@ impl PlanetRetrogradeOnEclipse {
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum EclipseClass {
   Solar,
   Lunar,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum EclipseType {
   TotalSolar,
   AnnularSolar,
+  HybridSolar,
   PartialSolar,
   PenumbralLunar,
   PartialLunar,
@@ -154,10 +156,10 @@ impl EclipseType {
           "Tm" => EclipseType::TotalSolar,
           "Tn" => EclipseType::TotalSolar,
           "Ts" => EclipseType::TotalSolar,
-          "H" => EclipseType::TotalSolar,
-          "Hm" => EclipseType::TotalSolar,
-          "H2" => EclipseType::TotalSolar,
-          "H3" => EclipseType::TotalSolar,
+          "H" => EclipseType::HybridSolar,
+          "Hm" => EclipseType::HybridSolar,
+          "H2" => EclipseType::HybridSolar,
+          "H3" => EclipseType::HybridSolar,
           _ => panic!("Invalid solar eclipse symbol"),
         }
       },
@@ -166,7 +168,7 @@ impl EclipseType {
 
   /// ## Eclipse Importance Ranking Greatest To Least:
   ///
-  /// 1) Total Solar Eclipse (TSE)
+  /// 1) Total Solar Eclipse (TSE) & Hybrid Solar Eclipse (HSE)
   ///
   /// 2) Annular Solar Eclipse (ASE) & Penumbral Lunar Eclipse (PenLE)
   ///
@@ -176,6 +178,7 @@ impl EclipseType {
   pub fn to_rank(&self) -> u8 {
     match self {
       EclipseType::TotalSolar => 1,
+      EclipseType::HybridSolar => 1,
       EclipseType::AnnularSolar => 2,
       EclipseType::PenumbralLunar => 2,
       EclipseType::PartialSolar => 3,
@@ -183,12 +186,47 @@ impl EclipseType {
       EclipseType::TotalLunar => 4,
     }
   }
+
+  pub fn class(&self) -> EclipseClass {
+    match self {
+      EclipseType::TotalSolar
+      | EclipseType::AnnularSolar
+      | EclipseType::HybridSolar
+      | EclipseType::PartialSolar => EclipseClass::Solar,
+      EclipseType::PenumbralLunar
+      | EclipseType::PartialLunar
+      | EclipseType::TotalLunar => EclipseClass::Lunar,
+    }
+  }
 }
 
 #[derive(Debug, Clone)]
 pub struct EclipseEvent {
   pub date: Time,
   pub kind: EclipseType,
+  /// Instant of greatest eclipse in Terrestrial Dynamical Time
+  pub time: Option<DateTime<Utc>>,
+  /// Solar: fraction of the Sun's diameter covered by the Moon.
+  /// Lunar: fraction of the Moon's diameter immersed in the umbra.
+  pub magnitude: Option<f64>,
+  /// Lunar only: fraction of the Moon's diameter immersed in the penumbra.
+  pub penumbral_magnitude: Option<f64>,
+  /// Distance of the shadow axis from the center of the Earth (solar)
+  /// or of the Moon from the shadow axis (lunar), in Earth radii.
+  pub gamma: Option<f64>,
+  /// Saros series the eclipse belongs to. Solar and lunar series are numbered independently.
+  pub saros: Option<i32>,
+  /// Number of New Moons since 2000-01-06
+  pub lunation: Option<i32>,
+  /// Terrestrial Dynamical Time minus Universal Time, in seconds
+  pub delta_t: Option<i64>,
+  /// Latitude of greatest eclipse (solar) or of the zenith Moon (lunar), north positive
+  pub latitude: Option<f64>,
+  /// Longitude of greatest eclipse (solar) or of the zenith Moon (lunar), east positive
+  pub longitude: Option<f64>,
+  /// Solar: duration of the central phase at greatest eclipse.
+  /// Lunar: duration of the deepest phase (total, partial or penumbral).
+  pub duration: Option<Duration>,
 }
 
 impl EclipseEvent {
@@ -196,6 +234,29 @@ impl EclipseEvent {
     Self {
       date,
       kind,
+      time: None,
+      magnitude: None,
+      penumbral_magnitude: None,
+      gamma: None,
+      saros: None,
+      lunation: None,
+      delta_t: None,
+      latitude: None,
+      longitude: None,
+      duration: None,
+    }
+  }
+
+  /// Time of greatest eclipse if known, otherwise midnight of `date`.
+  pub fn datetime(&self) -> DateTime<Utc> {
+    self.time.unwrap_or_else(|| self.date.to_datetime())
+  }
+
+  /// True if both eclipses are members of the same Saros series.
+  pub fn same_saros(&self, other: &EclipseEvent) -> bool {
+    match (self.saros, other.saros) {
+      (Some(a), Some(b)) => a == b && self.kind.class() == other.kind.class(),
+      _ => false
     }
   }
 }
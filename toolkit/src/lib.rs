@@ -2,6 +2,9 @@ pub mod planet_matrix;
 pub mod retrograde;
 pub mod declination;
 pub mod eclipses;
+pub mod significance;
+pub mod optimizer;
+pub mod synodic_phase;
 
 use std::path::PathBuf;
 use csv::WriterBuilder;
@@ -9,6 +12,9 @@ pub use planet_matrix::*;
 pub use retrograde::*;
 pub use declination::*;
 pub use eclipses::*;
+pub use significance::*;
+pub use optimizer::*;
+pub use synodic_phase::*;
 use ephemeris::*;
 use time_series::Time;
 
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use ephemeris::*;
+use time_series::{Candle, Direction, GridSearch, Parameter, Reversal, ReversalType, SquareOfNine, SwingDetector, TickerData, Time, Tolerance};
+use crate::*;
+
+/// Combinations with fewer signals than this score 0, so a lucky handful of signals can't top the ranking.
+pub const MIN_GRID_SIGNALS: u64 = 10;
+
+/// Grid search over toolkit signal parameters: `PlanetMatrix` alignments, Square of Nine price-planet signals,
+/// retrograde stations and declination equator crosses.
+/// Planet positions are queried once in `new` and shared by every combination.
+#[derive(Debug, Clone)]
+pub struct SignalOptimizer {
+  pub start_date: Time,
+  pub end_date: Time,
+  /// Daily geocentric ecliptic longitude of each planet
+  pub planet_angles: Vec<(Planet, Vec<(Time, f32)>)>,
+}
+
+impl SignalOptimizer {
+  pub async fn new(start_date: Time, end_date: Time, planets: &[Planet]) -> std::io::Result<Self> {
+    if start_date.diff_days(&end_date) < 1 {
+      return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Start date must be before end date"));
+    }
+    let mut planet_angles = Vec::new();
+    for planet in planets.iter() {
+      let angles = Query::query(Origin::Geocentric, planet, DataType::EclipticLongitude, start_date, end_date).await?;
+      planet_angles.push((planet.clone(), angles));
+    }
+    Ok(Self {
+      start_date,
+      end_date,
+      planet_angles
+    })
+  }
+
+  /// Reversals for each swing detector, keyed by index and computed once before the grid runs.
+  fn reversals_by_detector(ticker_data: &TickerData, swing_detectors: &[SwingDetector]) -> HashMap<usize, Vec<Reversal>> {
+    swing_detectors.iter()
+      .enumerate()
+      .map(|(index, swing_detector)| (index, ticker_data.detect_reversals(swing_detector)))
+      .collect()
+  }
+
+  /// Whole day counts as grid values, so every "error_margin_days" combination is a distinct `Tolerance::Days`.
+  fn day_values(days: &[i64]) -> Vec<f64> {
+    days.iter().map(|days| *days as f64).collect()
+  }
+
+  /// Lift of signal dates over random dates (see `Significance`), or 0 with too few signals.
+  fn lift(&self, reversals: &[Reversal], error_margin: Tolerance, signal_dates: &[Time]) -> f64 {
+    let significance = Significance::new(reversals, self.start_date, self.end_date, error_margin, 0);
+    let test = significance.test("", signal_dates);
+    if test.total_count < MIN_GRID_SIGNALS {
+      return 0.0;
+    }
+    test.lift
+  }
+
+  /// Search alignment orb, error margin and reversal definition for `PlanetMatrix` alignments.
+  /// The "swing_detector" parameter is an index into `swing_detectors`.
+  /// Objective is the lift of alignment win rate over the random-date baseline.
+  pub fn optimize_planet_matrix(
+    &self,
+    ticker_data: &TickerData,
+    orbs: &[f64],
+    error_margin_days: &[i64],
+    swing_detectors: &[SwingDetector],
+    harmonics: &[Alignment]
+  ) -> GridSearch {
+    let reversals = Self::reversals_by_detector(ticker_data, swing_detectors);
+    let detector_indices: Vec<f64> = (0..swing_detectors.len()).map(|index| index as f64).collect();
+    GridSearch::run(
+      vec![
+        Parameter::list("orb", orbs),
+        Parameter::list("error_margin_days", &Self::day_values(error_margin_days)),
+        Parameter::list("swing_detector", &detector_indices),
+      ],
+      |values| {
+        let planet_matrix = PlanetMatrix::from_angles(
+          &self.planet_angles,
+          &self.start_date,
+          &self.end_date,
+          values[0] as f32,
+          harmonics
+        );
+        let dates: Vec<Time> = planet_matrix.matrix.iter()
+          .flat_map(|(_, _, alignments)| alignments.iter().map(|(date, _, _)| *date))
+          .collect();
+        self.lift(&reversals[&(values[2] as usize)], Tolerance::Days(values[1] as i64), &dates)
+      }
+    )
+  }
+
+  /// Dates a candle's close, placed on a Square of Nine that starts at `origin` and grows by `step` per cell,
+  /// is within `orb` degrees of a planet's ecliptic longitude.
+  pub fn price_planet_dates(&self, ticker_data: &TickerData, origin: u32, step: f64, orb: f32) -> Vec<Time> {
+    let candles: Vec<&Candle> = ticker_data.get_candles().iter()
+      .filter(|candle| candle.date.within_range(self.start_date, self.end_date))
+      .collect();
+    let max_close = candles.iter().fold(0.0, |max: f64, candle| max.max(candle.close));
+    let dimension = SquareOfNine::dimension_for(origin as f64, step, max_close);
+    let square_of_nine = SquareOfNine::new(origin, step, dimension);
+    let mut dates = Vec::<Time>::new();
+    for candle in candles.iter() {
+      let price_angle = match square_of_nine.angle_of_value(candle.close) {
+        Some(angle) => angle,
+        None => continue,
+      };
+      // positions are daily from `start_date`
+      let index = self.start_date.diff_days(&candle.date) as usize;
+      let aligned = self.planet_angles.iter().any(|(_, angles)| match angles.get(index) {
+        Some((date, angle)) if *date == candle.date => {
+          let diff = (price_angle - angle).abs();
+          diff.min(360.0 - diff) <= orb
+        },
+        _ => false
+      });
+      if aligned {
+        dates.push(candle.date);
+      }
+    }
+    dates
+  }
+
+  /// Every day a planet is within `margin_degrees` of one of its stations in `retrograde`, as `Retrograde::backtest` tests them.
+  pub fn station_dates(&self, retrograde: &Retrograde, margin_degrees: f32) -> Vec<Time> {
+    let mut dates = Vec::<Time>::new();
+    for event in retrograde.retrogrades.iter() {
+      let daily_angles = match retrograde.daily_angles.iter().find(|(planet, _)| planet == &event.planet) {
+        Some((_, angles)) => angles,
+        None => continue,
+      };
+      for (kind, station_date) in [
+        (RetrogradeEventKind::StationRetrograde, &event.start_date),
+        (RetrogradeEventKind::StationDirect, &event.end_date),
+      ] {
+        if let Some((_, first_date, last_date, _)) = Retrograde::station_window(daily_angles, kind, station_date, margin_degrees) {
+          dates.extend(first_date.time_period(&last_date));
+        }
+      }
+    }
+    dates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    dates.dedup();
+    dates
+  }
+
+  /// Search the station margin, error margin and reversal definition for retrograde station signals.
+  /// The "swing_detector" parameter is an index into `swing_detectors`.
+  /// Objective is the lift of station days' win rate over the random-date baseline.
+  pub fn optimize_retrograde(
+    &self,
+    ticker_data: &TickerData,
+    retrograde: &Retrograde,
+    station_margin_degrees: &[f64],
+    error_margin_days: &[i64],
+    swing_detectors: &[SwingDetector]
+  ) -> GridSearch {
+    let reversals = Self::reversals_by_detector(ticker_data, swing_detectors);
+    let detector_indices: Vec<f64> = (0..swing_detectors.len()).map(|index| index as f64).collect();
+    GridSearch::run(
+      vec![
+        Parameter::list("station_margin_degrees", station_margin_degrees),
+        Parameter::list("error_margin_days", &Self::day_values(error_margin_days)),
+        Parameter::list("swing_detector", &detector_indices),
+      ],
+      |values| {
+        let dates = self.station_dates(retrograde, values[0] as f32);
+        self.lift(&reversals[&(values[2] as usize)], Tolerance::Days(values[1] as i64), &dates)
+      }
+    )
+  }
+
+  /// Search the error margin and reversal definition for declination equator crosses.
+  /// Crosses up are tested against tops and crosses down against bottoms, as `PlanetEquatorCrosses::test_declinations` pairs them;
+  /// the objective is the mean lift of the two.
+  pub fn optimize_equator_crosses(
+    &self,
+    ticker_data: &TickerData,
+    equator_crosses: &PlanetEquatorCrosses,
+    error_margin_days: &[i64],
+    swing_detectors: &[SwingDetector]
+  ) -> GridSearch {
+    let reversals = Self::reversals_by_detector(ticker_data, swing_detectors);
+    let detector_indices: Vec<f64> = (0..swing_detectors.len()).map(|index| index as f64).collect();
+    let cross_dates = |direction: Direction| equator_crosses.equator_crosses.iter()
+      .filter(|cross| cross.direction == direction)
+      .map(|cross| cross.date)
+      .collect::<Vec<Time>>();
+    let (up_dates, down_dates) = (cross_dates(Direction::Up), cross_dates(Direction::Down));
+    GridSearch::run(
+      vec![
+        Parameter::list("error_margin_days", &Self::day_values(error_margin_days)),
+        Parameter::list("swing_detector", &detector_indices),
+      ],
+      |values| {
+        let error_margin = Tolerance::Days(values[0] as i64);
+        let of_type = |reversal_type: ReversalType| reversals[&(values[1] as usize)].iter()
+          .filter(|reversal| reversal.reversal_type == reversal_type)
+          .cloned()
+          .collect::<Vec<Reversal>>();
+        (self.lift(&of_type(ReversalType::Top), error_margin, &up_dates)
+          + self.lift(&of_type(ReversalType::Bottom), error_margin, &down_dates)) / 2.0
+      }
+    )
+  }
+
+  /// Search the Square of Nine scale (price per cell) and orb for price-planet signals.
+  /// Objective is the lift of signal win rate over the random-date baseline.
+  pub fn optimize_square_of_nine(
+    &self,
+    ticker_data: &TickerData,
+    steps: &[f64],
+    orbs: &[f64],
+    error_margin: Tolerance,
+    swing_detector: &SwingDetector
+  ) -> GridSearch {
+    let reversals = ticker_data.detect_reversals(swing_detector);
+    GridSearch::run(
+      vec![
+        Parameter::list("step", steps),
+        Parameter::list("orb", orbs),
+      ],
+      |values| {
+        let dates = self.price_planet_dates(ticker_data, 1, values[0], values[1] as f32);
+        self.lift(&reversals, error_margin, &dates)
+      }
+    )
+  }
+}
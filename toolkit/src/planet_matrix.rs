@@ -3,6 +3,7 @@ use std::io::Write;
 use std::path::PathBuf;
 use ephemeris::*;
 use time_series::*;
+use crate::{Significance, SignificanceTest, DEFAULT_PERMUTATIONS};
 
 pub type Matrix = Vec<(Planet, Planet, Vec<(Time, f32, Alignment)>)>;
 pub type ConfluentMatrix = Vec<(Time, Vec<PlanetPairAlignment>)>;
@@ -51,25 +52,31 @@ impl PlanetMatrix {
         "Start time must be before end time.",
       ));
     }
-    let mut matrix: Matrix = Vec::new();
-
     let mut planet_alignments = Vec::new();
     for planet in planets.iter() {
-      planet_alignments.push(Query::query(
+      planet_alignments.push((planet.clone(), Query::query(
         origin,
         planet,
         DataType::RightAscension,
         *start_time,
         *end_time
-      ).await.expect("failed to query planet angles"));
+      ).await.expect("failed to query planet angles")));
     }
+    Ok(Self::from_angles(&planet_alignments, start_time, end_time, alignment_margin_error, harmonics))
+  }
 
-    for (index, planet_a_alignments) in planet_alignments.iter().enumerate() {
-      for planet_b_index in (index+1)..planet_alignments.len() {
-        let planet_a = &planets[index];
-        let planet_b = &planets[planet_b_index];
-        let planet_b_alignments = planet_alignments[planet_b_index].clone();
-
+  /// Build the matrix from daily right ascension already queried for each planet.
+  /// Used to rebuild the matrix with a different `alignment_margin_error` without querying again.
+  pub fn from_angles(
+    planet_alignments: &[(Planet, Vec<(Time, f32)>)],
+    start_time: &Time,
+    end_time: &Time,
+    alignment_margin_error: f32,
+    harmonics: &[Alignment]
+  ) -> Self {
+    let mut matrix: Matrix = Vec::new();
+    for (index, (planet_a, planet_a_alignments)) in planet_alignments.iter().enumerate() {
+      for (planet_b, planet_b_alignments) in planet_alignments.iter().skip(index + 1) {
         let mut vec: Vec<(Time, f32, Alignment)> = Vec::new();
         for (
           (time, planet_a_ra),
@@ -88,11 +95,11 @@ impl PlanetMatrix {
         matrix.push((planet_a.clone(), planet_b.clone(), vec));
       }
     }
-    Ok(Self {
+    Self {
       matrix,
       start_date: *start_time,
       end_date: *end_time
-    })
+    }
   }
 
   /// Search for all alignments on a given date.
@@ -170,7 +177,7 @@ impl PlanetMatrix {
     let mut vec = Vec::<PlanetPairAlignmentWinRate>::new();
     for (planet_a, planet_b, alignments) in self.matrix.iter() {
       // index follows `Alignment` enum order
-      let mut alignment_counts = vec![0; 21];
+      let mut alignment_counts = vec![0; Alignment::to_vec().len()];
 
       for data in alignments.iter() {
         let alignment = &data.2;
@@ -268,15 +275,15 @@ impl PlanetMatrix {
   /// Win rate is across all harmonics for a given planet pair.
   pub async fn test_planet_matrix(
     ticker_data_path: &PathBuf,
-    margin_of_error_days: u32,
+    error_margin: Tolerance,
     alignment_margin_error: f32,
-    candle_range: usize,
+    swing_detector: &SwingDetector,
     planets: &Vec<Planet>,
     harmonics: &Vec<Alignment>,
   ) {
     let mut ticker_data = TickerData::new();
     ticker_data.add_csv_series(ticker_data_path).expect("Failed to add CSV to TickerData");
-    let reversals = ticker_data.find_reversals(candle_range);
+    let reversals = ticker_data.detect_reversals(swing_detector);
     if ticker_data.candles.is_empty() {
       return
     }
@@ -292,29 +299,50 @@ impl PlanetMatrix {
       planets,
       harmonics
     ).await.unwrap();
-    println!("PLANET PAIR\tALIGNMENT\tWIN RATE\tWIN EVENTS\tTOTAL EVENTS");
-
-    let alignment_counts = planet_matrix.build_planet_pair_alignment_counts();
+    let mut alignment_counts = planet_matrix.build_planet_pair_alignment_counts();
+    // signal dates of each planet pair alignment, same order as `alignment_counts`
+    let mut alignment_dates: Vec<Vec<Time>> = vec![Vec::new(); alignment_counts.len()];
 
     for (planet_a, planet_b, alignments) in planet_matrix.matrix.into_iter() {
       for (time, _ra, alignment) in alignments.iter() {
-        let planet_pair_alignment_win_rate: Option<PlanetPairAlignmentWinRate> =
-          PlanetMatrix::get_planet_pair_alignment_win_rate(&alignment_counts, &planet_a, &planet_b, alignment);
+        let index = alignment_counts.iter().position(|ppawn| {
+          ppawn.planet_1.eq(&planet_a) && ppawn.planet_2.eq(&planet_b) && ppawn.alignment.eq(alignment)
+        });
 
-        if let Some(mut ppawn) = planet_pair_alignment_win_rate {
+        if let Some(index) = index {
+          alignment_dates[index].push(*time);
           // backtest planet-pair-alignment signal against known reversals
           for reversal in reversals.iter() {
-            let range_start = time.delta_date(-(margin_of_error_days as i64));
-            let range_end = time.delta_date(margin_of_error_days as i64);
+            let (range_start, range_end) = error_margin.window(time);
             if reversal.candle.date.within_range(range_start, range_end) {
               // increment the win count for this planet pair alignment
-              ppawn.win_count += 1;
+              alignment_counts[index].win_count += 1;
               break;
             }
           }
         }
       }
     }
+
+    // compare each planet pair alignment to random dates, correcting for the number of hypotheses
+    let significance = Significance::new(
+      &reversals,
+      *earliest_candle_date,
+      *latest_candle_date,
+      error_margin,
+      DEFAULT_PERMUTATIONS
+    );
+    let mut tests = Vec::<SignificanceTest>::new();
+    for (ppawn, dates) in alignment_counts.iter().zip(alignment_dates.iter()) {
+      if ppawn.alignment_total_count > 0 {
+        let hypothesis = format!("{}-{}\t{}", ppawn.planet_1.to_str(), ppawn.planet_2.to_str(), ppawn.alignment.to_str());
+        tests.push(significance.test(&hypothesis, dates));
+      }
+    }
+    Significance::benjamini_hochberg(&mut tests);
+    println!("Baseline win rate of a random date: {:.1}%", significance.baseline_rate() * 100.0);
+    println!("PLANET PAIR\tALIGNMENT\tWIN RATE\tWIN EVENTS\tTOTAL EVENTS");
+
     // print result for each PlanetPairAlignmentCount
     for ppawn in alignment_counts.iter() {
       let win_rate = ((ppawn.win_count as f32 / ppawn.alignment_total_count as f32) * 100.0).round();
@@ -339,5 +367,7 @@ impl PlanetMatrix {
         );
       }
     }
+    println!("\t\t### SIGNIFICANCE (Benjamini-Hochberg adjusted) ###\t\t");
+    Significance::print(&tests);
   }
 }
\ No newline at end of file
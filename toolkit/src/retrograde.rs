@@ -1,24 +1,112 @@
-use std::path::PathBuf;
+use csv::WriterBuilder;
 use log::debug;
+use serde::{Deserialize, Serialize};
 use ephemeris::*;
 use time_series::*;
 
 
 pub type ConfluentRetrograde = (Time, Vec<RetrogradeEvent>);
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetrogradePhase {
+  /// Planet is moving direct over the degrees it will retrace
+  PreShadow,
+  /// Planet is moving backwards between its stations
+  Retrograde,
+  /// Planet is moving direct over the degrees it retraced
+  PostShadow,
+}
+
+/// Part of a `RetrogradeEvent` that is backtested as a signal
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetrogradeEventKind {
+  /// Date of the SR station
+  Start,
+  /// Date of the SD station
+  End,
+  /// Days the planet is within a margin of degrees of the SR station
+  StationRetrograde,
+  /// Days the planet is within a margin of degrees of the SD station
+  StationDirect,
+}
+
+impl RetrogradeEventKind {
+  pub fn to_str(&self) -> &str {
+    match self {
+      RetrogradeEventKind::Start => "Start",
+      RetrogradeEventKind::End => "End",
+      RetrogradeEventKind::StationRetrograde => "StationRetrograde",
+      RetrogradeEventKind::StationDirect => "StationDirect",
+    }
+  }
+
+  pub fn to_vec() -> Vec<RetrogradeEventKind> {
+    vec![
+      RetrogradeEventKind::Start,
+      RetrogradeEventKind::End,
+      RetrogradeEventKind::StationRetrograde,
+      RetrogradeEventKind::StationDirect,
+    ]
+  }
+}
+
+pub type RetrogradeBacktest = (Planet, RetrogradeEventKind, Backtest);
+
+/// `SignalEvent::signal` of retrograde searches saved to a `Store`.
+pub const RETROGRADE_SIGNAL: &str = "retrograde";
+
 #[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct RetrogradeEvent {
   pub planet: Planet,
+  /// Stationary retrograde (SR)
   pub start_date: Time,
   pub start_angle: f32,
+  /// Stationary direct (SD)
   pub end_date: Time,
   pub end_angle: f32,
+  /// Planet first reaches `end_angle`, the degree of the SD station.
+  /// `None` if it occurred before the queried period.
+  pub shadow_start_date: Option<Time>,
+  /// Planet returns to `start_angle`, the degree of the SR station.
+  /// `None` if it occurs after the queried period.
+  pub shadow_end_date: Option<Time>,
+  /// Mercury and Venus only: planet passes between the Earth and Sun near mid-retrograde.
+  pub inferior_conjunction: Option<Time>,
+  /// Mercury and Venus only: planet passes behind the Sun after the retrograde.
+  pub superior_conjunction: Option<Time>,
+}
+
+impl RetrogradeEvent {
+  /// Degrees retraced between the SR and SD stations.
+  pub fn span(&self) -> f32 {
+    Alignment::normalize(self.start_angle - self.end_angle)
+  }
+
+  /// Phase of the retrograde cycle on `date`, or `None` if outside the shadow period.
+  pub fn phase_on_date(&self, date: &Time) -> Option<RetrogradePhase> {
+    if date.within_range(self.start_date, self.end_date) {
+      return Some(RetrogradePhase::Retrograde);
+    }
+    if let Some(shadow_start) = self.shadow_start_date {
+      if date >= &shadow_start && date < &self.start_date {
+        return Some(RetrogradePhase::PreShadow);
+      }
+    }
+    if let Some(shadow_end) = self.shadow_end_date {
+      if date > &self.end_date && date <= &shadow_end {
+        return Some(RetrogradePhase::PostShadow);
+      }
+    }
+    None
+  }
 }
 
 #[derive(Debug, Clone)]
 pub struct Retrograde {
   pub retrogrades: Vec<RetrogradeEvent>,
+  /// Daily geocentric ecliptic longitude of each searched planet
+  pub daily_angles: Vec<(Planet, Vec<(Time, f32)>)>,
   pub start_date: Time,
   pub end_date: Time
 }
@@ -26,69 +114,212 @@ pub struct Retrograde {
 impl Retrograde {
   /// Search time period for retrograde events
   pub async fn new(start_date: Time, end_date: Time, planets: &Vec<Planet>) -> std::io::Result<Self> {
+    Self::search(None, start_date, end_date, planets).await
+  }
+
+  /// `new`, reusing a search of the same period and planets saved in `store`.
+  /// Otherwise the search reads and saves ephemerides in `store`, then is saved itself.
+  pub async fn new_cached(store: &Store, start_date: Time, end_date: Time, planets: &[Planet]) -> std::io::Result<Self> {
+    let parameters = serde_json::json!({
+      "start_date": start_date.as_string(),
+      "end_date": end_date.as_string(),
+      "planets": planets.iter().map(|planet| planet.to_str()).collect::<Vec<&str>>(),
+    }).to_string();
+    if store.has_signal_events(RETROGRADE_SIGNAL, &parameters)? {
+      let mut retrogrades = Vec::<RetrogradeEvent>::new();
+      for event in store.load_signal_events(RETROGRADE_SIGNAL, Some(&parameters))?.iter() {
+        if let Some(detail) = &event.detail {
+          retrogrades.push(serde_json::from_str(detail)?);
+        }
+      }
+      debug!("{} retrogrades read from store", retrogrades.len());
+      // the search saved these ephemerides in `store`
+      let mut daily_angles = Vec::new();
+      for planet in planets.iter() {
+        daily_angles.push((planet.clone(), Self::query(Some(store), Origin::Geocentric, planet, start_date, end_date).await?));
+      }
+      return Ok(Self {
+        retrogrades,
+        daily_angles,
+        start_date,
+        end_date
+      });
+    }
+
+    let retrograde = Self::search(Some(store), start_date, end_date, planets).await?;
+    // the search row marks the period as searched even if no planet turned retrograde
+    let mut events = vec![SignalEvent {
+      signal: RETROGRADE_SIGNAL.to_string(),
+      parameters: parameters.clone(),
+      date: start_date,
+      label: "Search".to_string(),
+      value: None,
+      detail: None
+    }];
+    for event in retrograde.retrogrades.iter() {
+      events.push(SignalEvent {
+        signal: RETROGRADE_SIGNAL.to_string(),
+        parameters: parameters.clone(),
+        date: event.start_date,
+        label: event.planet.to_str().to_string(),
+        value: Some(event.start_angle as f64),
+        detail: Some(serde_json::to_string(event)?)
+      });
+    }
+    store.delete_signal_events(RETROGRADE_SIGNAL, &parameters)?;
+    store.upsert_signal_events(&events)?;
+    Ok(retrograde)
+  }
+
+  /// Query Horizons, through `store` if given.
+  async fn query(
+    store: Option<&Store>,
+    origin: Origin,
+    planet: &Planet,
+    start_date: Time,
+    end_date: Time
+  ) -> std::io::Result<Vec<(Time, f32)>> {
+    match store {
+      Some(store) => Query::query_cached(store, origin, planet, DataType::EclipticLongitude, start_date, end_date).await,
+      None => Query::query(origin, planet, DataType::EclipticLongitude, start_date, end_date).await,
+    }
+  }
+
+  async fn search(store: Option<&Store>, start_date: Time, end_date: Time, planets: &[Planet]) -> std::io::Result<Self> {
     if start_date > end_date {
       return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Start date must be before end date"));
     }
     let mut retrogrades = Vec::new();
+    let mut planet_angles = Vec::new();
+    // Earth's heliocentric position is opposite the Sun's geocentric position
+    let mut earth_heliocentric: Option<Vec<f32>> = None;
     for planet in planets.iter() {
-      let daily_angles = Query::query(
-        Origin::Geocentric,
-        planet,
-        DataType::RightAscension,
-        start_date,
-        end_date
-      ).await.expect("failed to query planet angles");
+      let daily_angles = Self::query(store, Origin::Geocentric, planet, start_date, end_date).await?;
+      let unwrapped_angles = Self::unwrap_angles(&daily_angles);
 
-      // retrograde identified as longitude decreasing (except)
-      // covers case where angle passes through 360
+      // angle of planet from Earth as seen from the Sun, for inferior/superior conjunctions
+      let mut conjunction_angles: Option<Vec<f32>> = None;
+      if *planet == Planet::Mercury || *planet == Planet::Venus {
+        if earth_heliocentric.is_none() {
+          let sun = Self::query(store, Origin::Geocentric, &Planet::Sun, start_date, end_date).await?;
+          earth_heliocentric = Some(sun.iter().map(|(_, angle)| Alignment::normalize(angle + 180.0)).collect());
+        }
+        let heliocentric = Self::query(store, Origin::Heliocentric, planet, start_date, end_date).await?;
+        conjunction_angles = earth_heliocentric.as_ref().map(|earth| {
+          heliocentric.iter().zip(earth.iter())
+            .map(|((_, planet_angle), earth_angle)| Alignment::normalize(planet_angle - earth_angle))
+            .collect()
+        });
+      }
+
+      // retrograde identified as longitude decreasing
+      let mut stations = Vec::<(usize, usize)>::new();
       let mut in_retrograde = false;
-      let mut retro_start_date: Option<Time> = None;
-      let mut retro_start_angle: Option<f32> = None;
-      for index in 0..daily_angles.len() - 1 {
-        let (date, angle) = daily_angles[index];
-        let (_, next_angle) = daily_angles[index + 1];
+      let mut retro_start_index: Option<usize> = None;
+      for (index, pair) in daily_angles.windows(2).enumerate() {
+        let ((_, angle), (_, next_angle)) = (pair[0], pair[1]);
         // start of retrograde
         if Self::is_retrograde(angle, next_angle) && !in_retrograde {
           in_retrograde = true;
-          retro_start_date = Some(date);
-          retro_start_angle = Some(angle);
+          retro_start_index = Some(index);
           continue;
         }
         // end of retrograde
         else if !Self::is_retrograde(angle, next_angle) && in_retrograde {
           in_retrograde = false;
-          if retro_start_date.is_some() && retro_start_angle.is_some() {
-            let start_date = retro_start_date.expect("failed to unwrap retrograde start date");
-            let start_angle = retro_start_angle.expect("failed to unwrap retrograde start angle");
-            retrogrades.push(RetrogradeEvent {
-              planet: planet.clone(),
-              start_date,
-              start_angle,
-              end_date: date,
-              end_angle: angle,
-            });
-            debug!(
-              "{}\t{} at {} to {} at {}",
-              planet.to_str(), start_date.as_string(), start_angle, date.as_string(), angle
-            );
-            retro_start_date = None;
-            retro_start_angle = None;
-          } else if retro_start_date.is_none() {
-            panic!("Retrograde start date not set");
-          }
-          else if retro_start_angle.is_none() {
-            panic!("Retrograde start angle not set");
-          }
+          let start_index = retro_start_index.take().expect("Retrograde start date not set");
+          stations.push((start_index, index));
         }
       }
+
+      for (station_index, (start_index, end_index)) in stations.iter().enumerate() {
+        let (start_date, start_angle) = daily_angles[*start_index];
+        let (end_date, end_angle) = daily_angles[*end_index];
+        // last date before the SR station the planet was short of the SD degree
+        let shadow_start_date = (0..*start_index).rev()
+          .find(|index| unwrapped_angles[*index] < unwrapped_angles[*end_index])
+          .map(|index| daily_angles[index + 1].0);
+        // first date after the SD station the planet is back at the SR degree
+        let shadow_end_date = (*end_index..daily_angles.len())
+          .find(|index| unwrapped_angles[*index] >= unwrapped_angles[*start_index])
+          .map(|index| daily_angles[index].0);
+
+        let mut inferior_conjunction = None;
+        let mut superior_conjunction = None;
+        if let Some(angles) = &conjunction_angles {
+          inferior_conjunction = Self::closest_to_angle(angles, *start_index, *end_index, 0.0)
+            .map(|index| daily_angles[index].0);
+          // superior conjunction is between this SD station and the next SR station
+          let next_start_index = stations.get(station_index + 1)
+            .map_or(daily_angles.len() - 1, |(next_start, _)| *next_start);
+          superior_conjunction = Self::closest_to_angle(angles, *end_index, next_start_index, 180.0)
+            .map(|index| daily_angles[index].0);
+        }
+
+        debug!(
+          "{}\t{} at {} to {} at {}",
+          planet.to_str(), start_date.as_string(), start_angle, end_date.as_string(), end_angle
+        );
+        retrogrades.push(RetrogradeEvent {
+          planet: planet.clone(),
+          start_date,
+          start_angle,
+          end_date,
+          end_angle,
+          shadow_start_date,
+          shadow_end_date,
+          inferior_conjunction,
+          superior_conjunction,
+        });
+      }
+      planet_angles.push((planet.clone(), daily_angles));
     }
     Ok(Self {
       retrogrades,
+      daily_angles: planet_angles,
       start_date,
       end_date
     })
   }
 
+  /// Remove the jump at 360 degrees so angles increase continuously with direct motion.
+  fn unwrap_angles(daily_angles: &[(Time, f32)]) -> Vec<f32> {
+    let mut unwrapped = Vec::<f32>::with_capacity(daily_angles.len());
+    for (index, (_, angle)) in daily_angles.iter().enumerate() {
+      match index {
+        0 => unwrapped.push(*angle),
+        _ => {
+          let previous = unwrapped[index - 1];
+          unwrapped.push(previous + Self::angle_change(daily_angles[index - 1].1, *angle));
+        }
+      }
+    }
+    unwrapped
+  }
+
+  /// Index in `start_index..=end_index` where `angles` crosses `target`, closest to exact.
+  /// `None` if `angles` never crosses `target` in that range.
+  fn closest_to_angle(angles: &[f32], start_index: usize, end_index: usize, target: f32) -> Option<usize> {
+    let end_index = end_index.min(angles.len() - 1);
+    let offset = |index: usize| Self::angle_change(target, angles[index]);
+    (start_index..end_index)
+      // ignore the jump where `angles` passes through the opposite of `target`
+      .find(|index| offset(*index).signum() != offset(index + 1).signum() && offset(*index).abs() < 90.0)
+      .map(|index| if offset(index).abs() <= offset(index + 1).abs() { index } else { index + 1 })
+  }
+
+  /// Signed change from `first` to `second` in (-180, 180].
+  fn angle_change(first: f32, second: f32) -> f32 {
+    let change = second - first;
+    if change > 180.0 {
+      change - 360.0
+    } else if change <= -180.0 {
+      change + 360.0
+    } else {
+      change
+    }
+  }
+
   // TODO: identify when >1 retrograde events happens within error margin of days
   pub fn confluent_retrograde(&self, error_margin_days: u8) -> Vec<ConfluentRetrograde> {
     // guaranteed to be positive i64 because of error catch in Self::new()
@@ -113,13 +344,129 @@ impl Retrograde {
     confluent_retrogrades
   }
 
+  /// Backtest each `RetrogradeEvent` against price reversals of `ticker_data`.
+  /// The SR and SD dates, and the days the planet is within `station_margin_degrees`
+  /// of each station, are signals. A "win" is a reversal within `error_margin` of a signal.
+  /// Results are aggregated per planet and `RetrogradeEventKind`, and written to `RETROGRADE_RESULTS_CSV`.
+  pub fn backtest(
+    &self,
+    ticker_data: &TickerData,
+    swing_detector: &SwingDetector,
+    station_margin_degrees: f32,
+    error_margin: Tolerance
+  ) -> std::io::Result<Vec<RetrogradeBacktest>> {
+    let reversals = ticker_data.detect_reversals(swing_detector);
+
+    let mut results = Vec::<RetrogradeBacktest>::new();
+    let mut planets = Vec::<Planet>::new();
+    for retrograde in self.retrogrades.iter() {
+      if !planets.contains(&retrograde.planet) {
+        planets.push(retrograde.planet.clone());
+      }
+    }
+    for planet in planets.iter() {
+      let daily_angles = self.daily_angles.iter()
+        .find(|(searched, _)| searched == planet)
+        .map_or(&[][..], |(_, angles)| angles.as_slice());
+      let mut backtests: Vec<RetrogradeBacktest> = RetrogradeEventKind::to_vec().into_iter()
+        .map(|kind| (planet.clone(), kind, Backtest::default()))
+        .collect();
+
+      for retrograde in self.retrogrades.iter().filter(|retrograde| &retrograde.planet == planet) {
+        let mut signals = vec![
+          (RetrogradeEventKind::Start, retrograde.start_date, retrograde.start_date, retrograde.start_angle),
+          (RetrogradeEventKind::End, retrograde.end_date, retrograde.end_date, retrograde.end_angle),
+        ];
+        signals.extend(Self::station_window(daily_angles, RetrogradeEventKind::StationRetrograde, &retrograde.start_date, station_margin_degrees));
+        signals.extend(Self::station_window(daily_angles, RetrogradeEventKind::StationDirect, &retrograde.end_date, station_margin_degrees));
+        for (kind, window_start, window_end, angle) in signals.into_iter() {
+          let (_, _, backtest) = backtests.iter_mut()
+            .find(|(_, backtest_kind, _)| backtest_kind == &kind)
+            .expect("failed to find backtest for retrograde event kind");
+          let (range_start, _) = error_margin.window(&window_start);
+          let (_, range_end) = error_margin.window(&window_end);
+          // signal must be within price history to be tested
+          if ticker_data.candles.is_empty()
+            || range_end < *ticker_data.earliest_date()
+            || range_start > *ticker_data.latest_date() {
+            continue;
+          }
+          backtest.increment_total_count();
+          if let Some(reversal) = reversals.iter().find(|reversal| reversal.candle.date.within_range(range_start, range_end)) {
+            backtest.increment_win_count();
+            backtest.add_signal((planet.clone(), angle, reversal.candle.clone()));
+          }
+        }
+      }
+      results.extend(backtests);
+    }
+
+    let mut wtr = WriterBuilder::new()
+      .has_headers(false)
+      .from_path(RETROGRADE_RESULTS_CSV)?;
+    wtr.write_record(["planet", "event", "win_rate", "win_count", "total_count"])?;
+    println!("PLANET\tEVENT\t\t\tWIN RATE\tWIN EVENTS\tTOTAL EVENTS");
+    for (planet, kind, backtest) in results.iter() {
+      let win_rate = (backtest.get_win_rate() * 100.0).round();
+      println!(
+        "{}\t{}\t\t{}%\t\t{}\t\t{}",
+        planet.to_str(),
+        kind.to_str(),
+        win_rate,
+        backtest.get_win_count(),
+        backtest.get_total_count()
+      );
+      wtr.write_record(&[
+        planet.to_str().to_string(),
+        kind.to_str().to_string(),
+        format!("{}", backtest.get_win_rate()),
+        backtest.get_win_count().to_string(),
+        backtest.get_total_count().to_string(),
+      ])?;
+    }
+    wtr.flush()?;
+    Ok(results)
+  }
+
+  /// Contiguous days around `station_date` the planet is within `margin_degrees` of its station angle.
+  /// Returns (kind, first date, last date, station angle), or `None` if `station_date` isn't in `daily_angles`.
+  pub(crate) fn station_window(
+    daily_angles: &[(Time, f32)],
+    kind: RetrogradeEventKind,
+    station_date: &Time,
+    margin_degrees: f32
+  ) -> Option<(RetrogradeEventKind, Time, Time, f32)> {
+    let station_index = daily_angles.iter().position(|(date, _)| date == station_date)?;
+    let station_angle = daily_angles[station_index].1;
+    let near_station = |index: &usize| Self::angle_change(station_angle, daily_angles[*index].1).abs() <= margin_degrees;
+    let first_index = (0..station_index).rev()
+      .take_while(near_station)
+      .last()
+      .unwrap_or(station_index);
+    let last_index = (station_index + 1..daily_angles.len())
+      .take_while(near_station)
+      .last()
+      .unwrap_or(station_index);
+    Some((kind, daily_angles[first_index].0, daily_angles[last_index].0, station_angle))
+  }
+
+  /// Angle decreased from `first` to `second`, allowing for the angle passing through 360.
   pub fn is_retrograde(first: f32, second: f32) -> bool {
-    second - first < 0.0
+    Self::angle_change(first, second) < 0.0
   }
 
   pub fn print(&self) {
+    println!("PLANET\tSHADOW START\tSR\t\tSD\t\tSHADOW END\tSPAN");
     for retrograde in self.retrogrades.iter() {
-      println!("{}\t{}\t{}", retrograde.planet.to_str(), retrograde.start_date.as_string(), retrograde.end_date.as_string());
+      println!(
+        "{}\t{}\t{}\t{}\t{}\t{:.2}",
+        retrograde.planet.to_str(),
+        retrograde.shadow_start_date.map_or("-".to_string(), |date| date.as_string()),
+        retrograde.start_date.as_string(),
+        retrograde.end_date.as_string(),
+        retrograde.shadow_end_date.map_or("-".to_string(), |date| date.as_string()),
+        retrograde.span()
+      );
     }
   }
 }
\ No newline at end of file
@@ -0,0 +1,216 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use time_series::{Reversal, Time, Tolerance};
+
+/// Seed for permutation tests, so repeated runs report the same p-values.
+pub const PERMUTATION_SEED: u64 = 42;
+/// Random signal sets per permutation test when a backtest doesn't specify.
+pub const DEFAULT_PERMUTATIONS: usize = 1000;
+
+/// Win rate of one hypothesis (a list of signal dates) compared to random placement.
+#[derive(Debug, Clone)]
+pub struct SignificanceTest {
+  pub hypothesis: String,
+  pub win_count: u64,
+  pub total_count: u64,
+  pub win_rate: f64,
+  /// Probability a random date is within the error margin of a reversal
+  pub baseline_rate: f64,
+  /// `win_rate` / `baseline_rate`
+  pub lift: f64,
+  /// Probability of at least `win_count` wins if each signal independently wins at `baseline_rate`
+  pub binomial_p_value: f64,
+  /// Fraction of circularly shifted signal sets with at least `win_count` wins
+  pub permutation_p_value: f64,
+  /// `binomial_p_value` adjusted by `Significance::bonferroni` or `Significance::benjamini_hochberg`
+  pub adjusted_p_value: Option<f64>,
+}
+
+/// Null model for "signal date lands within `error_margin` of a reversal".
+/// With a wide error margin and a wide reversal definition, random dates also win often;
+/// every win rate should be compared to this baseline.
+#[derive(Debug, Clone)]
+pub struct Significance {
+  pub start_date: Time,
+  pub end_date: Time,
+  pub error_margin: Tolerance,
+  /// Number of random signal sets per permutation test
+  pub permutations: usize,
+  /// For each day of the period: is it within `error_margin` of a reversal
+  hits: Vec<bool>,
+}
+
+impl Significance {
+  pub fn new(
+    reversals: &[Reversal],
+    start_date: Time,
+    end_date: Time,
+    error_margin: Tolerance,
+    permutations: usize
+  ) -> Self {
+    let period = start_date.diff_days(&end_date).max(0) as usize + 1;
+    let mut hits = vec![false; period];
+    for reversal in reversals.iter() {
+      let (range_start, range_end) = error_margin.window(&reversal.candle.date);
+      for index in start_date.diff_days(&range_start)..=start_date.diff_days(&range_end) {
+        if index >= 0 && (index as usize) < period {
+          hits[index as usize] = true;
+        }
+      }
+    }
+    Self {
+      start_date,
+      end_date,
+      error_margin,
+      permutations,
+      hits
+    }
+  }
+
+  /// Fraction of days in the period within `error_margin` of a reversal.
+  pub fn baseline_rate(&self) -> f64 {
+    self.hits.iter().filter(|hit| **hit).count() as f64 / self.hits.len() as f64
+  }
+
+  /// Signal date is within `error_margin` of a reversal.
+  pub fn is_hit(&self, date: &Time) -> bool {
+    match self.day_index(date) {
+      Some(index) => self.hits[index],
+      None => false
+    }
+  }
+
+  fn day_index(&self, date: &Time) -> Option<usize> {
+    let index = self.start_date.diff_days(date);
+    if index >= 0 && (index as usize) < self.hits.len() {
+      Some(index as usize)
+    } else {
+      None
+    }
+  }
+
+  /// Test a hypothesis: are `signal_dates` closer to reversals than random dates?
+  /// Signals outside the period are ignored.
+  pub fn test(&self, hypothesis: &str, signal_dates: &[Time]) -> SignificanceTest {
+    let indices: Vec<usize> = signal_dates.iter().filter_map(|date| self.day_index(date)).collect();
+    let total_count = indices.len() as u64;
+    let win_count = indices.iter().filter(|index| self.hits[**index]).count() as u64;
+    let win_rate = if total_count > 0 { win_count as f64 / total_count as f64 } else { 0.0 };
+    let baseline_rate = self.baseline_rate();
+    let lift = if baseline_rate > 0.0 { win_rate / baseline_rate } else { 0.0 };
+    SignificanceTest {
+      hypothesis: hypothesis.to_string(),
+      win_count,
+      total_count,
+      win_rate,
+      baseline_rate,
+      lift,
+      binomial_p_value: Self::binomial_p_value(win_count, total_count, baseline_rate),
+      permutation_p_value: self.permutation_p_value(&indices, win_count),
+      adjusted_p_value: None,
+    }
+  }
+
+  /// Shift all signals by the same random number of days (wrapping around the period)
+  /// and count wins. Keeps the spacing between signals, which matters for clustered astro events.
+  fn permutation_p_value(&self, indices: &[usize], win_count: u64) -> f64 {
+    if indices.is_empty() || self.permutations == 0 {
+      return 1.0;
+    }
+    let period = self.hits.len();
+    let mut rng = StdRng::seed_from_u64(PERMUTATION_SEED);
+    let mut at_least_as_extreme = 0;
+    for _ in 0..self.permutations {
+      let shift = rng.gen_range(0..period);
+      let wins = indices.iter().filter(|index| self.hits[(**index + shift) % period]).count() as u64;
+      if wins >= win_count {
+        at_least_as_extreme += 1;
+      }
+    }
+    (at_least_as_extreme + 1) as f64 / (self.permutations + 1) as f64
+  }
+
+  /// Probability of at least `wins` successes in `total` trials with success probability `p`.
+  pub fn binomial_p_value(wins: u64, total: u64, p: f64) -> f64 {
+    if wins == 0 {
+      return 1.0;
+    }
+    if p <= 0.0 {
+      return 0.0;
+    }
+    if p >= 1.0 {
+      return 1.0;
+    }
+    let mut p_value = 0.0;
+    for k in wins..=total {
+      let ln_probability = Self::ln_choose(total, k) + k as f64 * p.ln() + (total - k) as f64 * (1.0 - p).ln();
+      p_value += ln_probability.exp();
+    }
+    p_value.min(1.0)
+  }
+
+  fn ln_choose(n: u64, k: u64) -> f64 {
+    Self::ln_gamma(n as f64 + 1.0) - Self::ln_gamma(k as f64 + 1.0) - Self::ln_gamma((n - k) as f64 + 1.0)
+  }
+
+  /// Lanczos approximation of ln(Γ(x)) for x > 0
+  fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+      76.18009172947146,
+      -86.50532032941677,
+      24.01409824083091,
+      -1.231739572450155,
+      0.1208650973866179e-2,
+      -0.5395239384953e-5,
+    ];
+    let mut y = x;
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for coefficient in COEFFICIENTS.iter() {
+      y += 1.0;
+      series += coefficient / y;
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+  }
+
+  /// Family-wise error correction: multiply each p-value by the number of hypotheses.
+  pub fn bonferroni(tests: &mut [SignificanceTest]) {
+    let count = tests.len() as f64;
+    for test in tests.iter_mut() {
+      test.adjusted_p_value = Some((test.binomial_p_value * count).min(1.0));
+    }
+  }
+
+  /// False discovery rate correction across hypotheses.
+  pub fn benjamini_hochberg(tests: &mut [SignificanceTest]) {
+    let count = tests.len();
+    let mut order: Vec<usize> = (0..count).collect();
+    order.sort_by(|a, b| tests[*a].binomial_p_value.partial_cmp(&tests[*b].binomial_p_value).unwrap());
+    let mut running_min: f64 = 1.0;
+    for rank in (0..count).rev() {
+      let index = order[rank];
+      let adjusted = tests[index].binomial_p_value * count as f64 / (rank + 1) as f64;
+      running_min = running_min.min(adjusted);
+      tests[index].adjusted_p_value = Some(running_min.min(1.0));
+    }
+  }
+
+  pub fn print(tests: &[SignificanceTest]) {
+    println!("HYPOTHESIS\t\tWIN RATE\tBASELINE\tLIFT\tWINS\tTOTAL\tP-VALUE\tPERMUTATION\tADJUSTED");
+    for test in tests.iter() {
+      println!(
+        "{}\t\t{:.1}%\t\t{:.1}%\t\t{:.2}\t{}\t{}\t{:.4}\t{:.4}\t\t{}",
+        test.hypothesis,
+        test.win_rate * 100.0,
+        test.baseline_rate * 100.0,
+        test.lift,
+        test.win_count,
+        test.total_count,
+        test.binomial_p_value,
+        test.permutation_p_value,
+        test.adjusted_p_value.map_or("-".to_string(), |p| format!("{:.4}", p))
+      );
+    }
+  }
+}
@@ -0,0 +1,279 @@
+use std::collections::{HashMap, HashSet};
+use log::debug;
+use ephemeris::*;
+use time_series::*;
+use time_series::Reversal;
+
+/// Where a planet pair is in its synodic cycle on one date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SynodicPhasePoint {
+  pub date: Time,
+  /// Ecliptic degrees the faster planet is ahead of the slower one, in [0, 360): 0 at conjunction,
+  /// 90 at the waxing square, 180 at opposition, 270 at the waning square
+  pub phase: f32,
+  /// Conjunctions passed since the start date; 0 until the first
+  pub cycle: usize,
+}
+
+/// Day the phase crossed 0°, the closer of the two days either side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conjunction {
+  pub date: Time,
+  /// Cycle starting at this conjunction. Retrograde loops can cross 0° three times;
+  /// only the first crossing starts a new cycle, the others repeat its number.
+  pub cycle: usize,
+}
+
+/// Continuous synodic phase of a planet pair, unlike `PlanetMatrix` which only records aspect hits.
+/// Suited to pairs whose separation circles the zodiac; Mercury and Venus only oscillate around the Sun.
+#[derive(Debug, Clone)]
+pub struct SynodicPhase {
+  /// Faster planet by average motion over the period, then the slower
+  pub planets: (Planet, Planet),
+  pub phases: Vec<SynodicPhasePoint>,
+  pub conjunctions: Vec<Conjunction>,
+}
+
+/// Price behaviour while a pair's phase is within `start` to `end` degrees, over every cycle.
+#[derive(Debug, Clone)]
+pub struct PhaseBucketStats {
+  pub start: f32,
+  pub end: f32,
+  /// Trading days with a return in the bucket
+  pub days: usize,
+  /// Distinct cycles with days in the bucket
+  pub cycles: usize,
+  /// Mean close to close return of those days
+  pub mean_return: f64,
+  /// Standard deviation of those returns
+  pub volatility: f64,
+  /// Fraction of those days that closed up
+  pub up_rate: f64,
+  pub tops: usize,
+  pub bottoms: usize,
+  /// Share of all tops in the bucket over its share of days; above 1 if tops cluster here
+  pub top_lift: f64,
+  pub bottom_lift: f64,
+}
+
+impl SynodicPhase {
+  /// Query the ecliptic longitude of both planets as seen from `origin` and track their phase.
+  pub async fn new(
+    origin: Origin,
+    planet_a: &Planet,
+    planet_b: &Planet,
+    start_date: Time,
+    end_date: Time
+  ) -> std::io::Result<Self> {
+    let angles_a = Query::query(origin, planet_a, DataType::EclipticLongitude, start_date, end_date).await?;
+    let angles_b = Query::query(origin, planet_b, DataType::EclipticLongitude, start_date, end_date).await?;
+    Ok(Self::from_angles((planet_a, &angles_a), (planet_b, &angles_b)))
+  }
+
+  /// Every pair of `planet_angles`, as `PlanetMatrix::from_angles` compares them.
+  pub fn pairs(planet_angles: &[(Planet, Vec<(Time, f32)>)]) -> Vec<Self> {
+    let mut pairs = Vec::new();
+    for (index, (planet_a, angles_a)) in planet_angles.iter().enumerate() {
+      for (planet_b, angles_b) in planet_angles.iter().skip(index + 1) {
+        pairs.push(Self::from_angles((planet_a, angles_a), (planet_b, angles_b)));
+      }
+    }
+    pairs
+  }
+
+  /// Phase on each date both planets have an angle for.
+  pub fn from_angles(a: (&Planet, &[(Time, f32)]), b: (&Planet, &[(Time, f32)])) -> Self {
+    let angles_b: HashMap<i64, f32> = b.1.iter().map(|(date, angle)| (date.to_unix(), *angle)).collect();
+    let paired: Vec<(Time, f64, f64)> = a.1.iter()
+      .filter_map(|(date, angle_a)| angles_b.get(&date.to_unix()).map(|angle_b| (*date, *angle_a as f64, *angle_b as f64)))
+      .collect();
+
+    // direct motion is mostly positive, so whichever covered more degrees is faster
+    let travel = |angles: Vec<f64>| angles.windows(2).map(|pair| Self::angle_change(pair[0], pair[1])).sum::<f64>();
+    let a_is_faster = travel(paired.iter().map(|(_, a, _)| *a).collect()) >= travel(paired.iter().map(|(_, _, b)| *b).collect());
+    let planets = if a_is_faster { (a.0.clone(), b.0.clone()) } else { (b.0.clone(), a.0.clone()) };
+    let separations: Vec<(Time, f64)> = paired.iter()
+      .map(|(date, a, b)| (*date, if a_is_faster { a - b } else { b - a }.rem_euclid(360.0)))
+      .collect();
+
+    let mut phases = Vec::<SynodicPhasePoint>::new();
+    let mut conjunctions = Vec::<Conjunction>::new();
+    // degrees since the first conjunction before the start, so each multiple of 360 is a conjunction
+    let mut unwrapped = separations.first().map_or(0.0, |(_, separation)| *separation);
+    let mut highest_lap = 0;
+    let mut cycle = 0;
+    for (index, (date, separation)) in separations.iter().enumerate() {
+      if index > 0 {
+        let previous = unwrapped;
+        unwrapped += Self::angle_change(separations[index - 1].1, *separation);
+        let (previous_lap, lap) = ((previous / 360.0).floor() as i64, (unwrapped / 360.0).floor() as i64);
+        if lap != previous_lap {
+          if lap > highest_lap {
+            highest_lap = lap;
+            cycle += 1;
+          }
+          // the day either side nearest to 0°
+          let crossing = lap.max(previous_lap) as f64 * 360.0;
+          let conjunction_date = if (previous - crossing).abs() < (unwrapped - crossing).abs() {
+            separations[index - 1].0
+          } else {
+            *date
+          };
+          conjunctions.push(Conjunction {
+            date: conjunction_date,
+            cycle
+          });
+        }
+      }
+      phases.push(SynodicPhasePoint {
+        date: *date,
+        phase: unwrapped.rem_euclid(360.0) as f32,
+        cycle
+      });
+    }
+    debug!(
+      "{}-{}: {} conjunctions over {} days",
+      planets.0.to_str(),
+      planets.1.to_str(),
+      conjunctions.len(),
+      phases.len()
+    );
+    Self {
+      planets,
+      phases,
+      conjunctions
+    }
+  }
+
+  /// Degrees moved from `first` to `second`, in (-180, 180].
+  fn angle_change(first: f64, second: f64) -> f64 {
+    let change = (second - first).rem_euclid(360.0);
+    if change > 180.0 { change - 360.0 } else { change }
+  }
+
+  pub fn phase_on(&self, date: &Time) -> Option<&SynodicPhasePoint> {
+    self.phases.iter().find(|point| point.date == *date)
+  }
+
+  /// Mean days between the conjunctions that start a cycle.
+  pub fn mean_cycle_days(&self) -> Option<f64> {
+    let mut starts = Vec::<&Time>::new();
+    let mut last_cycle = 0;
+    for conjunction in self.conjunctions.iter() {
+      if conjunction.cycle > last_cycle {
+        starts.push(&conjunction.date);
+        last_cycle = conjunction.cycle;
+      }
+    }
+    if starts.len() < 2 {
+      return None;
+    }
+    Some(starts[0].diff_days(starts[starts.len() - 1]) as f64 / (starts.len() - 1) as f64)
+  }
+
+  /// Daily returns and reversals of `ticker_data` grouped by phase into buckets of `bucket_degrees`.
+  /// A day's return is from the previous date's close, so it falls in the phase of the day it ends on.
+  pub fn bucket_stats(&self, ticker_data: &TickerData, bucket_degrees: f32, reversals: &[Reversal]) -> std::io::Result<Vec<PhaseBucketStats>> {
+    if !(bucket_degrees > 0.0 && bucket_degrees <= 360.0) {
+      return Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("Bucket degrees must be in (0, 360], got {}", bucket_degrees)
+      ));
+    }
+    let bucket_count = (360.0 / bucket_degrees).ceil().max(1.0) as usize;
+    let bucket_of = |phase: f32| ((phase / bucket_degrees) as usize).min(bucket_count - 1);
+    let phases: HashMap<i64, &SynodicPhasePoint> = self.phases.iter().map(|point| (point.date.to_unix(), point)).collect();
+
+    let mut closes = Vec::<(Time, f64)>::new();
+    for candle in ticker_data.candles.iter() {
+      match closes.last_mut() {
+        Some(last) if last.0 == candle.date => last.1 = candle.close,
+        _ => closes.push((candle.date, candle.close)),
+      }
+    }
+    let mut returns = vec![Vec::<f64>::new(); bucket_count];
+    let mut cycles = vec![HashSet::<usize>::new(); bucket_count];
+    for pair in closes.windows(2) {
+      if let Some(point) = phases.get(&pair[1].0.to_unix()) {
+        let bucket = bucket_of(point.phase);
+        returns[bucket].push(pair[1].1 / pair[0].1 - 1.0);
+        cycles[bucket].insert(point.cycle);
+      }
+    }
+    let mut tops = vec![0; bucket_count];
+    let mut bottoms = vec![0; bucket_count];
+    for reversal in reversals.iter() {
+      if let Some(point) = phases.get(&reversal.candle.date.to_unix()) {
+        match reversal.reversal_type {
+          ReversalType::Top => tops[bucket_of(point.phase)] += 1,
+          ReversalType::Bottom => bottoms[bucket_of(point.phase)] += 1,
+        }
+      }
+    }
+
+    let total_days: usize = returns.iter().map(|bucket| bucket.len()).sum();
+    let total_tops: usize = tops.iter().sum();
+    let total_bottoms: usize = bottoms.iter().sum();
+    let lift = |count: usize, total: usize, days: usize| {
+      if total == 0 || days == 0 {
+        0.0
+      } else {
+        (count as f64 / total as f64) / (days as f64 / total_days as f64)
+      }
+    };
+    Ok((0..bucket_count)
+      .map(|bucket| {
+        let values = &returns[bucket];
+        let days = values.len();
+        let mean_return = if days > 0 { values.iter().sum::<f64>() / days as f64 } else { 0.0 };
+        let volatility = if days > 1 {
+          (values.iter().map(|value| (value - mean_return).powi(2)).sum::<f64>() / (days - 1) as f64).sqrt()
+        } else {
+          0.0
+        };
+        PhaseBucketStats {
+          start: bucket as f32 * bucket_degrees,
+          end: ((bucket + 1) as f32 * bucket_degrees).min(360.0),
+          days,
+          cycles: cycles[bucket].len(),
+          mean_return,
+          volatility,
+          up_rate: if days > 0 { values.iter().filter(|value| **value > 0.0).count() as f64 / days as f64 } else { 0.0 },
+          tops: tops[bucket],
+          bottoms: bottoms[bucket],
+          top_lift: lift(tops[bucket], total_tops, days),
+          bottom_lift: lift(bottoms[bucket], total_bottoms, days),
+        }
+      })
+      .collect())
+  }
+
+  pub fn print_conjunctions(&self) {
+    println!("{}-{} CONJUNCTIONS", self.planets.0.to_str().to_uppercase(), self.planets.1.to_str().to_uppercase());
+    println!("DATE\t\tCYCLE");
+    for conjunction in self.conjunctions.iter() {
+      println!("{}\t{}", conjunction.date.as_string(), conjunction.cycle);
+    }
+  }
+
+  pub fn print_bucket_stats(&self, stats: &[PhaseBucketStats]) {
+    println!("{}-{} PHASE", self.planets.0.to_str().to_uppercase(), self.planets.1.to_str().to_uppercase());
+    println!("PHASE\t\tDAYS\tCYCLES\tMEAN RETURN\tVOLATILITY\tUP RATE\tTOPS\tTOP LIFT\tBOTTOMS\tBOTTOM LIFT");
+    for bucket in stats.iter() {
+      println!(
+        "{:.0}-{:.0}\t\t{}\t{}\t{:.3}%\t\t{:.3}%\t\t{:.1}%\t{}\t{:.2}\t\t{}\t{:.2}",
+        bucket.start,
+        bucket.end,
+        bucket.days,
+        bucket.cycles,
+        bucket.mean_return * 100.0,
+        bucket.volatility * 100.0,
+        bucket.up_rate * 100.0,
+        bucket.tops,
+        bucket.top_lift,
+        bucket.bottoms,
+        bucket.bottom_lift
+      );
+    }
+  }
+}